@@ -69,7 +69,6 @@ ArtistsPageEmpty = Žádní umělci
 TrackLibrary = Knihovna skladeb
 TrackInputPlaceholder = Vložit informace o skladbě
 SearchFilter = Vyhledat pomocí
-SearchFilterSpecify = Pomocí {$filter}
 
 # Settings Page
 MusicDirectory = Adresář hudby
@@ -87,5 +86,4 @@ FooterToggle = Přepnout mini-přehrávač
 Theme = Motiv
 
 # Error
-SearchFailed = Vyhledávání selhalo!
 ScanFileDoesNotExist = Adresář s hudbou nebyl nalezen
\ No newline at end of file
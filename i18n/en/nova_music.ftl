@@ -10,6 +10,8 @@ settings = Settings
 home = Home
 tracks = Tracks
 artists = Artists
+composers = Composers
+genres = Genres
 albums = Albums
 playlists = Playlists
 Loading = Loading...
@@ -20,6 +22,18 @@ title = Title
 artist = Artist
 pageresults = Results {$number}
 singles = Singles
+AppearsOn = Appears on
+CreditedTracks = Credited On
+RolePrimary = Artist
+RoleFeatured = Featured
+RoleRemixer = Remixer
+RoleComposer = Composer
+RoleConductor = Conductor
+RolePerformer = Performer
+TotalRuntime = {$count ->
+    [one] {$count} track
+   *[other] {$count} tracks
+}, {$runtime}
 scandir = Scan directory
 pathtofolder = "Path to folder"
 
@@ -53,39 +67,161 @@ Queue = Queue
 CreatePlaylist = Create Playlist
 ClearAll = Clear Queue
 NowPlaying = Now Playing
+Lyrics = Lyrics
 None = None
 
 # Album Page
 AlbumLibrary = Album Library
 AlbumInputPlaceholder = Enter album name
 AlbumDiscNumber = Disc {$number}
+PlayWork = Play Work
 AlbumAttribution = {$artist}
+AlbumReleased = Released {$date}
+AlbumOriginallyReleased = Originally released {$date}
+AlbumSortAdded = Date Added
+AlbumSortTitle = Title
+AlbumSortArtist = Artist
+AlbumSortYear = Year
+Decade = {$decade}s
+UnknownYear = Unknown Year
 
 # Artist Page
 ArtistInputPlaceholder = Enter an artists name
 ArtistsPageEmpty = No Artists
 
+# Composers Page
+ComposerLibrary = Composers
+ComposersPageEmpty = No Composers
+ComposerTrackCount = {$count ->
+    [one] {$count} track
+   *[other] {$count} tracks
+}
+
+# Genres Page
+GenreLibrary = Genres
+GenresPageEmpty = No Genres
+GenreInputPlaceholder = Enter a genre
+GenreCounts = {$tracks ->
+    [one] {$tracks} track
+   *[other] {$tracks} tracks
+} · {$albums ->
+    [one] {$albums} album
+   *[other] {$albums} albums
+}
+PlayAll = Play All
+ShuffleGenre = Shuffle
+
 # Track Page
 TrackLibrary = Track Library
 TrackInputPlaceholder = Enter track information
 SearchFilter = Search By
-SearchFilterSpecify = By {$filter}
+
+# Track Properties
+TrackProperties = Track Properties
+Duration = Length
+Codec = Codec
+SampleRate = Sample Rate
+SampleRateValue = {$rate} kHz
+BitDepth = Bit Depth
+BitDepthValue = {$depth} bit
+Channels = Channels
+ChannelsValue = {$count ->
+    [1] Mono
+    [2] Stereo
+   *[other] {$count} channels
+}
+Bitrate = Bitrate
+BitrateValue = {$rate} kbit/s
+FileSize = File Size
+FileSizeValue = {$size} MB
+FilePath = Location
+Unknown = Unknown
 
 # Settings Page
 MusicDirectory = Music Directory
+LibraryFolders = Library Folders
+AddLibraryFolder = Add Folder
+RemoveLibraryFolder = Remove
+ExcludePatterns = Excluded Files
+ExcludePatternsDescription = Comma separated gitignore style patterns, a .novaignore file works in any folder
+ExcludePatternsPlaceholder = e.g. Podcasts/, *.tmp
 CurrentScanResults = Current Scan Results
+ScanProblems = Scan Problems
+ScanProblemsDescription = Files that could not be added, or were added without some of their tags
+ShowScanProblems = Show
+NoScanProblems = Every file was scanned without problems
 FilesScanned = Files Scanned
 MusicScanning = Music Scanning
+FallbackPattern = Tags From File Path
+FallbackPatternDescription = Used for files without tags, like {$example}. Changes apply from the next rescan
+ArtistSeparators = Artist Separators
+ArtistSeparatorsDescription = Split artist tags with several artists into one artist each, separated by spaces. Changes apply from the next rescan
+FeaturingSeparators = Featuring Separators
+FeaturingSeparatorsDescription = Like artist separators, but the artists after them are credited as featured
+GenreSeparators = Genre Separators
+GenreSeparatorsDescription = Split genre tags with several genres into one genre each, separated by spaces. Changes apply from the next rescan
+GenreMappings = Genres
+GenreMappingGenre = Genre
+GenreMappingAliases = Comma separated other spellings
+GenreMappingParent = Listed under
+AddGenreMapping = Add Genre
+AddGenreMappingDescription = Read other spellings of a genre as that genre, and list it under a broader one that then includes it when browsing. Changes apply from the next rescan
+RemoveGenreMapping = Remove
+CoverNames = Cover Images
+CoverNamesDescription = Images next to the files used as the cover of albums without embedded art, separated by spaces and without their extension. Earlier names are preferred
 FullRescan = Full Rescan
 Rescan = Rescan
 ScanProgress = Scan Progress:
+ScanThroughput = {$rate} files per second, {$eta} left
+LoudnessAnalysis = Loudness Analysis
+LoudnessAnalysisDescription = Measures tracks without ReplayGain tags so their volume is evened out too. Runs after each rescan while ReplayGain is on
+Analyze = Analyze
+LoudnessProgress = Analysis Progress:
 UserInterface = User Interface
 GridItemSize = Grid Item Size
 MusicPlayer = Music Player
 AppVolume = App Volume {$volume}
+ReplayGain = ReplayGain
+ReplayGainDescription = Evens out loudness between tracks using their ReplayGain tags
+ReplayGainOff = Off
+ReplayGainTrack = Track
+ReplayGainAlbum = Album
+ReplayGainAuto = Album when played in order
+ReplayGainPreamp = Preamp {$preamp} dB
+PreventClipping = Prevent Clipping
+PreventClippingDescription = Lowers the gain when a track would clip at its peak
 FooterToggle = Toggle Mini-Player
 Theme = Theme
 
 # Error
-SearchFailed = Search failed!
-ScanFileDoesNotExist = The music directory could not be found
\ No newline at end of file
+ScanFileDoesNotExist = The music directory could not be found
+RootUnavailable = {$path} is not available, skipping it
+RootOverlaps = This folder is already part of the library
+RootEmpty = No music was found in {$path}, its tracks are kept until it is available again
+RootDeviceChanged = {$path} is on another drive than before, its tracks are kept. Remove and add it again if the music moved
+DatabaseMigrationFailed = The library database could not be upgraded: {$error}
+LibraryDatabaseError = The library database could not be read: {$error}
+LibraryIoError = A file could not be read: {$error}
+AlbumNotFound = The album {$title} by {$artist} is not in the library
+ArtistNotFound = The artist {$name} is not in the library
+TrackNotFound = The track {$track} is not in the library
+
+# Scan problems
+InvalidDiscNumber = Disc number "{$value}" is not a number
+InvalidDiscTotal = Disc total "{$value}" is not a number
+InvalidTrackNumber = Track number "{$value}" is not a number
+InvalidTrackTotal = Track total "{$value}" is not a number
+InvalidDate = Date "{$value}" is not a date
+MissingTitle = No title tag, the file name is used instead
+MissingArtist = No artist tag
+ScanOpenFailed = The file could not be opened: {$error}
+ScanProbeFailed = Not a supported audio file: {$error}
+ScanWriteFailed = The file could not be added to the library: {$error}
+PlaylistCopyFailed = The playlist could not be copied: {$error}
+
+# Search queries
+QueryUnclosedQuote = A quote is not closed, end the phrase with another "
+QueryMissingValue = Nothing to look for after {$field}:
+QueryInvalidYear = "{$value}" is not a year, try 1958, >1958 or 1955..1960
+QueryInvalidDuration = "{$value}" is not a length, try 90s, 4:30, >10m or 1h..2h
+QueryInvalidBitrate = "{$value}" is not a bitrate, try 320, >=256 or 128..320
@@ -68,7 +68,6 @@ ArtistsPageEmpty = Sin artistas
 TrackLibrary = Biblioteca de Pistas
 TrackInputPlaceholder = Informacíon sobre alguna pista
 SearchFilter = Busca por
-SearchFilterSpecify = Por {$filter}
 
 # Settings Page
 MusicDirectory = Directorio de Música
@@ -86,5 +85,4 @@ FooterToggle = Mostrar barra de reproducion
 Theme = Tema
 
 # Error
-SearchFailed = Error con la buscada!
 ScanFileDoesNotExist = No se encuentra el directorio de música
\ No newline at end of file
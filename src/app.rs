@@ -2,18 +2,23 @@
 
 use crate::app::artists::ArtistInfo;
 use cosmic::dialog::file_chooser::Error;
-use regex::Regex;
 
 use rayon::iter::IndexedParallelIterator;
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 mod albums;
 mod artists;
+mod composers;
+mod genre;
 pub(crate) mod home;
+mod ignore;
+mod loudness;
+mod lyrics;
 mod playlists;
 mod scan;
 mod settings;
 mod tracks;
+mod watcher;
 
 use crate::app::albums::{
     get_album_info, get_top_album_info, Album, AlbumPage, AlbumPageState, FullAlbum,
@@ -21,16 +26,26 @@ use crate::app::albums::{
 
 use crate::app::artists::ArtistPageState::ArtistPage;
 use crate::app::artists::{ArtistPageState, ArtistsPage};
+use crate::app::composers::{ComposerInfo, ComposerPage, ComposersPage, ComposersPageState};
+use crate::app::genre::{GenreInfo, GenrePage, GenresPage, GenresPageState};
 use crate::app::home::HomePage;
+use crate::app::loudness::{analyze_loudness, LoudnessProgress};
+use crate::app::lyrics::Lyrics;
 use crate::app::playlists::{
     FullPlaylist, Playlist, PlaylistPage, PlaylistPageState, PlaylistTrack,
 };
-use crate::app::scan::scan_directory;
-use crate::app::tracks::{SearchResult, TrackPage, TrackPageState};
+use crate::app::scan::{apply_library_changes, scan_directory, ScanProgress};
+use crate::app::tracks::{search_positions, SearchResult, TrackPage, TrackPageState};
+use crate::app::watcher::LibraryChange;
 use crate::app::Message::ArtistPageEdit;
-use crate::config::{AppTheme, Config};
-use crate::database::{create_database, create_database_entry, find_visual};
-use crate::{app, config, fl};
+use crate::config::{
+    AlbumSort, AppTheme, Config, GenreMapping, LibraryRoot, ReplayGainMode, TagSettings,
+};
+use crate::database::{migrate_database, open_connection, remove_root, ReplayGain};
+use crate::library::{self, LibraryError, SearchField};
+use crate::mpris::{MprisHandle, PlaybackStatus, PlayerState, TrackMetadata};
+use crate::query::Query;
+use crate::{app, artwork, config, fl, fuzzy};
 use colored::Colorize;
 use cosmic::app::context_drawer;
 use cosmic::cosmic_theme::palette::cam16::Cam16IntoUnclamped;
@@ -43,22 +58,22 @@ use cosmic::iced::{keyboard, Alignment, Color, ContentFit, Event, Length};
 use cosmic::iced_widget::scrollable::Viewport;
 use cosmic::prelude::*;
 use cosmic::widget::{self, icon, menu, nav_bar};
+use cosmic::Application;
 use cosmic::{action, cosmic_config, cosmic_theme, theme};
 use futures_util::{SinkExt, StreamExt};
+use rand::seq::SliceRandom;
 use rand::Rng;
 use rodio::{Sink, Source};
-use rusqlite::fallible_iterator::FallibleIterator;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{BufRead, Read, Write as OtherWrite};
-use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 use std::{fs, io};
-use symphonia::default::get_probe;
 
 const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 const APP_ICON: &[u8] =
@@ -83,6 +98,20 @@ pub struct AppModel {
 
     //Settings Page
     pub rescan_available: bool,
+    /// Throughput of the running scan, `None` when no files are being probed
+    scan_progress: Option<ScanProgress>,
+    /// Tracks measured by the running loudness analysis
+    loudness_progress: Option<LoudnessProgress>,
+    /// Stops the running loudness analysis, `None` when there is none
+    loudness_cancel: Option<Arc<AtomicBool>>,
+    /// Shown in the scan problems drawer, loaded when it is opened
+    scan_problems: Vec<library::ScanProblem>,
+    /// Shown in the track properties drawer, loaded when it is opened
+    track_properties: Option<library::Track>,
+    /// Changes seen on disk while a rescan was running
+    pending_library_changes: Vec<(PathBuf, Vec<LibraryChange>)>,
+    /// Folder typed in by hand, for people without an xdg file chooser
+    root_input: String,
 
     //Audio
     pub mixer: rodio::stream::OutputStream,
@@ -95,6 +124,10 @@ pub struct AppModel {
     pub clear: bool,
     pub task_handle: Option<Vec<Handle>>,
 
+    // MPRIS
+    mpris: Option<MprisHandle>,
+    mpris_state: Arc<Mutex<PlayerState>>,
+
     // dialogs
     pub playlist_creation_dialog: bool,
     pub artistpage_edit_dialog: bool,
@@ -102,6 +135,8 @@ pub struct AppModel {
 
     // Searches
     pub search_field: String,
+    /// What is wrong with the query typed into `search_field`, shown underneath it
+    pub search_error: Option<String>,
     pub playlist_dialog_text: String,
     playlist_dialog_path: String,
     pub playlist_cover: Option<PathBuf>,
@@ -115,11 +150,13 @@ pub struct AppModel {
     albumsid: nav_bar::Id,
     tracksid: nav_bar::Id,
     artistsid: nav_bar::Id,
+    composersid: nav_bar::Id,
+    genresid: nav_bar::Id,
     playlistsid: nav_bar::Id,
     homeid: nav_bar::Id,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 /* todo: This is wasteful with memory at the benefit of less database accesses
 I think that the cost of accessing the database is much less important than the cost
 of having every track in the queue, for example, which is only displayed on one page,
@@ -135,6 +172,9 @@ pub struct AppTrack {
     pub album_title: String,
     pub path_buf: PathBuf,
     pub cover_art: Option<cosmic::widget::image::Handle>,
+    /// In seconds, from the scan
+    pub duration: Option<f64>,
+    pub replay_gain: ReplayGain,
 }
 
 /// Minimum amount of info required to display fully expose a Single track
@@ -143,15 +183,17 @@ pub struct DisplaySingle {
     pub id: u32,
     pub title: String,
     pub artist: String,
-    pub cover_art: Option<cosmic::widget::image::Handle>,
+    /// Hash of the cover in [`artwork`]
+    pub cover: Option<String>,
 }
 
 /// Messages emitted by the application and its widgets.
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum LoopState {
     LoopingTrack,
     LoopingQueue,
+    #[default]
     NotLooping,
     RandomShuffle,
 }
@@ -177,10 +219,24 @@ pub enum Message {
     ChooseFolder,
     FolderChosen(String),
     FolderPickerFail(String),
-    UpdateScanProgress,
-    UpdateScanDirSize,
-    AddToDatabase(PathBuf),
-    ProbeFail,
+    ToggleLibraryRoot(usize, bool),
+    LibraryRootExclusions(usize, String),
+    FallbackPatternEdit(String),
+    ArtistSeparatorsEdit(String),
+    FeaturingSeparatorsEdit(String),
+    GenreSeparatorsEdit(String),
+    AddGenreMapping,
+    GenreMappingEdit(usize, GenreMapping),
+    RemoveGenreMapping(usize),
+    CoverNamesEdit(String),
+    RemoveLibraryRoot(usize),
+    ScanProgress(ScanProgress),
+    AnalyzeLoudness,
+    CancelLoudnessAnalysis,
+    LoudnessProgress(LoudnessProgress),
+    LoudnessAnalysisFinished,
+    ShowScanProblems,
+    ShowTrackProperties(String),
     ChooseFile(FileChooserEvents),
 
     // Page Rendering
@@ -192,6 +248,7 @@ pub enum Message {
     AlbumsLoaded, // when albums table retrieved from db is exhausted after OnNavEnter in Album Page [Sets page state to loaded]
     AlbumPageStateAlbum(AlbumPage), // when album info is retrieved [Replaces AlbumPage with AlbumPage with new info]
     AlbumPageReturn,
+    AlbumSortChanged(AlbumSort),
 
     // impl for Artists & Album Page
     AlbumRequested((String, String)), // when an album icon is clicked [gets title & artist of album]
@@ -221,6 +278,17 @@ pub enum Message {
     ArtistRequested(String),
     //Artist
     ArtistPageReturn,
+
+    // Composers Page
+    ComposersLoaded(Vec<ComposerInfo>),
+    ComposerRequested(String),
+    ComposerPageReturn,
+
+    // Genres Page
+    GenresLoaded(Vec<GenreInfo>),
+    GenreRequested(String),
+    GenrePageReturn,
+    GenreShuffle,
     // Dialog Toggles
     ArtistPageEdit,
 
@@ -240,21 +308,35 @@ pub enum Message {
     AddTrackToSink(String),
     SkipTrack,
     ChangeLoopState,
+    SetLoopState(LoopState),
     PreviousTrack,
     SeekFinished,
     ClearQueue,
     SinkProgress(f64),
     SeekTrack(f64),
+    /// Jumps straight to a position, as clicking a line of synced lyrics does
+    SeekTo(f64),
+    /// Lyrics read for a track, dropped if another track started playing in the meantime
+    LyricsLoaded(u32, Option<String>),
     ChangeActiveInQueue(usize),
     RemoveSongInQueue(usize),
 
     // Settings
     GridSliderChange(u32),
     VolumeSliderChange(f32),
+    ReplayGainModeChange(ReplayGainMode),
+    ReplayGainPreampChange(f32),
+    TogglePreventClipping(bool),
 
     // Footer
     ToggleFooter(bool),
 
+    // MPRIS
+    MprisReady(MprisHandle),
+
+    // Library Watcher
+    LibraryChanged(PathBuf, Vec<LibraryChange>),
+
     // Error Reporting
     Toasts(cosmic::widget::toaster::ToastId),
     ToastError(String),
@@ -273,7 +355,9 @@ pub enum Message {
 #[derive(Clone, Debug)]
 pub enum ReEnterNavReason {
     UserInteraction,
-    Rescan,
+    /// With the settings the library was read with, unless some of it could not be read
+    Rescan(Option<TagSettings>),
+    LibraryUpdate,
     ArtistEdit,
     PlaylistEdit,
 }
@@ -335,6 +419,9 @@ impl cosmic::Application for AppModel {
             .unwrap(),
         }
 
+        // Bring the library database up to the current schema before anything reads it
+        let migration = open_connection().and_then(|conn| migrate_database(&conn));
+
         // Create a nav bar with three page items.
         let mut nav = nav_bar::Model::default();
         let mixer =
@@ -346,7 +433,7 @@ impl cosmic::Application for AppModel {
         let homeid = nav
             .insert()
             .text(fl!("home"))
-            .data::<Page>(Page::NowPlaying(HomePage { viewport: None }))
+            .data::<Page>(Page::NowPlaying(HomePage::new()))
             .icon(icon::from_name("applications-audio-symbolic"))
             .activate()
             .id();
@@ -365,6 +452,13 @@ impl cosmic::Application for AppModel {
             .icon(icon::from_name("avatar-default-symbolic"))
             .id();
 
+        let composersid = nav
+            .insert()
+            .text(fl!("composers"))
+            .data::<Page>(Page::Composers(ComposersPage::new()))
+            .icon(icon::from_name("audio-x-generic-symbolic"))
+            .id();
+
         let albumsid = nav
             .insert()
             .text(fl!("albums"))
@@ -372,6 +466,13 @@ impl cosmic::Application for AppModel {
             .icon(icon::from_name("media-optical-symbolic"))
             .id();
 
+        let genresid = nav
+            .insert()
+            .text(fl!("genres"))
+            .data::<Page>(Page::Genres(GenresPage::new()))
+            .icon(icon::from_name("folder-music-symbolic"))
+            .id();
+
         let playlistsid = nav
             .insert()
             .text(fl!("playlists"))
@@ -402,6 +503,13 @@ impl cosmic::Application for AppModel {
             config: config.1,
             config_handler,
             rescan_available: true,
+            scan_progress: None,
+            loudness_progress: None,
+            loudness_cancel: None,
+            scan_problems: vec![],
+            track_properties: None,
+            pending_library_changes: vec![],
+            root_input: String::new(),
             // Audio
             mixer,
             sink,
@@ -412,7 +520,10 @@ impl cosmic::Application for AppModel {
             queue_pos: 0,
             clear: false,
             task_handle: None,
+            mpris: None,
+            mpris_state: Arc::new(Mutex::new(PlayerState::default())),
             search_field: "".to_string(),
+            search_error: None,
 
             // dialogs toggles
 
@@ -435,12 +546,23 @@ impl cosmic::Application for AppModel {
             albumsid,
             tracksid,
             artistsid,
+            composersid,
+            genresid,
             playlistsid,
             homeid,
         };
 
         // Start up commands
-        let command = app.update_title();
+        let mut command = app.update_title();
+
+        if let Err(err) = migration {
+            command = Task::batch(vec![
+                command,
+                cosmic::task::future(async move {
+                    Message::ToastError(fl!("DatabaseMigrationFailed", error = err.to_string()))
+                }),
+            ]);
+        }
 
         (app, command)
     }
@@ -629,26 +751,8 @@ impl cosmic::Application for AppModel {
     }
 
     fn dialog(&self) -> Option<Element<Self::Message>> {
-        if !dirs::data_local_dir()
-            .unwrap()
-            .join(crate::app::AppModel::APP_ID)
-            .join("nova_music.db")
-            .exists()
-        {
-            match dirs::data_local_dir()
-                .unwrap()
-                .join(crate::app::AppModel::APP_ID)
-                .is_dir()
-            {
-                true => {}
-                false => fs::create_dir(
-                    dirs::data_local_dir()
-                        .unwrap()
-                        .join(crate::app::AppModel::APP_ID),
-                )
-                .unwrap(),
-            }
-
+        // The database exists from startup on, so first time set up is about having a library
+        if self.config.library_roots.is_empty() {
             return Some(
                 cosmic::widget::dialog::Dialog::new()
                     .title(fl!("firsttimetitle"))
@@ -657,7 +761,7 @@ impl cosmic::Application for AppModel {
                         cosmic::widget::row::with_children(vec![
                             cosmic::widget::text_input(
                                 fl!("pathtofolder"),
-                                self.config.scan_dir.as_str(),
+                                self.root_input.as_str(),
                             )
                             .on_input(|val| Message::ManualScanDirEdit(val))
                             .into(),
@@ -668,11 +772,13 @@ impl cosmic::Application for AppModel {
                                 .into(),
                         ]),
                     ))
-                    .primary_action(
-                        cosmic::widget::button::text(fl!("firsttimeprimary"))
+                    .primary_action(match self.root_input.is_empty() {
+                        true => cosmic::widget::button::text(fl!("firsttimeprimary"))
+                            .class(cosmic::theme::Button::Suggested),
+                        false => cosmic::widget::button::text(fl!("firsttimeprimary"))
                             .class(cosmic::theme::Button::Suggested)
-                            .on_press(Message::RescanDir),
-                    )
+                            .on_press(Message::FolderChosen(self.root_input.clone())),
+                    })
                     .into(),
             );
         }
@@ -754,6 +860,8 @@ impl cosmic::Application for AppModel {
                     return Some(page.artist_edit_dialog().into());
                 }
             }
+            Page::Composers(_) => {}
+            Page::Genres(_) => {}
             Page::Albums(_) => {}
             Page::Playlists(val) => {
                 let icon = match &self.playlist_cover {
@@ -874,6 +982,16 @@ impl cosmic::Application for AppModel {
                 Message::ToggleContextPage(ContextPage::Settings),
             )
             .title(fl!("settings")),
+            ContextPage::ScanProblems => context_drawer::context_drawer(
+                self.scan_problems(),
+                Message::ToggleContextPage(ContextPage::ScanProblems),
+            )
+            .title(fl!("ScanProblems")),
+            ContextPage::TrackProperties => context_drawer::context_drawer(
+                self.track_properties(),
+                Message::ToggleContextPage(ContextPage::TrackProperties),
+            )
+            .title(fl!("TrackProperties")),
         })
     }
 
@@ -887,6 +1005,8 @@ impl cosmic::Application for AppModel {
             Page::NowPlaying(home_page) => body = home_page.load_page(self),
             Page::Tracks(track_page) => body = track_page.load_page(self),
             Page::Artist(artists_page) => body = artists_page.load_page(self),
+            Page::Composers(composers_page) => body = composers_page.load_page(self),
+            Page::Genres(genres_page) => body = genres_page.load_page(self),
             Page::Albums(album_page) => body = album_page.load_page(self),
             Page::Playlists(playlist_page) => body = playlist_page.load_page(self),
         }
@@ -903,6 +1023,252 @@ impl cosmic::Application for AppModel {
     /// Tasks may be returned for asynchronous execution of code in the background
     /// on the application's async runtime.
     fn update(&mut self, message: Self::Message) -> Task<cosmic::Action<Self::Message>> {
+        let task = self.handle_message(message);
+        let load_lyrics = self.load_lyrics();
+        let lyrics = self.sync_lyrics();
+        self.sync_mpris();
+        Task::batch([task, load_lyrics, lyrics])
+    }
+
+    /// Called when a nav item is selected.
+    fn on_nav_select(&mut self, id: nav_bar::Id) -> Task<cosmic::Action<Self::Message>> {
+        // Activate the page in the model.
+        self.nav.activate(id);
+        self.update_title()
+    }
+
+    fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
+        struct KeybindSubscription;
+
+        let mut subscriptions = vec![
+            // Watch for application configuration changes.
+            cosmic::iced::event::listen_with(handle_keybinds),
+            crate::mpris::subscription(Arc::clone(&self.sink), Arc::clone(&self.mpris_state)),
+        ];
+
+        // Keep the library in sync with its enabled roots
+        subscriptions.extend(
+            self.config
+                .library_roots
+                .iter()
+                .filter(|root| root.enabled)
+                .map(|root| watcher::watch_library(PathBuf::from(&root.path))),
+        );
+
+        cosmic::iced::Subscription::batch(subscriptions)
+    }
+}
+
+/// Runs a library search off the UI thread, `positions` turns what it found into places in the
+/// list the page shows
+fn library_search<T: Send + 'static>(
+    search: impl FnOnce() -> Result<Vec<T>, LibraryError> + Send + 'static,
+    positions: impl FnOnce(Vec<T>) -> Vec<SearchResult> + Send + 'static,
+) -> Task<cosmic::Action<Message>> {
+    cosmic::Task::future(async move {
+        match tokio::task::spawn_blocking(move || search().map(positions)).await {
+            Ok(Ok(results)) => Message::SearchResults(results),
+            Ok(Err(err)) => Message::ToastError(err.to_string()),
+            Err(err) => Message::ToastError(err.to_string()),
+        }
+    })
+    .map(cosmic::Action::App)
+}
+
+fn handle_keybinds(
+    event: cosmic::iced::event::Event,
+    a: cosmic::iced::event::Status,
+    _: cosmic::iced::window::Id,
+) -> Option<Message> {
+    if let cosmic::iced::event::Status::Captured = a {
+        return None;
+    }
+
+    match event {
+        Event::Keyboard(key) => {
+            if let keyboard::Event::KeyPressed { key, .. } = key {
+                log::info!("[{:?}]", key);
+                match key {
+                    cosmic::iced::keyboard::Key::Named(
+                        cosmic::iced::keyboard::key::Named::Space,
+                    ) => return Some(Message::PlayPause),
+
+                    cosmic::iced::keyboard::Key::Named(
+                        cosmic::iced::keyboard::key::Named::MediaSkipBackward,
+                    ) => return Some(Message::PreviousTrack),
+
+                    cosmic::iced::keyboard::Key::Named(
+                        cosmic::iced::keyboard::key::Named::MediaSkipForward,
+                    ) => return Some(Message::SkipTrack),
+
+                    cosmic::iced::keyboard::Key::Named(
+                        cosmic::iced::keyboard::key::Named::MediaPlayPause,
+                    ) => return Some(Message::PlayPause),
+
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+impl AppModel {
+    /// Pushes the current playback state to MPRIS clients, if the server is up.
+    fn sync_mpris(&self) {
+        let Some(mpris) = &self.mpris else {
+            return;
+        };
+
+        let track = self.queue.get(self.queue_pos).map(|track| TrackMetadata {
+            id: track.id,
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album_title.clone(),
+            path: track.path_buf.clone(),
+            length: self.song_duration,
+            art_url: None,
+        });
+
+        let status = match track {
+            None => PlaybackStatus::Stopped,
+            Some(_) if self.sink.is_paused() => PlaybackStatus::Paused,
+            Some(_) => PlaybackStatus::Playing,
+        };
+
+        mpris.update(PlayerState {
+            status,
+            loop_state: self.loop_state.clone(),
+            volume: (self.config.volume / 100.0) as f64,
+            has_next: self.queue_pos + 1 < self.queue.len(),
+            has_previous: track.is_some(),
+            track,
+        });
+    }
+
+    /// Clears the lyrics when another track starts playing and reads its own in the background
+    fn load_lyrics(&mut self) -> Task<cosmic::Action<Message>> {
+        let track = self.queue.get(self.queue_pos).map(|track| track.id);
+        let Some(Page::NowPlaying(page)) = self.nav.data_mut::<Page>(self.homeid) else {
+            return Task::none();
+        };
+        if page.lyrics_track == track {
+            return Task::none();
+        }
+
+        page.lyrics_track = track;
+        page.lyrics = None;
+        page.lyrics_line = None;
+        let Some(track) = track else {
+            return Task::none();
+        };
+
+        cosmic::Task::future(async move {
+            match tokio::task::spawn_blocking(move || library::lyrics(track)).await {
+                Ok(Ok(lyrics)) => Message::LyricsLoaded(track, lyrics),
+                Ok(Err(err)) => {
+                    log::error!("Could not load lyrics: {}", err);
+                    Message::LyricsLoaded(track, None)
+                }
+                Err(err) => Message::ToastError(err.to_string()),
+            }
+        })
+        .map(cosmic::Action::App)
+    }
+
+    /// Keeps the synced line being sung in view
+    fn sync_lyrics(&mut self) -> Task<cosmic::Action<Message>> {
+        let position = self.song_progress;
+        let Some(Page::NowPlaying(page)) = self.nav.data_mut::<Page>(self.homeid) else {
+            return Task::none();
+        };
+
+        let Some(lyrics @ Lyrics::Synced(lines)) = &page.lyrics else {
+            return Task::none();
+        };
+        let line = lyrics.current_line(position);
+        if line == page.lyrics_line {
+            return Task::none();
+        }
+        page.lyrics_line = line;
+
+        let y = match lines.len() {
+            0 | 1 => 0.0,
+            len => line.unwrap_or(0) as f32 / (len - 1) as f32,
+        };
+        cosmic::iced_widget::scrollable::snap_to(
+            page.lyrics_scroll_id.clone(),
+            cosmic::iced_widget::scrollable::RelativeOffset { x: 0.0, y },
+        )
+    }
+
+    /// Opens a file for the sink, scaled by its ReplayGain as the settings ask for. Gain
+    /// settings changed while a track plays apply from the next one.
+    fn open_source(
+        &self,
+        path: &Path,
+    ) -> Result<rodio::source::Amplify<rodio::Decoder<File>>, String> {
+        let file = File::open(path).map_err(|_| {
+            format!(
+                "Track found in database but not at the filepath: {}",
+                path.to_string_lossy()
+            )
+        })?;
+        let byte_len = file.metadata().map_err(|err| err.to_string())?.len();
+
+        let decoder = rodio::Decoder::builder()
+            .with_byte_len(byte_len)
+            .with_data(file)
+            .with_gapless(true)
+            .with_seekable(true)
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        let album = match self.config.replay_gain {
+            ReplayGainMode::Off => return Ok(decoder.amplify(1.0)),
+            ReplayGainMode::Track => false,
+            ReplayGainMode::Album => true,
+            ReplayGainMode::Auto => self.playing_album_in_order(),
+        };
+        // Tracks are played from the queue, which has their gain from when they were added
+        let factor = match self.queue.iter().find(|track| track.path_buf == path) {
+            Some(track) => track.replay_gain.factor(
+                album,
+                self.config.replay_gain_preamp as f64,
+                self.config.prevent_clipping,
+            ),
+            None => {
+                log::warn!("No ReplayGain for {}, it is not queued", path.display());
+                1.0
+            }
+        };
+
+        Ok(decoder.amplify(factor))
+    }
+
+    /// Whether the current track is played along with its neighbours from the same album
+    fn playing_album_in_order(&self) -> bool {
+        if self.loop_state == LoopState::RandomShuffle {
+            return false;
+        }
+        let Some(current) = self.queue.get(self.queue_pos) else {
+            return false;
+        };
+        let same_album = |index: usize| {
+            self.queue
+                .get(index)
+                .is_some_and(|track| track.album_title == current.album_title)
+        };
+
+        !current.album_title.is_empty()
+            && ((self.queue_pos > 0 && same_album(self.queue_pos - 1))
+                || same_album(self.queue_pos + 1))
+    }
+
+    /// Applies a message to the model, `update` wraps this so every change reaches MPRIS clients.
+    fn handle_message(&mut self, message: Message) -> Task<cosmic::Action<Message>> {
         match message {
             Message::UpdateTheme(selection) => {
                 self.config.set_app_theme(&self.config_handler, selection);
@@ -911,40 +1277,27 @@ impl cosmic::Application for AppModel {
             Message::EditArtistConfirm => {
                 if let Page::Artist(artist) = self.nav.active_data_mut::<Page>().unwrap() {
                     if let ArtistPageState::ArtistPage(ref page) = artist.page_state {
-                        let conn = connect_to_db();
-
-                        if let Ok(result) = conn.query_row(
-                            "select id from artists where name = ?",
-                            [&page.artist.name],
-                            |row| row.get::<_, u32>("id"),
-                        ) {
-                            let file =
-                                fs::File::open(PathBuf::from(page.artist.path.clone())).unwrap();
-                            if let Ok(result) = conn.execute(
-                                "update artists set artistpfp = ? where id = ?",
-                                (
-                                    &Box::new(
-                                        file.bytes().filter_map(|a| a.ok()).collect::<Vec<u8>>(),
-                                    ),
-                                    &result,
-                                ),
-                            ) {
-                                log::info!("{} entries changed in artists table!", result);
-                                return cosmic::Task::future(
-                                    async move { Message::ArtistPageEdit },
-                                )
-                                .map(cosmic::action::Action::App);
-                            } else {
-                                log::warn!("artists table could not be updated")
+                        let result = fs::read(&page.artist.path)
+                            .map_err(LibraryError::from)
+                            .and_then(|picture| {
+                                library::set_artist_picture(&page.artist.name, picture)
+                            });
+
+                        return match result {
+                            Ok(_) => cosmic::Task::future(async move { Message::ArtistPageEdit })
+                                .map(cosmic::action::Action::App),
+                            Err(err) => {
+                                log::warn!("Artist picture could not be updated: {}", err);
+                                self.toasts
+                                    .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                                    .map(cosmic::Action::App)
                             }
-                        } else {
-                            log::warn!("Artist: {} not found", page.artist.name)
-                        }
+                        };
                     }
                 }
             }
             Message::ManualScanDirEdit(val) => {
-                self.config.set_scan_dir(&self.config_handler, val).unwrap();
+                self.root_input = val;
             }
             Message::ToastError(error) => {
                 return self
@@ -965,6 +1318,8 @@ impl cosmic::Application for AppModel {
                 Page::Playlists(page) => page.viewport = Some(view),
                 Page::Tracks(page) => page.viewport = Some(view),
                 Page::Artist(page) => page.viewport = Some(view),
+                Page::Composers(page) => page.viewport = Some(view),
+                Page::Genres(page) => page.viewport = Some(view),
             },
             Message::PlaylistDeleteConfirmed => {
                 if let Page::Playlists(toppage) =
@@ -1133,21 +1488,128 @@ impl cosmic::Application for AppModel {
                 .map(action::Action::App);
             }
             Message::FolderPickerFail(error) => {
-                if !(error.contains("Cancelled File Picker: Keeping scan directory the same")) {
-                    let _ = self
-                        .config
-                        .set_scan_dir(&self.config_handler, "".to_string());
-                } else {
-                }
-
                 return self
                     .toasts
                     .push(cosmic::widget::toaster::Toast::new(error))
                     .map(cosmic::Action::App);
             }
             Message::FolderChosen(fp) => {
-                self.rescan_available = true;
-                let _ = self.config.set_scan_dir(&self.config_handler, fp);
+                let path = PathBuf::from(&fp);
+                if self.config.library_roots.iter().any(|root| {
+                    path.starts_with(&root.path) || Path::new(&root.path).starts_with(&path)
+                }) {
+                    return self
+                        .toasts
+                        .push(cosmic::widget::toaster::Toast::new(fl!("RootOverlaps")))
+                        .map(cosmic::Action::App);
+                }
+
+                let mut roots = self.config.library_roots.clone();
+                roots.push(LibraryRoot::new(fp));
+                self.config
+                    .set_library_roots(&self.config_handler, roots)
+                    .expect("Failed to save to config");
+                self.root_input.clear();
+
+                // Pull the new folder in right away
+                if self.rescan_available {
+                    return self.update(Message::RescanDir);
+                }
+            }
+            Message::ToggleLibraryRoot(index, enabled) => {
+                let mut roots = self.config.library_roots.clone();
+                if let Some(root) = roots.get_mut(index) {
+                    root.enabled = enabled;
+                }
+                self.config
+                    .set_library_roots(&self.config_handler, roots)
+                    .expect("Failed to save to config");
+
+                if enabled && self.rescan_available {
+                    return self.update(Message::RescanDir);
+                }
+            }
+            Message::LibraryRootExclusions(index, patterns) => {
+                // Excluded files leave the library with the next rescan
+                let mut roots = self.config.library_roots.clone();
+                if let Some(root) = roots.get_mut(index) {
+                    root.exclude = patterns.split(',').map(|a| a.to_string()).collect();
+                }
+                self.config
+                    .set_library_roots(&self.config_handler, roots)
+                    .expect("Failed to save to config");
+            }
+            Message::FallbackPatternEdit(pattern) => {
+                // Applied to every file with the next rescan
+                self.config
+                    .set_fallback_pattern(&self.config_handler, pattern)
+                    .expect("Failed to save to config");
+            }
+            Message::ArtistSeparatorsEdit(separators) => {
+                self.config
+                    .set_artist_separators(&self.config_handler, separators)
+                    .expect("Failed to save to config");
+            }
+            Message::FeaturingSeparatorsEdit(separators) => {
+                self.config
+                    .set_featuring_separators(&self.config_handler, separators)
+                    .expect("Failed to save to config");
+            }
+            Message::GenreSeparatorsEdit(separators) => {
+                self.config
+                    .set_genre_separators(&self.config_handler, separators)
+                    .expect("Failed to save to config");
+            }
+            Message::AddGenreMapping => {
+                let mut mappings = self.config.genre_mappings.clone();
+                mappings.push(GenreMapping::default());
+                self.config
+                    .set_genre_mappings(&self.config_handler, mappings)
+                    .expect("Failed to save to config");
+            }
+            Message::GenreMappingEdit(index, mapping) => {
+                // Applied to every file with the next rescan
+                let mut mappings = self.config.genre_mappings.clone();
+                if let Some(old) = mappings.get_mut(index) {
+                    *old = mapping;
+                }
+                self.config
+                    .set_genre_mappings(&self.config_handler, mappings)
+                    .expect("Failed to save to config");
+            }
+            Message::RemoveGenreMapping(index) => {
+                let mut mappings = self.config.genre_mappings.clone();
+                if index < mappings.len() {
+                    mappings.remove(index);
+                }
+                self.config
+                    .set_genre_mappings(&self.config_handler, mappings)
+                    .expect("Failed to save to config");
+            }
+            Message::CoverNamesEdit(names) => {
+                self.config
+                    .set_cover_names(&self.config_handler, names)
+                    .expect("Failed to save to config");
+            }
+            Message::RemoveLibraryRoot(index) => {
+                if index >= self.config.library_roots.len() {
+                    return Task::none();
+                }
+
+                let mut roots = self.config.library_roots.clone();
+                let root = PathBuf::from(roots.remove(index).path);
+                self.config
+                    .set_library_roots(&self.config_handler, roots)
+                    .expect("Failed to save to config");
+
+                return cosmic::Task::future(async move {
+                    match tokio::task::spawn_blocking(move || remove_root(&root)).await {
+                        Ok(Ok(())) => Message::OnNavEnter(ReEnterNavReason::LibraryUpdate),
+                        Ok(Err(err)) => Message::ToastError(err.to_string()),
+                        Err(err) => Message::ToastError(err.to_string()),
+                    }
+                })
+                .map(cosmic::Action::App);
             }
             Message::OpenRepositoryUrl => {
                 _ = open::that_detached(REPOSITORY);
@@ -1169,6 +1631,8 @@ impl cosmic::Application for AppModel {
                 LoopState::NotLooping => self.loop_state = LoopState::LoopingQueue,
                 LoopState::RandomShuffle => self.loop_state = LoopState::NotLooping,
             },
+            Message::SetLoopState(loop_state) => self.loop_state = loop_state,
+            Message::MprisReady(handle) => self.mpris = Some(handle),
             Message::ToggleContextPage(context_page) => {
                 if self.context_page == context_page {
                     // Close the context drawer if the toggled context page is the same.
@@ -1179,111 +1643,128 @@ impl cosmic::Application for AppModel {
                     self.core.window.show_context = true;
                 }
             }
+            Message::ShowScanProblems => match library::scan_problems() {
+                Ok(problems) => {
+                    self.scan_problems = problems;
+                    self.context_page = ContextPage::ScanProblems;
+                    self.core.window.show_context = true;
+                }
+                Err(err) => {
+                    return self
+                        .toasts
+                        .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                        .map(cosmic::Action::App);
+                }
+            },
+            Message::ShowTrackProperties(path) => match library::track_by_path(&path) {
+                Ok(track) => {
+                    self.track_properties = Some(track);
+                    self.context_page = ContextPage::TrackProperties;
+                    self.core.window.show_context = true;
+                }
+                Err(err) => {
+                    return self
+                        .toasts
+                        .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                        .map(cosmic::Action::App);
+                }
+            },
             Message::UpdateSearch(search) => {
                 self.search_field = search;
+                self.search_error = None;
+                let input = self.search_field.clone();
+
+                // An empty search shows everything again
+                if input.trim().is_empty() {
+                    match self.nav.active_data_mut::<Page>().unwrap() {
+                        Page::Albums(page) => {
+                            if let AlbumPageState::Search(_) = page.page_state {
+                                page.page_state = AlbumPageState::Loaded
+                            }
+                        }
+                        Page::Playlists(page) => {
+                            if let PlaylistPageState::Search(_) = page.playlist_page_state {
+                                page.playlist_page_state = PlaylistPageState::Loaded
+                            }
+                        }
+                        Page::Tracks(page) => {
+                            if let TrackPageState::Search = page.track_page_state {
+                                page.track_page_state = TrackPageState::Loaded
+                            }
+                        }
+                        Page::Artist(page) => {
+                            if let ArtistPageState::Search(_) = page.page_state {
+                                page.page_state = ArtistPageState::Loaded
+                            }
+                        }
+                        Page::Genres(page) => {
+                            if let GenresPageState::Search(_) = page.page_state {
+                                page.page_state = GenresPageState::Loaded
+                            }
+                        }
+                        Page::NowPlaying(_) | Page::Composers(_) => {}
+                    }
+                    return Task::none();
+                }
 
-                let regex = match regex::RegexBuilder::new(self.search_field.as_str())
-                    .case_insensitive(true)
-                    .build()
-                {
-                    Ok(a) => a,
-                    Err(err) => {
-                        log::error!("Update Search Error: {:?}", err);
-                        return self
-                            .toasts
-                            .push(cosmic::widget::toaster::Toast::new(fl!("SearchFailed")))
-                            .map(cosmic::Action::App);
+                // Genres and playlists are looked up by name, the other pages take filters too
+                let query = match self.nav.active_data::<Page>().unwrap() {
+                    Page::Albums(_) | Page::Tracks(_) | Page::Artist(_) => {
+                        match Query::parse(&input) {
+                            Ok(query) => query,
+                            Err(err) => {
+                                self.search_error = Some(err.to_string());
+                                return Task::none();
+                            }
+                        }
                     }
+                    _ => Query::default(),
                 };
+                let ranked = query.ranked();
 
                 match self.nav.active_data::<Page>().unwrap() {
                     Page::NowPlaying(_) => {}
+                    Page::Composers(_) => {}
                     Page::Albums(page) => {
-                        let cloned_albums = page.albums.clone();
-                        return cosmic::Task::stream(
-                            cosmic::iced_futures::stream::channel(0, |mut tx| async move {
-                                tokio::task::spawn_blocking(move || {
-                                    let mut albums = cloned_albums
-                                        .par_iter()
-                                        .enumerate()
-                                        .map(|(index, album)| {
-                                            return match regex.find(&album.name) {
-                                                None => SearchResult {
-                                                    tracks_index: index,
-                                                    score: 999,
-                                                },
-                                                Some(val) => {
-                                                    if val.range().start == 0 {
-                                                        if val.range().end == album.name.len() {
-                                                            // Exact Match
-                                                            return SearchResult {
-                                                                tracks_index: index,
-                                                                score: 0,
-                                                            };
-                                                        }
-                                                        // Matches at the beginning
-
-                                                        return SearchResult {
-                                                            tracks_index: index,
-                                                            score: 1,
-                                                        };
-                                                    }
-                                                    // Matches somewhere else
-                                                    SearchResult {
-                                                        tracks_index: index,
-                                                        score: 2,
-                                                    }
-                                                }
-                                            };
-                                        })
-                                        .collect::<Vec<SearchResult>>();
-
-                                    albums.sort_by(|a, b| a.score.cmp(&b.score));
-                                    tx.try_send(Message::SearchResults(albums))
-                                });
-                                ()
-                            })
-                            .map(action::Action::App),
+                        let albums = page.albums.clone();
+                        return library_search(
+                            move || library::search_albums(&query),
+                            move |found| search_positions(&albums, found, ranked, |album| album.id),
                         );
                     }
                     Page::Playlists(page) => {
                         let cloned_playlists = page.playlists.clone();
 
+                        // Playlists are files rather than rows in the library, their titles are
+                        // matched here, closest first
+                        let typed = fuzzy::fold(input.trim());
+                        let words = fuzzy::words(&input);
+
                         return cosmic::Task::stream(
                             cosmic::iced_futures::stream::channel(0, |mut tx| async move {
                                 tokio::task::spawn_blocking(move || {
                                     let mut playlists = cloned_playlists
                                         .par_iter()
                                         .enumerate()
-                                        .map(|(index, playlist)| {
-                                            return match regex.find(&playlist.title) {
-                                                None => SearchResult {
-                                                    tracks_index: index,
-                                                    score: 999,
-                                                },
-                                                Some(val) => {
-                                                    if val.range().start == 0 {
-                                                        if val.range().end == playlist.title.len() {
-                                                            // Exact Match
-                                                            return SearchResult {
-                                                                tracks_index: index,
-                                                                score: 0,
-                                                            };
-                                                        }
-                                                        // Matches at the beginning
-
-                                                        return SearchResult {
-                                                            tracks_index: index,
-                                                            score: 1,
-                                                        };
-                                                    }
-                                                    // Matches somewhere else
-                                                    SearchResult {
-                                                        tracks_index: index,
-                                                        score: 2,
-                                                    }
+                                        .filter_map(|(index, playlist)| {
+                                            let title = fuzzy::fold(&playlist.title);
+                                            let score = match title.find(&typed) {
+                                                // Exact Match
+                                                Some(0) if title.len() == typed.len() => 0,
+                                                // Matches at the beginning
+                                                Some(0) => 1,
+                                                // Matches somewhere else
+                                                Some(_) => 2,
+                                                // Has every word, fewer typos first
+                                                None if !words.is_empty() => {
+                                                    3 + fuzzy::score(&words, &title)? as u32
                                                 }
+                                                None => return None,
                                             };
+                                            Some(SearchResult {
+                                                tracks_index: index,
+                                                score,
+                                            })
                                         })
                                         .collect::<Vec<SearchResult>>();
 
@@ -1296,156 +1777,48 @@ impl cosmic::Application for AppModel {
                         );
                     }
                     Page::Tracks(page) => {
-                        let cloned_tracks = page.tracks.clone();
-
-                        return cosmic::Task::stream(
-                            cosmic::iced_futures::stream::channel(0, |mut tx| async move {
-                                tokio::task::spawn_blocking(move || {
-                                    let mut tracks = cloned_tracks
-                                        .par_iter()
-                                        .enumerate()
-                                        .map(|(index, track)| {
-                                            match regex.find(&track.title) {
-                                                None => {
-                                                    match regex.find(&track.album_title) {
-                                                        None => {
-                                                            match regex.find(&track.artist) {
-                                                                None => SearchResult {
-                                                                    tracks_index: index,
-                                                                    score: 999,
-                                                                },
-                                                                Some(val) => {
-                                                                    if val.range().start == 0 {
-                                                                        if val.range().end
-                                                                            == track.artist.len()
-                                                                        {
-                                                                            // Exact Match
-                                                                            return SearchResult {
-                                                                                tracks_index: index,
-                                                                                score: 6,
-                                                                            };
-                                                                        }
-                                                                        // Matches at the beginning
-
-                                                                        return SearchResult {
-                                                                            tracks_index: index,
-                                                                            score: 7,
-                                                                        };
-                                                                    }
-                                                                    // Matches somewhere else
-                                                                    return SearchResult {
-                                                                        tracks_index: index,
-                                                                        score: 8,
-                                                                    };
-                                                                }
-                                                            }
-                                                        }
-                                                        Some(val) => {
-                                                            if val.range().start == 0 {
-                                                                if val.range().end
-                                                                    == track.album_title.len()
-                                                                {
-                                                                    // Exact Match
-                                                                    return SearchResult {
-                                                                        tracks_index: index,
-                                                                        score: 3,
-                                                                    };
-                                                                }
-                                                                // Matches at the beginning
-
-                                                                return SearchResult {
-                                                                    tracks_index: index,
-                                                                    score: 4,
-                                                                };
-                                                            }
-                                                            // Matches somewhere else
-                                                            return SearchResult {
-                                                                tracks_index: index,
-                                                                score: 5,
-                                                            };
-                                                        }
-                                                    }
-                                                }
-                                                Some(val) => {
-                                                    if val.range().start == 0 {
-                                                        if val.range().end == track.title.len() {
-                                                            // Exact Match
-                                                            return SearchResult {
-                                                                tracks_index: index,
-                                                                score: 0,
-                                                            };
-                                                        }
-                                                        // Matches at the beginning
-
-                                                        return SearchResult {
-                                                            tracks_index: index,
-                                                            score: 1,
-                                                        };
-                                                    }
-                                                    // Matches somewhere else
-                                                    return SearchResult {
-                                                        tracks_index: index,
-                                                        score: 2,
-                                                    };
-                                                }
-                                            }
-                                        })
-                                        .collect::<Vec<SearchResult>>();
+                        let mut fields = [
+                            (page.search_by_title, SearchField::Title),
+                            (page.search_by_album, SearchField::Album),
+                            (page.search_by_artist, SearchField::Artist),
+                        ]
+                        .into_iter()
+                        .filter(|(enabled, _)| *enabled)
+                        .map(|(_, field)| field)
+                        .collect::<Vec<SearchField>>();
+                        // Unless it is narrowed down, genres, composers and lyrics are searched too
+                        if fields.len() == 3 {
+                            fields = SearchField::ALL.to_vec();
+                        }
 
-                                    tracks.sort_by(|a, b| a.score.cmp(&b.score));
-                                    tx.try_send(Message::SearchResults(tracks))
-                                });
-                                ()
-                            })
-                            .map(action::Action::App),
+                        let tracks = page.tracks.clone();
+                        return library_search(
+                            move || library::search_tracks(&query, &fields),
+                            move |found| search_positions(&tracks, found, ranked, |track| track.id),
                         );
                     }
                     Page::Artist(page) => {
-                        let cloned_artists = page.artists.clone();
-                        return cosmic::Task::stream(
-                            cosmic::iced_futures::stream::channel(0, |mut tx| async move {
-                                tokio::task::spawn_blocking(move || {
-                                    let mut albums = cloned_artists
-                                        .par_iter()
-                                        .enumerate()
-                                        .map(|(index, album)| {
-                                            return match regex.find(&album.name) {
-                                                None => SearchResult {
-                                                    tracks_index: index,
-                                                    score: 999,
-                                                },
-                                                Some(val) => {
-                                                    if val.range().start == 0 {
-                                                        if val.range().end == album.name.len() {
-                                                            // Exact Match
-                                                            return SearchResult {
-                                                                tracks_index: index,
-                                                                score: 0,
-                                                            };
-                                                        }
-                                                        // Matches at the beginning
-
-                                                        return SearchResult {
-                                                            tracks_index: index,
-                                                            score: 1,
-                                                        };
-                                                    }
-                                                    // Matches somewhere else
-                                                    SearchResult {
-                                                        tracks_index: index,
-                                                        score: 2,
-                                                    }
-                                                }
-                                            };
-                                        })
-                                        .collect::<Vec<SearchResult>>();
-
-                                    albums.sort_by(|a, b| a.score.cmp(&b.score));
-                                    tx.try_send(Message::SearchResults(albums))
-                                });
-                                ()
-                            })
-                            .map(action::Action::App),
+                        let names = page
+                            .artists
+                            .iter()
+                            .map(|artist| artist.name.clone())
+                            .collect::<Vec<String>>();
+                        return library_search(
+                            move || library::search_artists(&query),
+                            move |found| {
+                                search_positions(&names, found, ranked, |name| name.clone())
+                            },
+                        );
+                    }
+                    Page::Genres(page) => {
+                        let names = page
+                            .genres
+                            .iter()
+                            .map(|genre| genre.name.clone())
+                            .collect::<Vec<String>>();
+                        return library_search(
+                            move || library::search_genres(&input),
+                            move |found| search_positions(&names, found, true, |name| name.clone()),
                         );
                     }
                 }
@@ -1458,29 +1831,36 @@ impl cosmic::Application for AppModel {
                 }
             },
             Message::RescanDir => {
-                self.clear = true;
-                self.sink.stop();
-                match &self.task_handle {
-                    None => {}
-                    Some(handles) => {
-                        for handle in handles {
-                            handle.abort()
-                        }
-                    }
-                }
-
-                if fs::exists(self.config.scan_dir.as_str()).is_err() {
-                    return cosmic::Task::future(async move {
-                        Message::ToastError(fl!("ScanFileDoesNotExist"))
+                // Unmounted drives are skipped so their tracks are not removed
+                let (roots, missing): (Vec<LibraryRoot>, Vec<LibraryRoot>) = self
+                    .config
+                    .library_roots
+                    .iter()
+                    .filter(|root| root.enabled)
+                    .cloned()
+                    .partition(|root| Path::new(&root.path).is_dir());
+
+                let mut tasks = missing
+                    .iter()
+                    .map(|root| {
+                        self.toasts
+                            .push(cosmic::widget::toaster::Toast::new(fl!(
+                                "RootUnavailable",
+                                path = root.path.clone()
+                            )))
+                            .map(cosmic::Action::App)
                     })
-                    .map(cosmic::Action::App);
-                }
+                    .collect::<Vec<_>>();
 
-                self.queue_pos = 0;
-                self.song_progress = 0.0;
-                self.song_duration = None;
-
-                self.queue.clear();
+                if roots.is_empty() {
+                    tasks.push(
+                        cosmic::Task::future(async move {
+                            Message::ToastError(fl!("ScanFileDoesNotExist"))
+                        })
+                        .map(cosmic::Action::App),
+                    );
+                    return cosmic::Task::batch(tasks);
+                }
 
                 // Settings: No rescan until current rescan finishes
                 self.rescan_available = false;
@@ -1499,6 +1879,7 @@ impl cosmic::Application for AppModel {
                     .set_albums_found(&self.config_handler, 0)
                     .expect("Failed to change config");
 
+                // Pages are reloaded once the rescan finishes, playback keeps going meanwhile
                 // Albums: Full reset
                 let album = self
                     .nav
@@ -1538,128 +1919,122 @@ impl cosmic::Application for AppModel {
                     page.page_state = ArtistPageState::Loading
                 }
 
-                create_database();
+                // Composers Reset
+                if let Page::Composers(page) = self
+                    .nav
+                    .data_mut::<Page>(self.composersid)
+                    .expect("Should always be intialized")
+                {
+                    page.page_state = ComposersPageState::Loading
+                }
+
+                // Genres Reset
+                if let Page::Genres(page) = self
+                    .nav
+                    .data_mut::<Page>(self.genresid)
+                    .expect("Should always be intialized")
+                {
+                    page.genre_page_cache = None;
+                    page.page_state = GenresPageState::Loading
+                }
 
-                let path = self.config.scan_dir.clone().parse().unwrap();
-                return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
+                // Files that did not change still need their tags read again
+                let options = self.config.tag_options();
+                let reprobe = self.config.tag_options_changed();
+                // Stored once the scan is done, files it did not get to are read again next time
+                let settings = self.config.tag_settings();
+                let complete = missing.is_empty();
+
+                // New tracks are measured right away unless their gain would not be used
+                let analyze = self.config.replay_gain != ReplayGainMode::Off;
+                let scan = cosmic::Task::stream(cosmic::iced_futures::stream::channel(
                     100,
-                    |mut tx| async move {
-                        scan_directory(path, &mut tx).await;
-                        tx.send(Message::OnNavEnter(ReEnterNavReason::Rescan))
-                            .await
-                            .expect("de")
+                    move |mut tx| async move {
+                        let imported = scan_directory(roots, options, reprobe, &mut tx).await;
+                        let settings = (imported && complete).then_some(settings);
+                        // Nobody listens anymore once the app is closing
+                        let _ = tx
+                            .send(Message::OnNavEnter(ReEnterNavReason::Rescan(settings)))
+                            .await;
+                        if analyze {
+                            let _ = tx.send(Message::AnalyzeLoudness).await;
+                        }
                     },
                 ))
                 .map(cosmic::Action::App);
+                tasks.push(scan);
+
+                return cosmic::Task::batch(tasks);
             }
-            Message::AddToDatabase(path) => {
+            Message::LibraryChanged(root, changes) => {
+                if !self.rescan_available {
+                    // A rescan is walking the library, these are applied once it is done
+                    self.pending_library_changes.push((root, changes));
+                    return Task::none();
+                }
+
+                // The root may have been removed while the changes settled
+                let Some(root) = self
+                    .config
+                    .library_roots
+                    .iter()
+                    .find(|library_root| Path::new(&library_root.path) == root)
+                    .cloned()
+                else {
+                    return Task::none();
+                };
+
+                let options = self.config.tag_options();
                 return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
                     100,
                     move |mut tx| async move {
-                        let file = fs::File::open(&path).unwrap();
-                        let probe = get_probe();
-                        let mss = symphonia::core::io::MediaSourceStream::new(
-                            Box::new(file),
-                            Default::default(),
-                        );
-
-                        if let Ok(mut reader) = probe.format(
-                            &Default::default(),
-                            mss,
-                            &Default::default(),
-                            &Default::default(),
-                        ) {
-                            if let Some(mdat) = reader.metadata.get() {
-                                let tags = mdat
-                                    .current()
-                                    .unwrap()
-                                    .tags()
-                                    .iter()
-                                    .filter(|a| a.is_known())
-                                    .map(|a| a.clone())
-                                    .collect();
-                                create_database_entry(tags, &path).await;
-                                tx.send(Message::UpdateScanProgress).await.unwrap();
-                            } else {
-                                let mdat = reader.format.metadata();
-                                let tags = mdat
-                                    .current()
-                                    .unwrap()
-                                    .tags()
-                                    .iter()
-                                    .filter(|a| a.is_known())
-                                    .map(|a| a.clone())
-                                    .collect();
-                                create_database_entry(tags, &path).await;
-                                tx.send(Message::UpdateScanProgress).await.unwrap();
-                            }
-                        } else {
-                            if path.with_extension("m3u") == path
-                                || path.with_extension("m3u8") == path
-                            {
-                                let mut dir = PathBuf::new();
-
-                                if dirs::data_local_dir()
-                                    .unwrap()
-                                    .join(crate::app::AppModel::APP_ID)
-                                    .join("Playlists")
-                                    .exists()
-                                {
-                                    dir = dirs::data_local_dir()
-                                        .unwrap()
-                                        .join(crate::app::AppModel::APP_ID)
-                                        .join("Playlists");
-                                } else {
-                                    match std::fs::create_dir(
-                                        dirs::data_local_dir()
-                                            .unwrap()
-                                            .join(crate::app::AppModel::APP_ID)
-                                            .join("Playlists"),
-                                    ) {
-                                        Ok(_) => {
-                                            dir = dirs::data_local_dir()
-                                                .unwrap()
-                                                .join(crate::app::AppModel::APP_ID)
-                                                .join("Playlists");
-                                        }
-                                        Err(err) => {
-                                            tx.send(Message::ToastError(err.to_string()))
-                                                .await
-                                                .unwrap();
-                                        }
-                                    }
-                                }
+                        apply_library_changes(root, changes, options, &mut tx).await;
+                        let _ = tx
+                            .send(Message::OnNavEnter(ReEnterNavReason::LibraryUpdate))
+                            .await;
+                    },
+                ))
+                .map(cosmic::Action::App);
+            }
+            Message::AnalyzeLoudness => {
+                if self.loudness_cancel.is_some() {
+                    return Task::none();
+                }
 
-                                let name = path.file_name().unwrap().to_string_lossy().to_string();
-                                fs::copy(path, dir.as_path().join(name)).unwrap();
-                                tx.send(Message::UpdateScanProgress).await.unwrap();
-                            } else {
-                                tx.send(Message::ProbeFail).await.unwrap();
+                let cancel = Arc::new(AtomicBool::new(false));
+                self.loudness_cancel = Some(Arc::clone(&cancel));
+                self.loudness_progress = Some(LoudnessProgress::default());
 
-                                log::info!(
-                                    "ERROR: Probe failure \nErred Path: {}",
-                                    path.to_str().unwrap().to_string()
-                                );
-                            }
-                        }
+                return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
+                    100,
+                    move |mut tx| async move {
+                        analyze_loudness(cancel, &mut tx).await;
+                        let _ = tx.send(Message::LoudnessAnalysisFinished).await;
                     },
                 ))
                 .map(cosmic::Action::App);
             }
-            Message::UpdateScanProgress => {
-                self.config
-                    .set_files_scanned(&self.config_handler, self.config.files_scanned + 1)
-                    .expect("Failed to save to config");
+            Message::CancelLoudnessAnalysis => {
+                // The analysis stops after the packet it is decoding, it reports back once done
+                if let Some(cancel) = &self.loudness_cancel {
+                    cancel.store(true, Ordering::Relaxed);
+                }
             }
-            Message::ProbeFail => {
-                self.config
-                    .set_num_files_found(&self.config_handler, self.config.num_files_found - 1)
-                    .expect("Failed to save to config");
+            Message::LoudnessProgress(progress) => {
+                self.loudness_progress = Some(progress);
+            }
+            Message::LoudnessAnalysisFinished => {
+                self.loudness_cancel = None;
+                self.loudness_progress = None;
             }
-            Message::UpdateScanDirSize => {
+            Message::ScanProgress(progress) => {
                 self.config
-                    .set_num_files_found(&self.config_handler, self.config.num_files_found + 1)
+                    .set_num_files_found(&self.config_handler, progress.found)
                     .expect("Config Save Failed");
+                self.config
+                    .set_files_scanned(&self.config_handler, progress.done)
+                    .expect("Failed to save to config");
+                self.scan_progress = Some(progress);
             }
 
             // PAGE TASK RESPONSES
@@ -1669,21 +2044,91 @@ impl cosmic::Application for AppModel {
                     .data_mut::<Page>(self.albumsid)
                     .expect("Should always be intialized")
                 {
-                    dat.albums = Arc::from(new_album)
+                    dat.albums = Arc::from(new_album);
+                    dat.sort_albums(self.config.album_sort);
                 }
             }
             Message::OnNavEnter(reasoning) => {
                 //always
                 self.search_field = "".to_string();
+                self.search_error = None;
 
                 // re-entered nav because:
                 match reasoning {
                     ReEnterNavReason::UserInteraction => {
                         // normal
                     }
-                    ReEnterNavReason::Rescan => {
-                        // rescan must have finished, make it available again
-                        self.rescan_available = true;
+                    ReEnterNavReason::Rescan(settings) => {
+                        // rescan must have finished, make it available again
+                        self.rescan_available = true;
+                        self.scan_progress = None;
+
+                        if let Some(settings) = settings {
+                            if let Err(err) = self
+                                .config
+                                .set_scanned_tag_settings(&self.config_handler, settings)
+                            {
+                                log::error!("Could not save the scanned tag settings: {}", err);
+                            }
+                        }
+
+                        if !self.pending_library_changes.is_empty() {
+                            let pending = std::mem::take(&mut self.pending_library_changes);
+                            return cosmic::task::batch(pending.into_iter().map(
+                                |(root, changes)| {
+                                    cosmic::task::future(async move {
+                                        Message::LibraryChanged(root, changes)
+                                    })
+                                },
+                            ));
+                        }
+                    }
+                    ReEnterNavReason::LibraryUpdate => {
+                        // files changed on disk, reload the top level views, pages showing a
+                        // single album, artist or playlist are left alone
+                        if let Some(Page::Albums(page)) = self.nav.data_mut::<Page>(self.albumsid) {
+                            if !matches!(page.page_state, AlbumPageState::Album(_)) {
+                                page.albums = Arc::from(vec![]);
+                                page.page_state = AlbumPageState::Loading;
+                            }
+                        }
+                        if let Some(Page::Tracks(page)) = self.nav.data_mut::<Page>(self.tracksid) {
+                            page.track_page_state = TrackPageState::Loading;
+                        }
+                        if let Some(Page::Playlists(page)) =
+                            self.nav.data_mut::<Page>(self.playlistsid)
+                        {
+                            if !matches!(
+                                page.playlist_page_state,
+                                PlaylistPageState::PlaylistPage(_)
+                            ) {
+                                page.playlist_page_state = PlaylistPageState::Loading;
+                            }
+                        }
+                        if let Some(Page::Artist(page)) = self.nav.data_mut::<Page>(self.artistsid)
+                        {
+                            if matches!(
+                                page.page_state,
+                                ArtistPageState::Loaded | ArtistPageState::Search(_)
+                            ) {
+                                page.page_state = ArtistPageState::Loading;
+                            }
+                        }
+                        if let Some(Page::Composers(page)) =
+                            self.nav.data_mut::<Page>(self.composersid)
+                        {
+                            if matches!(page.page_state, ComposersPageState::Loaded) {
+                                page.page_state = ComposersPageState::Loading;
+                            }
+                        }
+                        if let Some(Page::Genres(page)) = self.nav.data_mut::<Page>(self.genresid) {
+                            if matches!(
+                                page.page_state,
+                                GenresPageState::Loaded | GenresPageState::Search(_)
+                            ) {
+                                page.page_state = GenresPageState::Loading;
+                            }
+                        }
                     }
                     ReEnterNavReason::ArtistEdit => {
                         // an artist was edited
@@ -1703,57 +2148,15 @@ impl cosmic::Application for AppModel {
                                 5,
                                 |mut tx| async move {
                                     tokio::task::spawn_blocking(move || {
-                                        let conn =
-                                            match rusqlite::Connection::open(
-                                                dirs::data_local_dir().unwrap().join(Self::APP_ID).join("nova_music.db")
-                                            ) {
-                                                Ok(conn) => conn,
-                                                Err(err) => {
-                                                    panic!("{}", err)
-                                                }
-                                            };
-
-
-                                        let mut stmt = match conn
-                                            .prepare(
-                                                "
-                                            SELECT a.id, a.name,
-                                            a.disc_number, a.track_number, a.album_cover, art.name as artist_name
-                                            FROM album a
-                                            left JOIN artists art ON a.artist_id = art.id",
-                                            ) {
-                                            Ok(stmt) => stmt,
+                                        let albums = match library::albums() {
+                                            Ok(albums) => albums,
                                             Err(err) => {
-                                                panic!("{}", err)
+                                                let _ = tx.try_send(Message::ToastError(
+                                                    err.to_string(),
+                                                ));
+                                                vec![]
                                             }
                                         };
-
-                                        let album_iter = stmt
-                                            .query_map([], |row| {
-                                                Ok((
-                                                    row.get::<_, String>("name").unwrap_or("None".to_string()),
-                                                    row.get::<_, String>("artist_name").unwrap_or_default(),
-                                                    row.get::<_, u32>("disc_number").unwrap_or(0),
-                                                    row.get::<_, u32>("track_number").unwrap_or(0),
-                                                    match row.get::<_, Vec<u8>>("album_cover") {
-                                                        Ok(val) => Some(val),
-                                                        Err(e) => {
-                                                            log::info!("Album iter error [on nav enter] : {}", e);
-                                                            None
-                                                        }
-                                                    },
-                                                ))
-                                            })
-                                            .expect("error executing query");
-
-
-                                        let albums: Vec<(String, String, u32, u32, Option<Vec<u8>>)> =
-                                            album_iter.filter_map(|a| {
-                                                a.ok()
-                                            }).collect();
-                                        for each in &albums {
-                                            log::info!("ALBUM: {:?}", each.0);
-                                        }
                                         get_top_album_info(&mut tx, albums);
 
                                         tx.try_send(Message::AlbumsLoaded)
@@ -1851,61 +2254,34 @@ impl cosmic::Application for AppModel {
                                 100,
                                 |mut tx| async move {
                                     tokio::task::spawn_blocking(move || {
-                                        let conn = rusqlite::Connection::open(
-                                            dirs::data_local_dir()
-                                                .unwrap()
-                                                .join(Self::APP_ID)
-                                                .join("nova_music.db"),
-                                        )
-                                            .unwrap();
-                                        let stmt = conn.prepare(
-                                            "
-select track.id as id, track.name as title, art.name as artist, track.path, a.name as album_title
-from track
-         left join main.album_tracks at on track.id = at.track_id
-         left join main.artists art on track.artist_id = art.id
-         left join main.album a on at.album_id = a.id;
-                        ",
-                                        );
-
-                                        if let Ok(mut stmt) = stmt {
-                                            let tracks = stmt
-                                                .query_map([], |row| {
-                                                    Ok(AppTrack {
-                                                        id: row.get("id").unwrap_or(0),
-                                                        title: row
-                                                            .get("title")
-                                                            .unwrap_or("No Data".to_string()),
-                                                        artist: row
-                                                            .get("artist")
-                                                            .unwrap_or("N/A".to_string()),
-                                                        album_title: row
-                                                            .get("album_title")
-                                                            .unwrap_or("N/A".to_string()),
-                                                        path_buf: PathBuf::from(
-                                                            row.get::<&str, String>("path")
-                                                                .expect("This should never happen"),
-                                                        ),
+                                        match library::tracks() {
+                                            Ok(tracks) => {
+                                                let tracks = tracks
+                                                    .into_iter()
+                                                    .map(|track| AppTrack {
+                                                        id: track.id,
+                                                        title: track.title,
+                                                        artist: track.artist,
+                                                        album_title: track.album_title,
+                                                        path_buf: track.path,
                                                         cover_art: None,
+                                                        duration: track.properties.duration,
+                                                        replay_gain: track.replay_gain,
                                                     })
-                                                })
-                                                .expect("error executing query");
-
-                                            let tracks =
-                                                tracks.into_iter().filter_map(|a| a.ok()).collect();
-
-                                            tx.try_send(Message::TrackLoaded(tracks))
-                                                .expect("Failed to send");
-                                            tx.try_send(Message::TracksLoaded)
-                                                .expect("Failed to send");
-                                        } else {
-                                            tx.try_send(Message::TracksLoaded)
-                                                .expect("Failed to send");
+                                                    .collect();
+                                                let _ = tx.try_send(Message::TrackLoaded(tracks));
+                                            }
+                                            Err(err) => {
+                                                let _ = tx
+                                                    .try_send(Message::ToastError(err.to_string()));
+                                            }
                                         }
+
+                                        tx.try_send(Message::TracksLoaded)
                                     });
                                 },
                             ))
-                                .map(cosmic::Action::App);
+                            .map(cosmic::Action::App);
                         }
                         TrackPageState::Loaded => {}
                         TrackPageState::Search => {
@@ -1914,165 +2290,86 @@ from track
                     },
                     Page::Artist(page) => match &page.page_state {
                         ArtistPageState::Loading => {
-                            return cosmic::Task::stream(
-                                cosmic::iced_futures::stream::channel(100, |mut tx| async move {
-                                    tokio::task::spawn_blocking(move || {
-                                        let mut artists: Vec<ArtistInfo> = vec![];
-                                        let conn = rusqlite::Connection::open(
-                                            dirs::data_local_dir()
-                                                .unwrap()
-                                                .join(Self::APP_ID)
-                                                .join("nova_music.db"),
-                                        ).unwrap();
-
-                                        let mut stmt = conn.prepare("
-select artists.name, artists.artistpfp
-from artists
-where exists(select *
-             from album
-             where album.artist_id = artists.id)
-   or exists(select *
-             from single
-             where exists(select *
-                          from track
-                          where single.track_id = track.id
-                            and track.artist_id = artists.id))
-                                        ").expect("Statement Faulty @ OnNavEnter Artists");
-
-                                        let rows = stmt.query_map([], |row| {
-                                            let name = row.get::<_, String>("name").expect("Should be string");
-                                            let regex = regex::RegexBuilder::new(r"feat\.|with|ft\.|&").case_insensitive(true).build().unwrap();
-
-
-                                            match regex.is_match(&name) {
-                                                false => {
-                                                    return Ok(
-                                                        ArtistInfo {
-                                                            name: name,
-                                                            path: String::from(""),
-                                                            image: match row.get::<_, Vec<u8>>("artistpfp") {
-                                                                Ok(val) => {
-                                                                    Some(cosmic::widget::image::Handle::from_bytes(val))
-                                                                }
-                                                                Err(e) => {
-                                                                    log::warn!("Potential Error @ OnNavEnter Artists: {}", e);
-                                                                    None
-                                                                }
-                                                            },
-                                                        }
-                                                    )
-                                                }
-                                                true => {
-                                                    return Err(rusqlite::Error::UnwindingPanic)
-                                                }
-                                            };
-                                        }).expect("Query map failed");
-
-                                        artists = rows.into_iter().filter_map(|a| a.ok()).collect();
-
-
-                                        tx.try_send(Message::ArtistsLoaded(artists))
-                                    });
-                                })
-                                    .map(cosmic::Action::App),
-                            )
+                            return cosmic::Task::future(async move {
+                                match tokio::task::spawn_blocking(library::artists).await {
+                                    Ok(Ok(artists)) => Message::ArtistsLoaded(
+                                        artists.into_iter().map(ArtistInfo::from).collect(),
+                                    ),
+                                    Ok(Err(err)) => Message::ToastError(err.to_string()),
+                                    Err(err) => Message::ToastError(err.to_string()),
+                                }
+                            })
+                            .map(cosmic::Action::App);
                         }
                         ArtistPageState::Search(_) => {}
                         ArtistPageState::Loaded => {}
                         ArtistPageState::ArtistPage(page) => {}
                         &ArtistPageState::Album(_) => {}
                     },
+                    Page::Composers(page) => {
+                        if let ComposersPageState::Loading = page.page_state {
+                            return cosmic::Task::future(async move {
+                                match tokio::task::spawn_blocking(library::composers).await {
+                                    Ok(Ok(composers)) => Message::ComposersLoaded(
+                                        composers.into_iter().map(ComposerInfo::from).collect(),
+                                    ),
+                                    Ok(Err(err)) => Message::ToastError(err.to_string()),
+                                    Err(err) => Message::ToastError(err.to_string()),
+                                }
+                            })
+                            .map(cosmic::Action::App);
+                        }
+                    }
+                    Page::Genres(page) => {
+                        if let GenresPageState::Loading = page.page_state {
+                            return cosmic::Task::future(async move {
+                                match tokio::task::spawn_blocking(library::genres).await {
+                                    Ok(Ok(genres)) => Message::GenresLoaded(
+                                        genres.into_iter().map(GenreInfo::from).collect(),
+                                    ),
+                                    Ok(Err(err)) => Message::ToastError(err.to_string()),
+                                    Err(err) => Message::ToastError(err.to_string()),
+                                }
+                            })
+                            .map(cosmic::Action::App);
+                        }
+                    }
                 }
             }
 
             Message::ArtistRequested(artist) => {
-                let conn = rusqlite::Connection::open(
-                    dirs::data_local_dir()
-                        .unwrap()
-                        .join(Self::APP_ID)
-                        .join("nova_music.db"),
-                )
-                .unwrap();
-
-                let mut stmt = conn.prepare(
-                    "
-                    SELECT a.id, a.name as name, a.album_cover as cover, art.name as artist, a.disc_number as dn, a.track_number as tn
-                    FROM album a
-                             left JOIN artists art ON a.artist_id = art.id
-                    Where art.name = ?"
-                ).expect("SQL is wrong");
-
-                let val = stmt
-                    .query_map([&artist], |row| {
-                        Ok(Album {
-                            name: row.get("name").expect("get name fail"),
-                            artist: row.get("artist").expect("get artist fail"),
-                            cover_art: match row.get::<_, Vec<u8>>("cover") {
-                                Ok(val) => Some(cosmic::widget::image::Handle::from_bytes(val)),
-                                Err(_) => None,
-                            },
-                            disc_number: row.get("dn").expect("get disc number fail"),
-                            track_number: row.get("tn").expect("get track number fail"),
-                        })
-                    })
-                    .unwrap();
-
-                //todo single really only needs a reference to track_id
-                let mut stmt = conn
-                    .prepare(
-                        "
-SELECT t.id as track_id, t.name as name, a.name as artist, s.cover as cover
-FROM single s
-    left join track t on s.track_id = t.id
-    left join artists a on t.artist_id = a.id
-where a.name = ?    ",
-                    )
-                    .expect("SQL is wrong");
-
-                let albums: Vec<Album> = val
-                    .into_iter()
-                    .filter_map(|a| a.ok())
-                    .collect::<Vec<Album>>();
-
-                let val = stmt
-                    .query_map([&artist], |row| {
-                        Ok(DisplaySingle {
-                            id: row.get("track_id").unwrap_or(0),
-                            title: row.get("name").expect("get name fail"),
-                            artist: row.get("artist").expect("get artist fail"),
-                            cover_art: match row.get::<_, Vec<u8>>("cover") {
-                                Ok(val) => Some(cosmic::widget::image::Handle::from_bytes(val)),
-                                Err(_) => None,
-                            },
-                        })
-                    })
-                    .unwrap();
-
-                let singles: Vec<DisplaySingle> = val
-                    .into_iter()
-                    .filter_map(|a| a.ok())
-                    .collect::<Vec<DisplaySingle>>();
-
-                let image = conn.query_row(
-                    "select artistpfp from artists where name = ?",
-                    [&artist],
-                    |row| row.get::<_, Vec<u8>>("artistpfp"),
-                );
+                let artist = match library::artist_page(&artist) {
+                    Ok(artist) => artist,
+                    Err(err) => {
+                        log::error!("Artist page could not be loaded: {}", err);
+                        return self
+                            .toasts
+                            .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                            .map(cosmic::Action::App);
+                    }
+                };
 
                 let new_page = crate::app::artists::ArtistPage {
                     artist: ArtistInfo {
-                        name: artist,
+                        name: artist.name,
                         path: String::from(""),
-                        image: match image {
-                            Ok(val) => Some(cosmic::widget::image::Handle::from_bytes(val)),
-                            Err(err) => {
-                                log::warn!("Possible error: {}", err);
-                                None
-                            }
-                        },
+                        image: artist
+                            .picture
+                            .map(cosmic::widget::image::Handle::from_bytes),
                     },
-                    singles,
-                    albums,
+                    singles: artist
+                        .singles
+                        .into_iter()
+                        .map(|single| DisplaySingle {
+                            id: single.track_id,
+                            title: single.title,
+                            artist: single.artist,
+                            cover: single.cover,
+                        })
+                        .collect(),
+                    albums: artist.albums.into_iter().map(Album::from).collect(),
+                    appears_on: artist.appears_on.into_iter().map(Album::from).collect(),
+                    credits: artist.credits,
                 };
 
                 if let Page::Artist(page) = self.nav.data_mut::<Page>(self.artistsid).unwrap() {
@@ -2080,6 +2377,91 @@ where a.name = ?    ",
                 }
             }
 
+            Message::ComposersLoaded(composers) => {
+                if let Some(Page::Composers(page)) = self.nav.data_mut::<Page>(self.composersid) {
+                    page.composers = composers;
+                    page.page_state = ComposersPageState::Loaded;
+                }
+            }
+
+            Message::ComposerRequested(name) => {
+                let works = match library::works_by_composer(&name) {
+                    Ok(works) => works,
+                    Err(err) => {
+                        log::error!("Composer page could not be loaded: {}", err);
+                        return self
+                            .toasts
+                            .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                            .map(cosmic::Action::App);
+                    }
+                };
+
+                if let Some(Page::Composers(page)) = self.nav.data_mut::<Page>(self.composersid) {
+                    page.page_state = ComposersPageState::Composer(ComposerPage { name, works })
+                }
+            }
+
+            Message::ComposerPageReturn => {
+                if let Some(Page::Composers(page)) = self.nav.data_mut::<Page>(self.composersid) {
+                    page.page_state = ComposersPageState::Loaded
+                }
+            }
+
+            Message::GenresLoaded(genres) => {
+                if let Some(Page::Genres(page)) = self.nav.data_mut::<Page>(self.genresid) {
+                    page.genres = genres;
+                    page.page_state = GenresPageState::Loaded;
+                }
+            }
+
+            Message::GenreRequested(name) => {
+                let genre = match library::genre_page(&name) {
+                    Ok(genre) => genre,
+                    Err(err) => {
+                        log::error!("Genre page could not be loaded: {}", err);
+                        return self
+                            .toasts
+                            .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                            .map(cosmic::Action::App);
+                    }
+                };
+
+                if let Some(Page::Genres(page)) = self.nav.data_mut::<Page>(self.genresid) {
+                    page.page_state = GenresPageState::Genre(GenrePage::from(genre))
+                }
+            }
+
+            Message::GenrePageReturn => {
+                if let Some(Page::Genres(page)) = self.nav.data_mut::<Page>(self.genresid) {
+                    // Going back from an album shows the genre it was opened from
+                    page.page_state = match (&page.page_state, page.genre_page_cache.take()) {
+                        (GenresPageState::Album(_), Some(genre)) => GenresPageState::Genre(genre),
+                        _ => GenresPageState::Loaded,
+                    };
+                }
+            }
+
+            Message::GenreShuffle => {
+                if let Some(Page::Genres(page)) = self.nav.data::<Page>(self.genresid) {
+                    if let GenresPageState::Genre(genre) = &page.page_state {
+                        let mut paths = genre
+                            .tracks
+                            .iter()
+                            .map(|track| track.path.to_string_lossy().to_string())
+                            .collect::<Vec<String>>();
+                        paths.shuffle(&mut rand::rng());
+
+                        return self.update(Message::AddAlbumToQueue(
+                            paths
+                                .into_iter()
+                                .enumerate()
+                                .map(|(index, path)| (path, index as u32))
+                                .collect(),
+                        ));
+                    }
+                }
+            }
+
             Message::ArtistPageReturn => {
                 if let Page::Artist(artistpage) = self
                     .nav
@@ -2160,9 +2542,13 @@ where a.name = ?    ",
                         if is_m3u && track_title.is_some() {
                             let path = PathBuf::from(line);
 
+                            let path = path.to_string_lossy().to_string();
                             tracks.push(PlaylistTrack {
                                 title: track_title.take().unwrap().parse().unwrap(),
-                                path: path.to_string_lossy().to_string(),
+                                duration: library::track_by_path(&path)
+                                    .ok()
+                                    .and_then(|track| track.properties.duration),
+                                path,
                             });
                         }
                     }
@@ -2249,6 +2635,7 @@ where a.name = ?    ",
 
                 match self.nav.active_data_mut::<Page>().unwrap() {
                     Page::Artist(page) => page.page_state = ArtistPageState::Album(fullalbum),
+                    Page::Genres(page) => page.page_state = GenresPageState::Album(fullalbum),
                     Page::Albums(page) => {
                         page.page_state = AlbumPageState::Album(fullalbum);
                     }
@@ -2265,10 +2652,11 @@ where a.name = ?    ",
                         return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
                             0,
                             |mut tx| async move {
-                                let album = get_album_info(dat.0, dat.1).await;
-                                tx.send(Message::AlbumInfoRetrieved(album))
-                                    .await
-                                    .expect("send")
+                                let message = match get_album_info(dat.0, dat.1).await {
+                                    Ok(album) => Message::AlbumInfoRetrieved(album),
+                                    Err(err) => Message::ToastError(err.to_string()),
+                                };
+                                tx.send(message).await.expect("send")
                             },
                         ))
                         .map(cosmic::Action::App)
@@ -2279,10 +2667,27 @@ where a.name = ?    ",
                             return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
                                 0,
                                 |mut tx| async move {
-                                    let album = get_album_info(dat.0, dat.1).await;
-                                    tx.send(Message::AlbumInfoRetrieved(album))
-                                        .await
-                                        .expect("send")
+                                    let message = match get_album_info(dat.0, dat.1).await {
+                                        Ok(album) => Message::AlbumInfoRetrieved(album),
+                                        Err(err) => Message::ToastError(err.to_string()),
+                                    };
+                                    tx.send(message).await.expect("send")
+                                },
+                            ))
+                            .map(cosmic::Action::App);
+                        }
+                    }
+                    Page::Genres(toppage) => {
+                        if let GenresPageState::Genre(page) = &toppage.page_state {
+                            toppage.genre_page_cache = Some(page.clone());
+                            return cosmic::Task::stream(cosmic::iced_futures::stream::channel(
+                                0,
+                                |mut tx| async move {
+                                    let message = match get_album_info(dat.0, dat.1).await {
+                                        Ok(album) => Message::AlbumInfoRetrieved(album),
+                                        Err(err) => Message::ToastError(err.to_string()),
+                                    };
+                                    tx.send(message).await.expect("send")
                                 },
                             ))
                             .map(cosmic::Action::App);
@@ -2294,6 +2699,14 @@ where a.name = ?    ",
                     }
                 }
             }
+            Message::AlbumSortChanged(sort) => {
+                self.config
+                    .set_album_sort(&self.config_handler, sort)
+                    .expect("Failed To Update Config");
+                if let Some(Page::Albums(page)) = self.nav.data_mut::<Page>(self.albumsid) {
+                    page.sort_albums(sort);
+                }
+            }
             app::Message::GridSliderChange(val) => {
                 self.config
                     .set_grid_item_size(&self.config_handler, val)
@@ -2306,83 +2719,67 @@ where a.name = ?    ",
                     Err(_) => {}
                 }
             }
-            Message::SeekFinished => self.sink.set_volume(self.config.volume / 100.0),
+            Message::SeekTo(val) => {
+                if self.sink.try_seek(Duration::from_secs_f64(val)).is_ok() {
+                    self.song_progress = val;
+                    if let Some(mpris) = &self.mpris {
+                        mpris.seeked(self.sink.get_pos());
+                    }
+                }
+            }
+            Message::LyricsLoaded(track, lyrics) => {
+                if let Some(Page::NowPlaying(page)) = self.nav.data_mut::<Page>(self.homeid) {
+                    if page.lyrics_track == Some(track) {
+                        page.lyrics = lyrics.map(|text| Lyrics::parse(&text));
+                        page.lyrics_line = None;
+                    }
+                }
+            }
+            Message::SeekFinished => {
+                self.sink.set_volume(self.config.volume / 100.0);
+                if let Some(mpris) = &self.mpris {
+                    mpris.seeked(self.sink.get_pos());
+                }
+            }
             app::Message::AddTrackToQueue(filepath) => {
                 let pos = self.nav.entity_at(0).expect("REASON");
                 let home_page = self.nav.data_mut::<Page>(pos).unwrap();
                 if let Page::NowPlaying(_) = home_page {
-                    let conn = rusqlite::Connection::open(
-                        dirs::data_local_dir()
-                            .unwrap()
-                            .join(Self::APP_ID)
-                            .join("nova_music.db"),
-                    )
-                    .unwrap();
-                    let mut stmt = conn
-                        .prepare(
-                            "
-                                select track.id as id, track.name as title, art.name as artist, track.path, a.album_cover, a.name as album_title
-                                from track
-                                left join main.album_tracks at on track.id = at.track_id
-                                left join main.artists art on track.artist_id = art.id
-                                left join main.album a on at.album_id = a.id
-                                where track.path=?;
-                            ",
-                        )
-                        .expect("error preparing sql");
-
-                    if let Ok(track) = stmt.query_row([&filepath], |row| {
-                        Ok(AppTrack {
-                            id: row.get("id").unwrap_or(0),
-                            title: row.get("title").unwrap_or("".to_string()),
-                            artist: row.get("artist").unwrap_or("".to_string()),
-                            album_title: row.get("album_title").unwrap_or("".to_string()),
-                            path_buf: PathBuf::from(
-                                row.get::<&str, String>("path")
-                                    .expect("There should always be a file path"),
-                            ),
-                            cover_art: match row.get::<&str, Vec<u8>>("album_cover") {
-                                Ok(val) => Some(cosmic::widget::image::Handle::from_bytes(val)),
-                                Err(_) => None,
-                            },
-                        })
-                    }) {
-                        self.queue.push(track);
-                    } else {
-                        return self
-                            .toasts
-                            .push(cosmic::widget::toaster::Toast::new(format!(
-                                "Track at \"{}\" not found in database",
-                                filepath
-                            )))
-                            .map(cosmic::Action::App);
+                    match library::track_by_path(&filepath) {
+                        Ok(track) => self.queue.push(AppTrack {
+                            id: track.id,
+                            title: track.title,
+                            artist: track.artist,
+                            album_title: track.album_title,
+                            path_buf: track.path,
+                            cover_art: track
+                                .cover
+                                .map(|cover| artwork::handle(&cover, artwork::LARGEST)),
+                            duration: track.properties.duration,
+                            replay_gain: track.replay_gain,
+                        }),
+                        Err(err) => {
+                            return self
+                                .toasts
+                                .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                                .map(cosmic::Action::App);
+                        }
                     }
                 }
 
                 if self.sink.empty() {
-                    let file = match std::fs::File::open(&filepath) {
-                        Ok(file) => file,
+                    let decoder = match self.open_source(Path::new(&filepath)) {
+                        Ok(decoder) => decoder,
                         Err(err) => {
                             log::error!("Error: {}", err);
 
                             return self
                                 .toasts
-                                .push(cosmic::widget::toaster::Toast::new(format!(
-                                    "Track found in database but not at the filepath: {}",
-                                    filepath.to_string()
-                                )))
+                                .push(cosmic::widget::toaster::Toast::new(err))
                                 .map(cosmic::Action::App);
                         }
                     };
 
-                    let decoder = rodio::Decoder::builder()
-                        .with_byte_len(file.metadata().unwrap().len())
-                        .with_data(file)
-                        .with_gapless(true)
-                        .with_seekable(true)
-                        .build()
-                        .expect("Failed to build decoder");
-
                     self.song_duration = decoder.total_duration().map(|val| val.as_secs_f64());
                     self.sink.append(decoder);
                     let sleeping_task_sink = Arc::clone(&self.sink);
@@ -2609,17 +3006,18 @@ where a.name = ?    ",
                 }
             }
             Message::AddTrackToSink(filepath) => {
-                let file = std::fs::File::open(filepath).expect("Failed to open file");
-
-                let decoder = rodio::Decoder::builder()
-                    .with_byte_len(file.metadata().unwrap().len())
-                    .with_data(file)
-                    .with_gapless(true)
-                    .with_seekable(true)
-                    .build()
-                    .expect("Failed to build decoder");
+                let decoder = match self.open_source(Path::new(&filepath)) {
+                    Ok(decoder) => decoder,
+                    Err(err) => {
+                        log::error!("Error: {}", err);
+                        return self
+                            .toasts
+                            .push(cosmic::widget::toaster::Toast::new(err))
+                            .map(cosmic::Action::App);
+                    }
+                };
 
-                self.song_duration = Some(decoder.total_duration().unwrap().as_secs_f64());
+                self.song_duration = decoder.total_duration().map(|val| val.as_secs_f64());
                 self.sink.append(decoder);
 
                 let task_sink = Arc::clone(&self.sink);
@@ -2885,7 +3283,11 @@ where a.name = ?    ",
                     new_file
                         .write_all(
                             format!(
-                                "#EXTINF:0,{} - {}\n{}\n",
+                                "#EXTINF:{},{} - {}\n{}\n",
+                                // m3u wants -1 for an unknown length
+                                track
+                                    .duration
+                                    .map_or(-1, |duration| duration.round() as i64),
                                 track.artist,
                                 track.title,
                                 track.path_buf.to_string_lossy().to_string()
@@ -2925,6 +3327,8 @@ where a.name = ?    ",
                     Page::Artist(page) => {
                         page.page_state = ArtistPageState::Search(tracks);
                     }
+                    Page::Composers(_) => {}
+                    Page::Genres(page) => page.page_state = GenresPageState::Search(tracks),
                 }
             }
             Message::ToggleTitle(val) => {
@@ -2935,6 +3339,7 @@ where a.name = ?    ",
                 {
                     page.search_by_title = val
                 }
+                return self.update(Message::UpdateSearch(self.search_field.clone()));
             }
             Message::ToggleAlbum(val) => {
                 if let Page::Tracks(page) = self
@@ -2944,6 +3349,7 @@ where a.name = ?    ",
                 {
                     page.search_by_album = val
                 }
+                return self.update(Message::UpdateSearch(self.search_field.clone()));
             }
             Message::ToggleArtist(val) => {
                 if let Page::Tracks(page) = self
@@ -2953,6 +3359,7 @@ where a.name = ?    ",
                 {
                     page.search_by_artist = val
                 }
+                return self.update(Message::UpdateSearch(self.search_field.clone()));
             }
             Message::VolumeSliderChange(val) => {
                 log::info!("volume: {}", val);
@@ -2961,59 +3368,62 @@ where a.name = ?    ",
                     .set_volume(&self.config_handler, val)
                     .expect("Failed to set volume");
             }
+            Message::ReplayGainModeChange(mode) => {
+                self.config
+                    .set_replay_gain(&self.config_handler, mode)
+                    .expect("Failed to edit config");
+            }
+            Message::ReplayGainPreampChange(val) => {
+                self.config
+                    .set_replay_gain_preamp(&self.config_handler, val)
+                    .expect("Failed to edit config");
+            }
+            Message::TogglePreventClipping(val) => {
+                self.config
+                    .set_prevent_clipping(&self.config_handler, val)
+                    .expect("Failed to edit config");
+            }
             Message::ToggleFooter(val) => {
                 self.config
                     .set_footer(&self.config_handler, val)
                     .expect("Failed to edit config");
             }
             app::Message::AddTrackById((t_type, id)) => {
-                let conn = connect_to_db();
-
-                let mut stmt =
-                    "
-                                select track.id as id, track.name as title, art.name as artist, track.path as path, a.album_cover, a.name as album_title
-                                from track
-                                left join main.album_tracks at on track.id = at.track_id
-                                left join main.artists art on track.artist_id = art.id
-                                left join main.album a on at.album_id = a.id
-                                where track.id = ?
-                            ";
-
-                if let Ok(result) = conn.query_row(stmt, [&id], |row| {
-                    let filepath = PathBuf::from(row.get::<_, String>("path").unwrap());
-                    let visual = find_visual(&filepath);
-
-                    Ok(AppTrack {
-                        id: row.get("id").unwrap(),
-                        artist: row.get("artist").unwrap(),
-                        path_buf: filepath,
-                        title: row.get("title").unwrap(),
+                match library::track_by_id(id) {
+                    Ok(track) => self.queue.push(AppTrack {
+                        id: track.id,
+                        artist: track.artist,
+                        title: track.title,
                         album_title: match t_type {
-                            TrackType::AlbumTrack => {
-                                row.get("album_title").unwrap_or(String::from(""))
-                            }
+                            TrackType::AlbumTrack => track.album_title,
                             TrackType::Single => String::from(""),
                         },
-                        cover_art: match visual {
-                            Some(cover) => Some(cosmic::widget::image::Handle::from_bytes(cover)),
-                            None => None,
-                        },
-                    })
-                }) {
-                    self.queue.push(result)
+                        path_buf: track.path,
+                        cover_art: track
+                            .cover
+                            .map(|cover| artwork::handle(&cover, artwork::LARGEST)),
+                        duration: track.properties.duration,
+                        replay_gain: track.replay_gain,
+                    }),
+                    Err(err) => {
+                        return self
+                            .toasts
+                            .push(cosmic::widget::toaster::Toast::new(err.to_string()))
+                            .map(cosmic::Action::App);
+                    }
                 }
 
                 if self.sink.empty() {
-                    let file = std::fs::File::open(self.queue.get(0).unwrap().path_buf.clone())
-                        .expect("Failed to open file");
-
-                    let decoder = rodio::Decoder::builder()
-                        .with_byte_len(file.metadata().unwrap().len())
-                        .with_data(file)
-                        .with_gapless(true)
-                        .with_seekable(true)
-                        .build()
-                        .expect("Failed to build decoder");
+                    let decoder = match self.open_source(&self.queue[0].path_buf) {
+                        Ok(decoder) => decoder,
+                        Err(err) => {
+                            log::error!("Error: {}", err);
+                            return self
+                                .toasts
+                                .push(cosmic::widget::toaster::Toast::new(err))
+                                .map(cosmic::Action::App);
+                        }
+                    };
 
                     self.song_duration = decoder.total_duration().map(|val| val.as_secs_f64());
                     self.sink.append(decoder);
@@ -3078,64 +3488,6 @@ where a.name = ?    ",
         Task::none()
     }
 
-    /// Called when a nav item is selected.
-    fn on_nav_select(&mut self, id: nav_bar::Id) -> Task<cosmic::Action<Self::Message>> {
-        // Activate the page in the model.
-        self.nav.activate(id);
-        self.update_title()
-    }
-
-    fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
-        struct KeybindSubscription;
-
-        cosmic::iced::Subscription::batch(vec![
-            // Watch for application configuration changes.
-            cosmic::iced::event::listen_with(handle_keybinds),
-        ])
-    }
-}
-
-fn handle_keybinds(
-    event: cosmic::iced::event::Event,
-    a: cosmic::iced::event::Status,
-    _: cosmic::iced::window::Id,
-) -> Option<Message> {
-    if let cosmic::iced::event::Status::Captured = a {
-        return None;
-    }
-
-    match event {
-        Event::Keyboard(key) => {
-            if let keyboard::Event::KeyPressed { key, .. } = key {
-                log::info!("[{:?}]", key);
-                match key {
-                    cosmic::iced::keyboard::Key::Named(
-                        cosmic::iced::keyboard::key::Named::Space,
-                    ) => return Some(Message::PlayPause),
-
-                    cosmic::iced::keyboard::Key::Named(
-                        cosmic::iced::keyboard::key::Named::MediaSkipBackward,
-                    ) => return Some(Message::PreviousTrack),
-
-                    cosmic::iced::keyboard::Key::Named(
-                        cosmic::iced::keyboard::key::Named::MediaSkipForward,
-                    ) => return Some(Message::SkipTrack),
-
-                    cosmic::iced::keyboard::Key::Named(
-                        cosmic::iced::keyboard::key::Named::MediaPlayPause,
-                    ) => return Some(Message::PlayPause),
-
-                    _ => None,
-                }
-            } else {
-                None
-            }
-        }
-        _ => None,
-    }
-}
-
-impl AppModel {
     /// The about page for this app.
     pub fn about<'a>(&self) -> Element<'a, Message> {
         let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
@@ -3198,6 +3550,8 @@ impl AppModel {
 pub enum Page {
     NowPlaying(HomePage),
     Artist(ArtistsPage),
+    Composers(ComposersPage),
+    Genres(GenresPage),
     Albums(AlbumPage),
     Playlists(PlaylistPage),
     Tracks(TrackPage),
@@ -3209,6 +3563,8 @@ pub enum ContextPage {
     #[default]
     About,
     Settings,
+    ScanProblems,
+    TrackProperties,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -3227,18 +3583,3 @@ impl menu::action::MenuAction for Action {
         }
     }
 }
-
-fn connect_to_db() -> rusqlite::Connection {
-    let conn = match rusqlite::Connection::open(
-        dirs::data_local_dir()
-            .unwrap()
-            .join("dev.lunarsrl.NovaMusic")
-            .join("nova_music.db"),
-    ) {
-        Ok(conn) => conn,
-        Err(err) => {
-            panic!("{}", err)
-        }
-    };
-    conn
-}
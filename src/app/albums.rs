@@ -1,8 +1,11 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
-use crate::app::tracks::SearchResult;
+use crate::app::home::{format_time, total_runtime};
+use crate::app::tracks::{library_search_input, SearchResult};
 use crate::app::{AppModel, Message};
-use crate::{app, fl};
+use crate::config::AlbumSort;
+use crate::library::{self, LibraryError};
+use crate::{app, artwork, fl};
 use cosmic::iced::futures::channel::mpsc::Sender;
 use cosmic::iced::{Alignment, ContentFit, Length};
 use cosmic::iced_widget::scrollable::Viewport;
@@ -67,13 +70,7 @@ impl AlbumPage {
                                         cosmic::widget::horizontal_space()
                                             .width(Length::Shrink)
                                             .into(),
-                                        cosmic::widget::search_input(
-                                            fl!("AlbumInputPlaceholder"),
-                                            model.search_field.as_str(),
-                                        )
-                                            .on_input(|input| Message::UpdateSearch(input))
-                                            .width(Length::FillPortion(1))
-                                            .into(),
+                                        library_search_input(fl!("AlbumInputPlaceholder"), model),
                                     ])
                                         .padding(iced::core::padding::Padding::from([
                                             0,
@@ -107,13 +104,15 @@ impl AlbumPage {
                             cosmic::widget::horizontal_space()
                                 .width(Length::Shrink)
                                 .into(),
-                            cosmic::widget::search_input(
-                                fl!("AlbumInputPlaceholder"),
-                                model.search_field.as_str(),
+                            cosmic::widget::dropdown(
+                                AlbumSort::ALL.map(sort_label).to_vec(),
+                                AlbumSort::ALL
+                                    .iter()
+                                    .position(|sort| *sort == model.config.album_sort),
+                                |index| Message::AlbumSortChanged(AlbumSort::ALL[index]),
                             )
-                            .on_input(|input| Message::UpdateSearch(input))
-                            .width(Length::FillPortion(1))
                             .into(),
+                            library_search_input(fl!("AlbumInputPlaceholder"), model),
                         ])
                         .padding(iced::core::padding::Padding::from([
                             0,
@@ -124,108 +123,39 @@ impl AlbumPage {
                         .into(),
                         cosmic::widget::container(cosmic::widget::responsive(move |size| {
                             // Body
-                            let mut elements: Vec<Element<Message>> = vec![];
-
-                            for album in self.albums.as_ref() {
-                                elements.push(
-                                    cosmic::widget::button::custom(
-                                        cosmic::widget::column::with_children(vec![
-                                            if let Some(cover_art) = &album.cover_art {
-                                                cosmic::widget::container::Container::new(
-                                                    cosmic::widget::image(cover_art)
-                                                        .content_fit(ContentFit::Fill),
-                                                )
-                                                .height((model.config.grid_item_size * 32) as f32)
-                                                .width((model.config.grid_item_size * 32) as f32)
-                                                .into()
-                                            } else {
-                                                cosmic::widget::container(
-                                                    cosmic::widget::icon::from_name(
-                                                        "media-optical-symbolic",
-                                                    )
-                                                        .size((model.config.grid_item_size * 32) as u16),
-                                                )
-                                                .align_x(Alignment::Center)
-                                                .align_y(Alignment::Center)
-                                                .into()
-                                            },
+                            let width = size.width - cosmic::theme::spacing().space_m as f32 * 2.0;
+
+                            let body: Element<Message> = match model.config.album_sort {
+                                AlbumSort::Year => {
+                                    // Sorted by year already, so every decade is one run
+                                    let sections = self
+                                        .albums
+                                        .chunk_by(|a, b| decade(a) == decade(b))
+                                        .map(|albums| {
+                                            let heading = match decade(&albums[0]) {
+                                                Some(decade) => fl!("Decade", decade = decade),
+                                                None => fl!("UnknownYear"),
+                                            };
                                             cosmic::widget::column::with_children(vec![
-                                                cosmic::widget::text::text(album.name.as_str())
-                                                    .center()
-                                                    .into(),
-                                                cosmic::widget::text::text(album.artist.as_str())
-                                                    .center()
-                                                    .into(),
+                                                cosmic::widget::text::title4(heading).into(),
+                                                album_grid(albums, width, model),
                                             ])
-                                            .align_x(Alignment::Center)
-                                            .width(cosmic::iced::Length::Fill)
-                                            .into(),
-                                        ]),
-                                    )
-                                    .class(cosmic::widget::button::ButtonClass::Icon)
-                                    .on_press(Message::AlbumRequested((
-                                        album.name.clone(),
-                                        album.artist.clone(),
-                                    )))
-                                    .width((model.config.grid_item_size * 32) as f32)
-                                    .into(),
-                                )
-                            }
-
-                            let mut old_grid = Some(
-                                cosmic::widget::Grid::new()
-                                    .width(Length::Fill)
-                                    .height(Length::Shrink),
-                            );
-
-                            let width =
-                                size.width as u32 - cosmic::theme::spacing().space_m as u32 * 2;
-                            let spacing;
-                            let mut items_per_row = 0;
-                            let mut index = 0;
-
-                            while width > (items_per_row * model.config.grid_item_size * 32) {
-                                items_per_row += 1;
-                            }
-                            items_per_row -= 1;
-
-                            let check_spacing: u32 =
-                                ((items_per_row + 1) * model.config.grid_item_size * 32)
-                                    .saturating_sub(width);
-                            let check_final = model.config.grid_item_size * 32 - check_spacing;
-
-                            if items_per_row < 3 {
-                                spacing = check_final as u16
-                            } else {
-                                spacing = (check_final / (items_per_row - 1)) as u16;
-                            }
+                                            .spacing(cosmic::theme::spacing().space_xs)
+                                            .into()
+                                        })
+                                        .collect::<Vec<Element<Message>>>();
 
-                            for element in elements {
-                                index += 1;
-                                if let Some(grid) = old_grid.take() {
-                                    if (index % items_per_row) == 0 {
-                                        old_grid = Some(grid.push(element).insert_row());
-                                    } else {
-                                        old_grid = Some(grid.push(element));
-                                    }
+                                    cosmic::widget::column::with_children(sections)
+                                        .spacing(cosmic::theme::spacing().space_m)
+                                        .into()
                                 }
-                            }
+                                _ => album_grid(&self.albums, width, model),
+                            };
 
-                            cosmic::widget::scrollable::vertical(
-                                cosmic::widget::container(
-                                    old_grid
-                                        .take()
-                                        .unwrap()
-                                        .column_spacing(spacing)
-                                        .column_alignment(Alignment::Center)
-                                        .justify_content(JustifyContent::Center)
-                                        .row_alignment(Alignment::Center),
-                                )
-                                .align_x(Alignment::Center),
-                            )
-                            .id(self.scrollbar_id.clone())
-                            .on_scroll(|view| Message::ScrollView(view))
-                            .into()
+                            cosmic::widget::scrollable::vertical(body)
+                                .id(self.scrollbar_id.clone())
+                                .on_scroll(|view| Message::ScrollView(view))
+                                .into()
                         }))
                         .height(Length::Fill)
                         .into(),
@@ -248,13 +178,7 @@ impl AlbumPage {
                         cosmic::widget::horizontal_space()
                             .width(Length::Shrink)
                             .into(),
-                        cosmic::widget::search_input(
-                            fl!("AlbumInputPlaceholder"),
-                            model.search_field.as_str(),
-                        )
-                        .on_input(|input| Message::UpdateSearch(input))
-                        .width(Length::FillPortion(1))
-                        .into(),
+                        library_search_input(fl!("AlbumInputPlaceholder"), model),
                     ])
                     .padding(iced::core::padding::Padding::from([
                         0,
@@ -269,12 +193,10 @@ impl AlbumPage {
                         let mut albums: Vec<Album> = vec![];
 
                         for each in search_results {
-                            if (0..=2).contains(&each.score) {
-                                match self.albums.get(each.tracks_index) {
-                                    None => {}
-                                    Some(val) => {
-                                        albums.push(val.clone());
-                                    }
+                            match self.albums.get(each.tracks_index) {
+                                None => {}
+                                Some(val) => {
+                                    albums.push(val.clone());
                                 }
                             }
                         }
@@ -283,9 +205,12 @@ impl AlbumPage {
                             elements.push(
                                 cosmic::widget::button::custom(
                                     cosmic::widget::column::with_children(vec![
-                                        if let Some(cover_art) = &album.cover_art {
+                                        if let Some(cover) = &album.cover {
                                             cosmic::widget::container::Container::new(
-                                                cosmic::widget::image(cover_art),
+                                                cosmic::widget::image(artwork::handle(
+                                                    cover,
+                                                    model.config.grid_item_size * 32,
+                                                )),
                                             )
                                             .height((model.config.grid_item_size * 32) as f32)
                                             .width((model.config.grid_item_size * 32) as f32)
@@ -383,42 +308,262 @@ impl AlbumPage {
             }
         }
     }
+
+    /// Orders the albums the way the grid lists them
+    pub fn sort_albums(&mut self, sort: AlbumSort) {
+        let albums = Arc::make_mut(&mut self.albums);
+        match sort {
+            AlbumSort::Added => albums.sort_by_key(|album| album.id),
+            AlbumSort::Title => albums.sort_by_cached_key(|album| {
+                (album.name.to_lowercase(), album.artist.to_lowercase())
+            }),
+            AlbumSort::Artist => albums.sort_by_cached_key(|album| {
+                (
+                    album.artist.to_lowercase(),
+                    album.year,
+                    album.name.to_lowercase(),
+                )
+            }),
+            AlbumSort::Year => albums.sort_by_cached_key(|album| {
+                (
+                    album.year.is_none(),
+                    album.year,
+                    album.artist.to_lowercase(),
+                    album.name.to_lowercase(),
+                )
+            }),
+        }
+    }
+}
+
+fn sort_label(sort: AlbumSort) -> String {
+    match sort {
+        AlbumSort::Added => fl!("AlbumSortAdded"),
+        AlbumSort::Title => fl!("AlbumSortTitle"),
+        AlbumSort::Artist => fl!("AlbumSortArtist"),
+        AlbumSort::Year => fl!("AlbumSortYear"),
+    }
+}
+
+/// First year of the decade an album came out in
+fn decade(album: &Album) -> Option<i32> {
+    album.year.map(|year| year - year.rem_euclid(10))
+}
+
+/// Covers of `albums` in as many columns as fit in `width`
+fn album_grid<'a>(albums: &'a [Album], width: f32, model: &AppModel) -> Element<'a, Message> {
+    let item_size = model.config.grid_item_size * 32;
+    let mut elements: Vec<Element<Message>> = vec![];
+
+    for album in albums {
+        elements.push(
+            cosmic::widget::button::custom(cosmic::widget::column::with_children(vec![
+                if let Some(cover) = &album.cover {
+                    cosmic::widget::container::Container::new(
+                        cosmic::widget::image(artwork::handle(cover, item_size))
+                            .content_fit(ContentFit::Fill),
+                    )
+                    .height(item_size as f32)
+                    .width(item_size as f32)
+                    .into()
+                } else {
+                    cosmic::widget::container(
+                        cosmic::widget::icon::from_name("media-optical-symbolic")
+                            .size(item_size as u16),
+                    )
+                    .align_x(Alignment::Center)
+                    .align_y(Alignment::Center)
+                    .into()
+                },
+                cosmic::widget::column::with_children(vec![
+                    cosmic::widget::text::text(album.name.as_str())
+                        .center()
+                        .into(),
+                    cosmic::widget::text::text(album.artist.as_str())
+                        .center()
+                        .into(),
+                ])
+                .push_maybe(
+                    album
+                        .year
+                        .map(|year| cosmic::widget::text::caption(year.to_string()).center()),
+                )
+                .align_x(Alignment::Center)
+                .width(cosmic::iced::Length::Fill)
+                .into(),
+            ]))
+            .class(cosmic::widget::button::ButtonClass::Icon)
+            .on_press(Message::AlbumRequested((
+                album.name.clone(),
+                album.artist.clone(),
+            )))
+            .width(item_size as f32)
+            .into(),
+        )
+    }
+
+    cover_grid(elements, item_size, width)
+}
+
+/// Lays out `elements`, each `item_size` wide, in as many columns as fit in `width`
+pub(crate) fn cover_grid(
+    elements: Vec<Element<'_, Message>>,
+    item_size: u32,
+    width: f32,
+) -> Element<'_, Message> {
+    let mut old_grid = Some(
+        cosmic::widget::Grid::new()
+            .width(Length::Fill)
+            .height(Length::Shrink),
+    );
+
+    let width = width as u32;
+    let spacing;
+    let mut items_per_row = 0;
+    let mut index = 0;
+
+    while width > (items_per_row * item_size) {
+        items_per_row += 1;
+    }
+    items_per_row -= 1;
+
+    let check_spacing: u32 = ((items_per_row + 1) * item_size).saturating_sub(width);
+    let check_final = item_size - check_spacing;
+
+    if items_per_row < 3 {
+        spacing = check_final as u16
+    } else {
+        spacing = (check_final / (items_per_row - 1)) as u16;
+    }
+
+    for element in elements {
+        index += 1;
+        if let Some(grid) = old_grid.take() {
+            if (index % items_per_row) == 0 {
+                old_grid = Some(grid.push(element).insert_row());
+            } else {
+                old_grid = Some(grid.push(element));
+            }
+        }
+    }
+
+    cosmic::widget::container(
+        old_grid
+            .take()
+            .unwrap()
+            .column_spacing(spacing)
+            .column_alignment(Alignment::Center)
+            .justify_content(JustifyContent::Center)
+            .row_alignment(Alignment::Center),
+    )
+    .align_x(Alignment::Center)
+    .into()
 }
 
 #[derive(Debug, Clone)]
 pub struct Album {
+    pub(crate) id: u32,
     pub name: String,
     pub artist: String,
     pub(crate) disc_number: u32,
     pub(crate) track_number: u32,
-    pub cover_art: Option<cosmic::widget::image::Handle>,
+    /// Hash of the cover in [`artwork`], the grids load it at the size they show it
+    pub cover: Option<String>,
+    pub year: Option<i32>,
+}
+
+impl From<library::Album> for Album {
+    fn from(album: library::Album) -> Self {
+        Album {
+            id: album.id,
+            name: album.title,
+            artist: album.artist,
+            disc_number: album.disc_count,
+            track_number: album.track_count,
+            cover: album.cover,
+            year: album.year,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FullAlbum {
     album: Album,
     tracks: Vec<Track>,
+    compilation: bool,
+    release_date: Option<String>,
+    original_date: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 struct Track {
     pub name: String,
+    artist: String,
     file_path: String,
     pub track_number: u32,
     disc_number: u32,
+    work: Option<String>,
+    opus: Option<String>,
+    movement_name: Option<String>,
+    movement_number: Option<u32>,
+    duration: Option<f64>,
 }
 
 //noinspection ALL
-fn tracks_listify<'a>(tracks: &Vec<Track>, num_of_discs: u32) -> Element<'a, Message> {
+/// `show_artists` adds the artist under every track title, for albums the tracks of which are
+/// not all by the album artist
+fn tracks_listify<'a>(
+    tracks: &Vec<Track>,
+    num_of_discs: u32,
+    show_artists: bool,
+) -> Element<'a, Message> {
     log::info!("Number of discs: {}", num_of_discs);
     let mut discs: Vec<Vec<cosmic::widget::Container<Message, Theme>>> = vec![vec![]];
 
-    for track in tracks {
-        let container = cosmic::widget::container::Container::new(
+    for (index, track) in tracks.iter().enumerate() {
+        let disc_num;
+        if (track.disc_number as i32 - 1) < 0 {
+            disc_num = 1;
+        } else {
+            disc_num = track.disc_number
+        }
+
+        let mut containers = vec![];
+
+        // Movements of a work follow each other, the work gets a heading above the first one
+        let same_work =
+            |other: &Track| other.work == track.work && other.disc_number == track.disc_number;
+        if track.work.is_some() && (index == 0 || !same_work(&tracks[index - 1])) {
+            let movements = tracks[index..]
+                .iter()
+                .take_while(|movement| same_work(movement))
+                .collect::<Vec<&Track>>();
+            containers.push(work_heading(&movements));
+        }
+
+        let title = match (&track.work, &track.movement_name) {
+            (Some(_), Some(movement)) => movement,
+            _ => &track.name,
+        };
+
+        containers.push(cosmic::widget::container::Container::new(
             cosmic::widget::row::with_children(vec![
-                cosmic::widget::text::heading(format!("{}. {}", track.track_number, track.name))
-                    .into(),
+                cosmic::widget::column::with_children(vec![cosmic::widget::text::heading(
+                    format!("{}. {}", track.track_number, title),
+                )
+                .into()])
+                .push_maybe(
+                    show_artists.then(|| cosmic::widget::text::caption(track.artist.clone())),
+                )
+                .into(),
                 cosmic::widget::horizontal_space().into(),
+                cosmic::widget::text::text(track.duration.map(format_time).unwrap_or_default())
+                    .into(),
+                cosmic::widget::button::icon(cosmic::widget::icon::from_name(
+                    "document-properties-symbolic",
+                ))
+                .on_press(Message::ShowTrackProperties(track.file_path.clone()))
+                .into(),
                 cosmic::widget::button::icon(cosmic::widget::icon::from_name(
                     "media-playback-start-symbolic",
                 ))
@@ -426,21 +571,14 @@ fn tracks_listify<'a>(tracks: &Vec<Track>, num_of_discs: u32) -> Element<'a, Mes
                 .into(),
             ])
             .align_y(Alignment::Center),
-        );
-
-        let disc_num;
-        if (track.disc_number as i32 - 1) < 0 {
-            disc_num = 1;
-        } else {
-            disc_num = track.disc_number
-        }
+        ));
 
         match discs.get_mut((disc_num - 1) as usize) {
             None => {
-                discs.push(vec![container]);
+                discs.push(containers);
             }
             Some(val) => {
-                val.push(container);
+                val.extend(containers);
             }
         }
     }
@@ -470,6 +608,39 @@ fn tracks_listify<'a>(tracks: &Vec<Track>, num_of_discs: u32) -> Element<'a, Mes
         .into()
 }
 
+/// Title of the work its first movement belongs to, with a button queueing every movement
+fn work_heading<'a>(movements: &[&Track]) -> cosmic::widget::Container<'a, Message, Theme> {
+    let first = movements[0];
+
+    cosmic::widget::container::Container::new(
+        cosmic::widget::row::with_children(vec![
+            cosmic::widget::text::title4(library::work_title(
+                first.work.as_deref().unwrap_or_default(),
+                first.opus.as_deref(),
+            ))
+            .into(),
+            cosmic::widget::horizontal_space().into(),
+            cosmic::widget::button::text(fl!("PlayWork"))
+                .leading_icon(cosmic::widget::icon::from_name(
+                    "media-playback-start-symbolic",
+                ))
+                .on_press(Message::AddAlbumToQueue(
+                    movements
+                        .iter()
+                        .map(|movement| {
+                            (
+                                movement.file_path.clone(),
+                                movement.movement_number.unwrap_or(movement.track_number),
+                            )
+                        })
+                        .collect(),
+                ))
+                .into(),
+        ])
+        .align_y(Alignment::Center),
+    )
+}
+
 impl Track {
     fn track_list_itemify(&self) -> Element<'static, Message> {
         cosmic::widget::text::heading(format!(
@@ -481,138 +652,35 @@ impl Track {
     }
 }
 
-pub async fn get_album_info(title: String, artist: String) -> FullAlbum {
-    log::info!("Before DB");
-    let conn = rusqlite::Connection::open(
-        dirs::data_local_dir()
-            .unwrap()
-            .join(crate::app::AppModel::APP_ID)
-            .join("nova_music.db"),
-    )
-    .expect("Nothing");
-
-    log::info!("After DB");
-
-    let row_num;
-    if artist.is_empty() {
-        row_num = conn
-            .query_row(
-                "
-SELECT * FROM album
-WHERE album.name = ?
-            ",
-                [title.as_str()],
-                |row| {
-                    Ok((
-                        row.get::<usize, u32>(0),
-                        row.get::<usize, u32>(3),
-                        row.get::<usize, u32>(4),
-                        row.get::<&str, Vec<u8>>("album_cover"),
-                    ))
-                },
-            )
-            .unwrap();
-
-
-    } else {
-        row_num = conn
-            .query_row(
-                "
-SELECT * FROM album
-    left join artists art on album.artist_id = art.id
-WHERE album.name = ? and art.name = ?
-            ",
-                [title.as_str(), artist.as_str()],
-                |row| {
-                    Ok((
-                        row.get::<usize, u32>(0),
-                        row.get::<usize, u32>(3),
-                        row.get::<usize, u32>(4),
-                        row.get::<&str, Vec<u8>>("album_cover"),
-                    ))
-                },
-            )
-            .unwrap();
-    }
-
-    let album = Album {
-        name: title,
-        artist,
-        disc_number: row_num.1.unwrap_or(0),
-        track_number: row_num.2.unwrap_or(0),
-        cover_art: match row_num.3 {
-            Ok(bytes) => Some(cosmic::widget::image::Handle::from_bytes(bytes)),
-            Err(_) => None,
-        },
-    };
-
-    let mut track_vector = vec![];
-
-    // Select all tracks with a certain album ID and count them
-
-    let mut value = conn
-        .prepare("select * from album_tracks where album_id = ?")
-        .expect("error preparing sql to fetch album tracks of a certain album id");
-    let mut rows = value
-        .query([row_num.0.expect("No row num, shouldve exited by now")])
-        .expect("error fetching album tracks of a certain album id");
-
-    while let Some(row) = rows.next().unwrap() {
-        let track_num = row.get::<usize, u32>(3).unwrap();
-        let disc_num = row.get::<usize, u32>(4).unwrap();
-
-        let track_dat = match row.get::<usize, u32>(2) {
-            Ok(val) => conn
-                .query_row("SELECT name, path FROM track WHERE id = ?", [val], |row| {
-                    Ok((
-                        row.get::<usize, String>(0)
-                            .unwrap_or(String::from("NOT FOUND")),
-                        row.get::<usize, String>(1)
-                            .unwrap_or(String::from("NOT FOUND")),
-                    ))
-                })
-                .unwrap_or((String::from("ERROR"), String::from("ERROR"))),
-            Err(_) => {
-                panic!("NO ID")
-            }
-        };
-
-        let track = Track {
-            name: track_dat.0,
-            file_path: track_dat.1,
-            track_number: track_num,
-            disc_number: disc_num,
-        };
-        track_vector.push(track);
-        track_vector.sort_by(|a, b| a.track_number.cmp(&b.track_number))
-    }
-
-    FullAlbum {
-        album,
-        tracks: track_vector,
-    }
+pub async fn get_album_info(title: String, artist: String) -> Result<FullAlbum, LibraryError> {
+    let album = library::album_by_title_artist(&title, &artist)?;
+    let tracks = library::tracks_for_album(album.id)?
+        .into_iter()
+        .map(|track| Track {
+            name: track.title,
+            artist: track.artist,
+            file_path: track.path.to_string_lossy().to_string(),
+            track_number: track.track_number,
+            disc_number: track.disc_number,
+            work: track.work,
+            opus: track.opus,
+            movement_name: track.movement_name,
+            movement_number: track.movement_number,
+            duration: track.properties.duration,
+        })
+        .collect();
+
+    Ok(FullAlbum {
+        compilation: album.compilation,
+        release_date: album.release_date.clone(),
+        original_date: album.original_date.clone(),
+        album: Album::from(album),
+        tracks,
+    })
 }
 
-pub fn get_top_album_info(
-    tx: &mut Sender<Message>,
-    album_iter: Vec<(String, String, u32, u32, Option<Vec<u8>>)>,
-) {
-    // Prepare and execute query in a separate scope
-    // Process results and send them
-    let mut albums: Vec<Album> = vec![];
-
-    for album_result in album_iter {
-        albums.push(Album {
-            name: album_result.0,
-            artist: album_result.1,
-            disc_number: album_result.2,
-            track_number: album_result.3,
-            cover_art: match album_result.4 {
-                None => None,
-                Some(val) => Some(cosmic::widget::image::Handle::from_bytes(val)),
-            },
-        })
-    }
+pub fn get_top_album_info(tx: &mut Sender<Message>, album_iter: Vec<library::Album>) {
+    let albums: Vec<Album> = album_iter.into_iter().map(Album::from).collect();
 
     tx.try_send(Message::AlbumProcessed(albums))
         .expect("Failed to send album process");
@@ -643,11 +711,11 @@ impl FullAlbum {
                 .into(),
                 cosmic::widget::Row::with_children([
                     // Art Area?
-                    match &self.album.cover_art {
+                    match &self.album.cover {
                         None => cosmic::widget::icon::from_name("applications-audio-symbolic")
                             .size(128)
                             .into(),
-                        Some(handle) => cosmic::widget::image(handle)
+                        Some(cover) => cosmic::widget::image(artwork::handle(cover, 128))
                             .content_fit(ContentFit::Fill)
                             .height(128.0)
                             .width(128.0)
@@ -661,6 +729,26 @@ impl FullAlbum {
                             artist = self.album.artist.as_str()
                         ))
                         .into(),
+                        cosmic::widget::text::caption(total_runtime(
+                            self.tracks.iter().map(|track| track.duration),
+                        ))
+                        .into(),
+                    ])
+                    .push_maybe(self.release_date.as_deref().map(|date| {
+                        cosmic::widget::text::caption(fl!("AlbumReleased", date = date))
+                    }))
+                    .push_maybe(
+                        self.original_date
+                            .as_deref()
+                            .filter(|date| Some(*date) != self.release_date.as_deref())
+                            .map(|date| {
+                                cosmic::widget::text::caption(fl!(
+                                    "AlbumOriginallyReleased",
+                                    date = date
+                                ))
+                            }),
+                    )
+                    .push(
                         cosmic::widget::button::text(fl!("AddToQueue"))
                             .leading_icon(cosmic::widget::icon::from_name(
                                 "media-playback-start-symbolic",
@@ -671,9 +759,8 @@ impl FullAlbum {
                                     .iter()
                                     .map(|a| (a.file_path.clone(), a.disc_number))
                                     .collect::<Vec<(String, u32)>>(),
-                            ))
-                            .into(),
-                    ])
+                            )),
+                    )
                     .spacing(cosmic::theme::spacing().space_xxxs)
                     .into(),
                 ])
@@ -681,7 +768,15 @@ impl FullAlbum {
                 .into(),
                 // BODY
                 cosmic::widget::scrollable(cosmic::widget::container::Container::new(
-                    tracks_listify(&self.tracks, self.album.disc_number),
+                    tracks_listify(
+                        &self.tracks,
+                        self.album.disc_number,
+                        self.compilation
+                            || self
+                                .tracks
+                                .iter()
+                                .any(|track| track.artist != self.album.artist),
+                    ),
                 ))
                 .width(Length::Fill)
                 .height(Length::Fill)
@@ -1,10 +1,12 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use crate::app::albums::{Album, FullAlbum};
-use crate::app::tracks::SearchResult;
+use crate::app::tracks::{library_search_input, SearchResult};
 use crate::app::{AppModel, FileChooserEvents, TrackType};
 use crate::app::{DisplaySingle, Message};
-use crate::{app, fl};
+use crate::database::ArtistRole;
+use crate::library::Credit;
+use crate::{app, artwork, fl, library};
 use cosmic::iced::alignment::{Horizontal, Vertical};
 use cosmic::iced::{Alignment, ContentFit, Length};
 use cosmic::widget::{Dialog, JustifyContent};
@@ -18,6 +20,18 @@ pub struct ArtistInfo {
     pub image: Option<cosmic::widget::image::Handle>,
 }
 
+impl From<library::Artist> for ArtistInfo {
+    fn from(artist: library::Artist) -> Self {
+        ArtistInfo {
+            name: artist.name,
+            path: String::new(),
+            image: artist
+                .picture
+                .map(cosmic::widget::image::Handle::from_bytes),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ArtistsPage {
     pub page_state: ArtistPageState,
@@ -44,6 +58,9 @@ pub struct ArtistPage {
     pub artist: ArtistInfo,
     pub singles: Vec<DisplaySingle>,
     pub albums: Vec<Album>,
+    /// Albums by other artists that have tracks by this one, compilations mostly
+    pub appears_on: Vec<Album>,
+    pub credits: Vec<Credit>,
 }
 
 impl ArtistsPage {
@@ -244,6 +261,32 @@ impl ArtistsPage {
                             cosmic::widget::text::title4(fl!("singles")).into(),
                             cosmic::widget::row::with_children(data.1).into(),
                         ])
+                        .push_maybe((!data.2.is_empty()).then(|| {
+                            cosmic::widget::column::with_children(vec![
+                                cosmic::widget::text::title4(fl!("AppearsOn")).into(),
+                                cosmic::widget::scrollable::horizontal(
+                                    cosmic::widget::row::with_children(data.2).padding(
+                                        cosmic::iced_core::padding::Padding::from([
+                                            0,
+                                            0,
+                                            cosmic::theme::spacing().space_s,
+                                            0,
+                                        ]),
+                                    ),
+                                )
+                                .into(),
+                            ])
+                            .spacing(cosmic::theme::spacing().space_m)
+                        }))
+                        .push_maybe((!artistpage.credits.is_empty()).then(|| {
+                            cosmic::widget::column::with_children(vec![
+                                cosmic::widget::text::title4(fl!("CreditedTracks")).into(),
+                                cosmic::widget::scrollable(artistpage.credits_list())
+                                    .height(Length::Fill)
+                                    .into(),
+                            ])
+                            .spacing(cosmic::theme::spacing().space_m)
+                        }))
                         .spacing(cosmic::theme::spacing().space_m)
                         .into(),
                     ])
@@ -268,12 +311,10 @@ impl ArtistsPage {
                         let mut artists: Vec<ArtistInfo> = vec![];
 
                         for each in search {
-                            if (0..=2).contains(&each.score) {
-                                match self.artists.get(each.tracks_index) {
-                                    None => {}
-                                    Some(val) => {
-                                        artists.push(val.clone());
-                                    }
+                            match self.artists.get(each.tracks_index) {
+                                None => {}
+                                Some(val) => {
+                                    artists.push(val.clone());
                                 }
                             }
                         }
@@ -394,13 +435,7 @@ impl ArtistsPage {
                     cosmic::widget::horizontal_space()
                         .width(Length::Shrink)
                         .into(),
-                    cosmic::widget::search_input(
-                        fl!("ArtistInputPlaceholder"),
-                        model.search_field.as_str(),
-                    )
-                    .on_input(|input| Message::UpdateSearch(input))
-                    .width(Length::FillPortion(1))
-                    .into(),
+                    library_search_input(fl!("ArtistInputPlaceholder"), model),
                     // todo, Allow users to ask application to try and find covers for all their
                     //  artists for ease of use purposes.
                     //  This would be part of a larger effort to bring network features so delayed for now
@@ -549,16 +584,25 @@ impl ArtistPage {
     fn product_cover_button(
         &self,
         model: &AppModel,
-    ) -> (Vec<Element<app::Message>>, Vec<Element<app::Message>>) {
+    ) -> (
+        Vec<Element<app::Message>>,
+        Vec<Element<app::Message>>,
+        Vec<Element<app::Message>>,
+    ) {
         let mut singles = vec![];
         let mut albums = vec![];
+        let mut appears_on = vec![];
 
         for single in self.singles.as_slice() {
             singles.push(
                 cosmic::widget::button::custom(cosmic::widget::column::with_children(vec![
-                    if let Some(cover_art) = &single.cover_art {
+                    if let Some(cover) = &single.cover {
                         cosmic::widget::container::Container::new(
-                            cosmic::widget::image(cover_art).content_fit(ContentFit::Cover),
+                            cosmic::widget::image(artwork::handle(
+                                cover,
+                                model.config.grid_item_size * 32,
+                            ))
+                            .content_fit(ContentFit::Cover),
                         )
                         .height((model.config.grid_item_size * 32) as f32)
                         .width((model.config.grid_item_size * 32) as f32)
@@ -592,46 +636,117 @@ impl ArtistPage {
         }
 
         for album in self.albums.as_slice() {
-            albums.push(
-                cosmic::widget::button::custom(cosmic::widget::column::with_children(vec![
-                    if let Some(cover_art) = &album.cover_art {
-                        cosmic::widget::container::Container::new(
-                            cosmic::widget::image(cover_art).content_fit(ContentFit::Cover),
-                        )
-                        .height((model.config.grid_item_size * 32) as f32)
-                        .width((model.config.grid_item_size * 32) as f32)
-                        .into()
-                    } else {
-                        cosmic::widget::container(
-                            cosmic::widget::icon::from_name("media-optical-symbolic")
-                            .size((model.config.grid_item_size * 32) as u16),
-                        )
-                        .align_x(Alignment::Center)
-                        .align_y(Alignment::Center)
-                        .into()
-                    },
-                    cosmic::widget::column::with_children(vec![
-                        cosmic::widget::text::text(album.name.as_str())
-                            .center()
-                            .into(),
-                        cosmic::widget::text::text(album.artist.as_str())
-                            .center()
-                            .into(),
-                    ])
-                    .align_x(Alignment::Center)
-                    .width(cosmic::iced::Length::Fill)
+            albums.push(album_cover_button(album, model))
+        }
+
+        for album in self.appears_on.as_slice() {
+            appears_on.push(album_cover_button(album, model))
+        }
+
+        (albums, singles, appears_on)
+    }
+
+    /// Tracks the artist is credited on, with what for when it is not as the artist
+    fn credits_list(&self) -> Element<'_, app::Message> {
+        let mut list = cosmic::widget::ListColumn::new();
+
+        for credit in &self.credits {
+            let mut details = vec![];
+            if !credit.track.album_title.is_empty() {
+                details.push(credit.track.album_title.clone());
+            }
+            details.extend(
+                credit
+                    .roles
+                    .iter()
+                    .filter(|role| **role != ArtistRole::Primary)
+                    .map(role_label),
+            );
+
+            list = list.add(
+                cosmic::widget::row::with_children(vec![
+                    cosmic::widget::column::with_children(vec![cosmic::widget::text::heading(
+                        credit.track.title.as_str(),
+                    )
+                    .into()])
+                    .push_maybe(
+                        (!details.is_empty())
+                            .then(|| cosmic::widget::text::caption(details.join(" · "))),
+                    )
                     .into(),
-                ]))
-                .class(cosmic::widget::button::ButtonClass::Icon)
-                .on_press(Message::AlbumRequested((
-                    album.name.clone(),
-                    album.artist.clone(),
-                )))
-                .width((model.config.grid_item_size * 32) as f32)
-                .into(),
-            )
+                    cosmic::widget::horizontal_space().into(),
+                    cosmic::widget::button::icon(cosmic::widget::icon::from_name(
+                        "media-playback-start-symbolic",
+                    ))
+                    .on_press(Message::AddTrackToQueue(
+                        credit.track.path.to_string_lossy().to_string(),
+                    ))
+                    .into(),
+                ])
+                .align_y(Alignment::Center),
+            );
         }
 
-        (albums, singles)
+        list.into_element()
+    }
+}
+
+fn role_label(role: &ArtistRole) -> String {
+    match role {
+        ArtistRole::Primary => fl!("RolePrimary"),
+        ArtistRole::Featured => fl!("RoleFeatured"),
+        ArtistRole::Remixer => fl!("RoleRemixer"),
+        ArtistRole::Composer => fl!("RoleComposer"),
+        ArtistRole::Conductor => fl!("RoleConductor"),
+        ArtistRole::Performer => fl!("RolePerformer"),
     }
 }
+
+/// Cover, title, album artist and year of an album, opens the album page when pressed
+pub(crate) fn album_cover_button<'a>(
+    album: &'a Album,
+    model: &AppModel,
+) -> Element<'a, app::Message> {
+    cosmic::widget::button::custom(cosmic::widget::column::with_children(vec![
+        if let Some(cover) = &album.cover {
+            cosmic::widget::container::Container::new(
+                cosmic::widget::image(artwork::handle(cover, model.config.grid_item_size * 32))
+                    .content_fit(ContentFit::Cover),
+            )
+            .height((model.config.grid_item_size * 32) as f32)
+            .width((model.config.grid_item_size * 32) as f32)
+            .into()
+        } else {
+            cosmic::widget::container(
+                cosmic::widget::icon::from_name("media-optical-symbolic")
+                    .size((model.config.grid_item_size * 32) as u16),
+            )
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+        },
+        cosmic::widget::column::with_children(vec![
+            cosmic::widget::text::text(album.name.as_str())
+                .center()
+                .into(),
+            cosmic::widget::text::text(album.artist.as_str())
+                .center()
+                .into(),
+        ])
+        .push_maybe(
+            album
+                .year
+                .map(|year| cosmic::widget::text::caption(year.to_string()).center()),
+        )
+        .align_x(Alignment::Center)
+        .width(cosmic::iced::Length::Fill)
+        .into(),
+    ]))
+    .class(cosmic::widget::button::ButtonClass::Icon)
+    .on_press(Message::AlbumRequested((
+        album.name.clone(),
+        album.artist.clone(),
+    )))
+    .width((model.config.grid_item_size * 32) as f32)
+    .into()
+}
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::app::{AppModel, Message};
+use crate::library::{self, Work};
+use crate::{app, fl};
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::{iced, Element};
+use iced::widget::scrollable::Viewport;
+
+#[derive(Debug, Clone)]
+pub struct ComposerInfo {
+    pub name: String,
+    pub image: Option<cosmic::widget::image::Handle>,
+    pub track_count: u32,
+}
+
+impl From<library::Composer> for ComposerInfo {
+    fn from(composer: library::Composer) -> Self {
+        ComposerInfo {
+            name: composer.name,
+            image: composer
+                .picture
+                .map(cosmic::widget::image::Handle::from_bytes),
+            track_count: composer.track_count,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ComposersPage {
+    pub page_state: ComposersPageState,
+    pub composers: Vec<ComposerInfo>,
+
+    //Scrollbar
+    pub viewport: Option<Viewport>,
+    pub scrollbar_id: cosmic::iced_core::widget::Id,
+}
+
+#[derive(Debug)]
+pub enum ComposersPageState {
+    Loading,
+    Loaded,
+    Composer(ComposerPage),
+}
+
+/// Works of one composer, each with its movements in order
+#[derive(Debug)]
+pub struct ComposerPage {
+    pub name: String,
+    pub works: Vec<Work>,
+}
+
+impl ComposersPage {
+    pub fn new() -> ComposersPage {
+        ComposersPage {
+            page_state: ComposersPageState::Loading,
+            composers: vec![],
+            viewport: None,
+            scrollbar_id: cosmic::iced_core::widget::Id::unique(),
+        }
+    }
+
+    pub fn load_page<'a>(&'a self, model: &'a AppModel) -> Element<'a, app::Message> {
+        let body: Element<Message> = match &self.page_state {
+            ComposersPageState::Loading => cosmic::widget::text(fl!("Loading")).into(),
+            ComposersPageState::Loaded => match self.composers.is_empty() {
+                true => cosmic::widget::text::text(fl!("ComposersPageEmpty")).into(),
+                false => self.composer_list(model),
+            },
+            ComposersPageState::Composer(page) => page.works_list(),
+        };
+
+        cosmic::widget::container(
+            cosmic::widget::column::with_children(vec![
+                match &self.page_state {
+                    ComposersPageState::Composer(page) => {
+                        cosmic::widget::column::with_children(vec![
+                            cosmic::widget::button::custom(
+                                cosmic::widget::row::with_children(vec![
+                                    cosmic::widget::icon::from_name("go-previous-symbolic").into(),
+                                    cosmic::widget::text::text(fl!("composers")).into(),
+                                ])
+                                .align_y(Alignment::Center),
+                            )
+                            .on_press(Message::ComposerPageReturn)
+                            .class(cosmic::widget::button::ButtonClass::Link)
+                            .into(),
+                            cosmic::widget::text::title3(page.name.as_str()).into(),
+                        ])
+                        .into()
+                    }
+                    _ => cosmic::widget::text::title3(fl!("ComposerLibrary")).into(),
+                },
+                cosmic::widget::scrollable::vertical(body)
+                    .id(self.scrollbar_id.clone())
+                    .on_scroll(|view| Message::ScrollView(view))
+                    .height(Length::Fill)
+                    .into(),
+            ])
+            .spacing(cosmic::theme::spacing().space_s),
+        )
+        .padding(iced::core::padding::Padding::from([
+            0,
+            cosmic::theme::spacing().space_m,
+        ]))
+        .height(Length::Fill)
+        .into()
+    }
+
+    fn composer_list<'a>(&'a self, model: &'a AppModel) -> Element<'a, app::Message> {
+        let size = (model.config.grid_item_size * 16) as f32;
+        let mut list = cosmic::widget::ListColumn::new();
+
+        for composer in &self.composers {
+            let picture: Element<Message> = match &composer.image {
+                None => cosmic::widget::icon::from_name("avatar-default-symbolic")
+                    .size(size as u16)
+                    .into(),
+                Some(handle) => cosmic::widget::image(handle)
+                    .content_fit(ContentFit::Fill)
+                    .width(size)
+                    .height(size)
+                    .into(),
+            };
+
+            list = list.add(
+                cosmic::widget::button::custom(
+                    cosmic::widget::row::with_children(vec![
+                        picture,
+                        cosmic::widget::column::with_children(vec![
+                            cosmic::widget::text::heading(composer.name.as_str()).into(),
+                            cosmic::widget::text::caption(fl!(
+                                "ComposerTrackCount",
+                                count = composer.track_count
+                            ))
+                            .into(),
+                        ])
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(cosmic::theme::spacing().space_s),
+                )
+                .on_press(Message::ComposerRequested(composer.name.clone()))
+                .class(cosmic::widget::button::ButtonClass::Text)
+                .width(Length::Fill),
+            );
+        }
+
+        list.into_element()
+    }
+}
+
+impl ComposerPage {
+    fn works_list(&self) -> Element<'_, app::Message> {
+        let mut works = vec![];
+
+        for work in &self.works {
+            let mut movements = cosmic::widget::ListColumn::new();
+            for (index, movement) in work.movements.iter().enumerate() {
+                let title = match (&movement.work, &movement.movement_name) {
+                    (Some(_), Some(name)) => name,
+                    _ => &movement.title,
+                };
+
+                movements = movements.add(
+                    cosmic::widget::row::with_children(vec![
+                        cosmic::widget::text::heading(format!(
+                            "{}. {}",
+                            movement.movement_number.unwrap_or(index as u32 + 1),
+                            title
+                        ))
+                        .into(),
+                        cosmic::widget::horizontal_space().into(),
+                        cosmic::widget::button::icon(cosmic::widget::icon::from_name(
+                            "media-playback-start-symbolic",
+                        ))
+                        .on_press(Message::AddTrackToQueue(
+                            movement.path.to_string_lossy().to_string(),
+                        ))
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center),
+                );
+            }
+
+            works.push(
+                cosmic::widget::column::with_children(vec![
+                    cosmic::widget::row::with_children(vec![
+                        cosmic::widget::column::with_children(vec![cosmic::widget::text::title4(
+                            library::work_title(&work.title, work.opus.as_deref()),
+                        )
+                        .into()])
+                        .push_maybe(
+                            (!work.album_title.is_empty())
+                                .then(|| cosmic::widget::text::caption(work.album_title.as_str())),
+                        )
+                        .into(),
+                        cosmic::widget::horizontal_space().into(),
+                        cosmic::widget::button::text(fl!("PlayWork"))
+                            .leading_icon(cosmic::widget::icon::from_name(
+                                "media-playback-start-symbolic",
+                            ))
+                            .class(cosmic::theme::Button::Suggested)
+                            // Already in movement order, the queue keeps it
+                            .on_press(Message::AddAlbumToQueue(
+                                work.movements
+                                    .iter()
+                                    .map(|movement| {
+                                        (movement.path.to_string_lossy().to_string(), 0)
+                                    })
+                                    .collect(),
+                            ))
+                            .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .into(),
+                    movements.into_element(),
+                ])
+                .spacing(cosmic::theme::spacing().space_xs)
+                .into(),
+            );
+        }
+
+        cosmic::widget::column::with_children(works)
+            .spacing(cosmic::theme::spacing().space_m)
+            .padding(iced::core::padding::Padding::from([
+                0,
+                0,
+                cosmic::theme::spacing().space_m,
+                0,
+            ]))
+            .into()
+    }
+}
@@ -0,0 +1,335 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::app::albums::{cover_grid, Album, FullAlbum};
+use crate::app::artists::album_cover_button;
+use crate::app::tracks::SearchResult;
+use crate::app::{AppModel, Message};
+use crate::library::{self, Track};
+use crate::{app, artwork, fl};
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::{iced, Element};
+use iced::widget::scrollable::Viewport;
+
+#[derive(Debug, Clone)]
+pub struct GenreInfo {
+    pub name: String,
+    pub track_count: u32,
+    pub album_count: u32,
+    /// Hash of the cover of one of its albums in [`artwork`]
+    pub cover: Option<String>,
+}
+
+impl From<library::Genre> for GenreInfo {
+    fn from(genre: library::Genre) -> Self {
+        GenreInfo {
+            name: genre.name,
+            track_count: genre.track_count,
+            album_count: genre.album_count,
+            cover: genre.cover,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GenresPage {
+    pub page_state: GenresPageState,
+    pub genres: Vec<GenreInfo>,
+    /// Genre an album was opened from, shown again when going back
+    pub genre_page_cache: Option<GenrePage>,
+
+    //Scrollbar
+    pub viewport: Option<Viewport>,
+    pub scrollbar_id: cosmic::iced_core::widget::Id,
+}
+
+#[derive(Debug)]
+pub enum GenresPageState {
+    Loading,
+    Loaded,
+    Genre(GenrePage),
+    Album(FullAlbum),
+    Search(Vec<SearchResult>),
+}
+
+/// Albums and tracks of one genre
+#[derive(Debug, Clone)]
+pub struct GenrePage {
+    pub name: String,
+    pub albums: Vec<Album>,
+    pub tracks: Vec<Track>,
+}
+
+impl From<library::GenrePage> for GenrePage {
+    fn from(page: library::GenrePage) -> Self {
+        GenrePage {
+            name: page.name,
+            albums: page.albums.into_iter().map(Album::from).collect(),
+            tracks: page.tracks,
+        }
+    }
+}
+
+impl GenresPage {
+    pub fn new() -> GenresPage {
+        GenresPage {
+            page_state: GenresPageState::Loading,
+            genres: vec![],
+            genre_page_cache: None,
+            viewport: None,
+            scrollbar_id: cosmic::iced_core::widget::Id::unique(),
+        }
+    }
+
+    pub fn load_page<'a>(&'a self, model: &'a AppModel) -> Element<'a, app::Message> {
+        let body: Element<Message> = match &self.page_state {
+            GenresPageState::Loading => cosmic::widget::text(fl!("Loading")).into(),
+            GenresPageState::Loaded => match self.genres.is_empty() {
+                true => cosmic::widget::text::text(fl!("GenresPageEmpty")).into(),
+                false => self.genre_grid(self.genres.iter().collect(), model),
+            },
+            GenresPageState::Search(results) => self.genre_grid(
+                results
+                    .iter()
+                    .filter_map(|result| self.genres.get(result.tracks_index))
+                    .collect(),
+                model,
+            ),
+            GenresPageState::Genre(page) => return page.genre_page(model),
+            GenresPageState::Album(album) => {
+                let name = self
+                    .genre_page_cache
+                    .as_ref()
+                    .map(|page| page.name.clone())
+                    .unwrap_or_default();
+                return album.full_album_page(model, Message::GenrePageReturn, name);
+            }
+        };
+
+        cosmic::widget::container(
+            cosmic::widget::column::with_children(vec![
+                cosmic::widget::row::with_children(vec![
+                    cosmic::widget::text::title3(fl!("GenreLibrary"))
+                        .width(Length::FillPortion(2))
+                        .into(),
+                    cosmic::widget::horizontal_space()
+                        .width(Length::Shrink)
+                        .into(),
+                    cosmic::widget::search_input(
+                        fl!("GenreInputPlaceholder"),
+                        model.search_field.as_str(),
+                    )
+                    .on_input(|input| Message::UpdateSearch(input))
+                    .width(Length::FillPortion(1))
+                    .into(),
+                ])
+                .align_y(Alignment::Center)
+                .spacing(cosmic::theme::spacing().space_s)
+                .into(),
+                body,
+            ])
+            .spacing(cosmic::theme::spacing().space_s),
+        )
+        .padding(iced::core::padding::Padding::from([
+            0,
+            cosmic::theme::spacing().space_m,
+        ]))
+        .height(Length::Fill)
+        .into()
+    }
+
+    fn genre_grid<'a>(
+        &'a self,
+        genres: Vec<&'a GenreInfo>,
+        model: &'a AppModel,
+    ) -> Element<'a, app::Message> {
+        let item_size = model.config.grid_item_size * 32;
+
+        cosmic::widget::responsive(move |size| {
+            let elements = genres
+                .iter()
+                .map(|genre| genre_button(genre, item_size))
+                .collect();
+
+            cosmic::widget::scrollable::vertical(cover_grid(elements, item_size, size.width))
+                .id(self.scrollbar_id.clone())
+                .on_scroll(|view| Message::ScrollView(view))
+                .into()
+        })
+        .into()
+    }
+}
+
+/// Cover of one of its albums, name and counts of a genre, opens the genre when pressed
+fn genre_button(genre: &GenreInfo, item_size: u32) -> Element<'_, app::Message> {
+    cosmic::widget::button::custom(cosmic::widget::column::with_children(vec![
+        match &genre.cover {
+            Some(cover) => cosmic::widget::container::Container::new(
+                cosmic::widget::image(artwork::handle(cover, item_size))
+                    .content_fit(ContentFit::Cover),
+            )
+            .height(item_size as f32)
+            .width(item_size as f32)
+            .into(),
+            None => cosmic::widget::container(
+                cosmic::widget::icon::from_name("folder-music-symbolic").size(item_size as u16),
+            )
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into(),
+        },
+        cosmic::widget::column::with_children(vec![
+            cosmic::widget::text::text(genre.name.as_str())
+                .center()
+                .into(),
+            cosmic::widget::text::caption(fl!(
+                "GenreCounts",
+                tracks = genre.track_count,
+                albums = genre.album_count
+            ))
+            .center()
+            .into(),
+        ])
+        .align_x(Alignment::Center)
+        .width(Length::Fill)
+        .into(),
+    ]))
+    .class(cosmic::widget::button::ButtonClass::Icon)
+    .on_press(Message::GenreRequested(genre.name.clone()))
+    .width(item_size as f32)
+    .into()
+}
+
+impl GenrePage {
+    fn genre_page<'a>(&'a self, model: &'a AppModel) -> Element<'a, app::Message> {
+        let albums = self
+            .albums
+            .iter()
+            .map(|album| album_cover_button(album, model))
+            .collect::<Vec<Element<Message>>>();
+
+        cosmic::widget::container(
+            cosmic::widget::column::with_children(vec![
+                cosmic::widget::button::custom(
+                    cosmic::widget::row::with_children(vec![
+                        cosmic::widget::icon::from_name("go-previous-symbolic").into(),
+                        cosmic::widget::text::text(fl!("genres")).into(),
+                    ])
+                    .align_y(Alignment::Center),
+                )
+                .on_press(Message::GenrePageReturn)
+                .class(cosmic::widget::button::ButtonClass::Link)
+                .into(),
+                cosmic::widget::row::with_children(vec![
+                    cosmic::widget::column::with_children(vec![
+                        cosmic::widget::text::title3(self.name.as_str()).into(),
+                        cosmic::widget::text::caption(fl!(
+                            "GenreCounts",
+                            tracks = self.tracks.len(),
+                            albums = self.albums.len()
+                        ))
+                        .into(),
+                    ])
+                    .into(),
+                    cosmic::widget::horizontal_space().into(),
+                    cosmic::widget::button::text(fl!("PlayAll"))
+                        .leading_icon(cosmic::widget::icon::from_name(
+                            "media-playback-start-symbolic",
+                        ))
+                        .class(cosmic::theme::Button::Suggested)
+                        // Numbered in the order they are listed, the queue keeps it
+                        .on_press(Message::AddAlbumToQueue(
+                            self.tracks
+                                .iter()
+                                .enumerate()
+                                .map(|(index, track)| {
+                                    (track.path.to_string_lossy().to_string(), index as u32)
+                                })
+                                .collect(),
+                        ))
+                        .into(),
+                    cosmic::widget::button::text(fl!("ShuffleGenre"))
+                        .leading_icon(cosmic::widget::icon::from_name(
+                            "media-playlist-shuffle-symbolic",
+                        ))
+                        .class(cosmic::theme::Button::Standard)
+                        .on_press(Message::GenreShuffle)
+                        .into(),
+                ])
+                .align_y(Alignment::Center)
+                .spacing(cosmic::theme::spacing().space_s)
+                .into(),
+                cosmic::widget::divider::horizontal::default().into(),
+            ])
+            .push_maybe((!albums.is_empty()).then(|| {
+                cosmic::widget::column::with_children(vec![
+                    cosmic::widget::text::title4(fl!("albums")).into(),
+                    cosmic::widget::scrollable::horizontal(
+                        cosmic::widget::row::with_children(albums).padding(
+                            cosmic::iced_core::padding::Padding::from([
+                                0,
+                                0,
+                                cosmic::theme::spacing().space_s,
+                                0,
+                            ]),
+                        ),
+                    )
+                    .into(),
+                ])
+                .spacing(cosmic::theme::spacing().space_m)
+            }))
+            .push(
+                cosmic::widget::column::with_children(vec![
+                    cosmic::widget::text::title4(fl!("tracks")).into(),
+                    cosmic::widget::scrollable(self.track_list())
+                        .height(Length::Fill)
+                        .into(),
+                ])
+                .spacing(cosmic::theme::spacing().space_m),
+            )
+            .padding(iced::core::padding::Padding::from([
+                0,
+                cosmic::theme::spacing().space_m,
+            ]))
+            .spacing(cosmic::theme::spacing().space_s),
+        )
+        .height(Length::Fill)
+        .into()
+    }
+
+    /// Tracks with who they are by and what album they are on, each can be queued on its own
+    fn track_list(&self) -> Element<'_, app::Message> {
+        let mut list = cosmic::widget::ListColumn::new();
+
+        for track in &self.tracks {
+            let details = [track.artist.as_str(), track.album_title.as_str()]
+                .into_iter()
+                .filter(|detail| !detail.is_empty())
+                .collect::<Vec<&str>>();
+
+            list = list.add(
+                cosmic::widget::row::with_children(vec![
+                    cosmic::widget::column::with_children(vec![cosmic::widget::text::heading(
+                        track.title.as_str(),
+                    )
+                    .into()])
+                    .push_maybe(
+                        (!details.is_empty())
+                            .then(|| cosmic::widget::text::caption(details.join(" · "))),
+                    )
+                    .into(),
+                    cosmic::widget::horizontal_space().into(),
+                    cosmic::widget::button::icon(cosmic::widget::icon::from_name(
+                        "media-playback-start-symbolic",
+                    ))
+                    .on_press(Message::AddTrackToQueue(
+                        track.path.to_string_lossy().to_string(),
+                    ))
+                    .into(),
+                ])
+                .align_y(Alignment::Center),
+            );
+        }
+
+        list.into_element()
+    }
+}
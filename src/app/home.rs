@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use crate::app;
+use crate::app::lyrics::Lyrics;
 use crate::app::{AppModel, AppTrack, LoopState, Message};
 use crate::fl;
 use cosmic::iced::alignment::{Horizontal, Vertical};
@@ -15,9 +16,26 @@ use cosmic::iced_widget::scrollable::Viewport;
 #[derive(Debug)]
 pub(crate) struct HomePage {
     pub viewport: Option<Viewport>,
+    /// Of the track playing, loaded when it starts
+    pub lyrics: Option<Lyrics>,
+    /// Track the lyrics were loaded for
+    pub lyrics_track: Option<u32>,
+    /// Synced line being sung, the lyrics panel keeps it in view
+    pub lyrics_line: Option<usize>,
+    pub lyrics_scroll_id: cosmic::iced_core::widget::Id,
 }
 
 impl HomePage {
+    pub fn new() -> HomePage {
+        HomePage {
+            viewport: None,
+            lyrics: None,
+            lyrics_track: None,
+            lyrics_line: None,
+            lyrics_scroll_id: cosmic::iced_core::widget::Id::unique(),
+        }
+    }
+
     pub fn load_page<'a>(&self, model: &'a AppModel) -> Element<'a, app::Message> {
         // Time ELapsed
         let time_elapsed = format_time(model.song_progress);
@@ -156,6 +174,7 @@ impl HomePage {
                                 .class(cosmic::style::Container::Card)
                                 .into(),
                             ])
+                            .push_maybe(self.lyrics_panel())
                             .spacing(cosmic::theme::spacing().space_xs),
                         )
                         .width(Length::Fill)
@@ -210,6 +229,61 @@ impl HomePage {
         .width(Length::Fill)
         .into()
     }
+
+    /// Synced lyrics follow the track and seek to a line when it is clicked, plain ones are
+    /// shown as they are
+    fn lyrics_panel<'a>(&self) -> Option<Element<'a, app::Message>> {
+        const LYRICS_HEIGHT: f32 = 240.0;
+
+        let lines: Element<'a, app::Message> = match self.lyrics.as_ref()? {
+            Lyrics::Synced(lines) => cosmic::widget::column::with_children(
+                lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, line)| {
+                        let text = match line.text.is_empty() {
+                            true => "♪".to_string(),
+                            false => line.text.clone(),
+                        };
+                        let text = match self.lyrics_line == Some(index) {
+                            true => cosmic::widget::text::heading(text)
+                                .class(cosmic::theme::Text::Accent),
+                            false => cosmic::widget::text::body(text),
+                        };
+
+                        cosmic::widget::button::custom(text.wrapping(Wrapping::WordOrGlyph))
+                            .class(cosmic::widget::button::ButtonClass::Text)
+                            .on_press(Message::SeekTo(line.time))
+                            .into()
+                    })
+                    .collect::<Vec<Element<'a, app::Message>>>(),
+            )
+            .into(),
+            Lyrics::Plain(text) => cosmic::widget::text::body(text.clone())
+                .wrapping(Wrapping::WordOrGlyph)
+                .into(),
+        };
+
+        Some(
+            cosmic::widget::container(
+                cosmic::widget::column::with_children(vec![
+                    cosmic::widget::text::heading(fl!("Lyrics")).into(),
+                    cosmic::widget::divider::horizontal::default().into(),
+                    cosmic::widget::scrollable(
+                        cosmic::widget::container(lines).width(Length::Fill),
+                    )
+                    .id(self.lyrics_scroll_id.clone())
+                    .height(LYRICS_HEIGHT)
+                    .into(),
+                ])
+                .spacing(cosmic::theme::spacing().space_xxs),
+            )
+            .padding(cosmic::theme::spacing().space_xxs)
+            .class(cosmic::style::Container::Card)
+            .width(Length::Fill)
+            .into(),
+        )
+    }
 }
 
 pub fn listify_queue(queue: &Vec<AppTrack>, active: usize) -> Element<'static, Message> {
@@ -217,6 +291,7 @@ pub fn listify_queue(queue: &Vec<AppTrack>, active: usize) -> Element<'static, M
 
     for (index, item) in queue.iter().enumerate() {
         let name = format!("{}. {}", index + 1, item.title);
+        let length = item.duration.map(format_time).unwrap_or_default();
 
         match list.take() {
             None => {}
@@ -227,6 +302,7 @@ pub fn listify_queue(queue: &Vec<AppTrack>, active: usize) -> Element<'static, M
                             cosmic::widget::row::with_children(vec![
                                 cosmic::widget::text(name).into(),
                                 cosmic::widget::horizontal_space().into(),
+                                cosmic::widget::text::caption(length).into(),
                                 cosmic::widget::button::icon(cosmic::widget::icon::from_name(
                                     "window-close-symbolic",
                                 ))
@@ -246,6 +322,7 @@ pub fn listify_queue(queue: &Vec<AppTrack>, active: usize) -> Element<'static, M
                             cosmic::widget::row::with_children(vec![
                                 cosmic::widget::text(name).into(),
                                 cosmic::widget::horizontal_space().into(),
+                                cosmic::widget::text::caption(length).into(),
                                 cosmic::widget::button::icon(cosmic::widget::icon::from_name(
                                     "window-close-symbolic",
                                 ))
@@ -340,3 +417,21 @@ pub fn format_time(mut seconds: f64) -> String {
 
     return format!("{}:{}", minute_format, seconds_format);
 }
+
+/// Number of tracks and their summed up length, as albums and playlists show it. Tracks of an
+/// unknown length count as empty.
+pub fn total_runtime(durations: impl IntoIterator<Item = Option<f64>>) -> String {
+    let (count, seconds) = durations
+        .into_iter()
+        .fold((0, 0.0), |(count, seconds), duration| {
+            (count + 1, seconds + duration.unwrap_or(0.0))
+        });
+
+    let hours = (seconds / 3600.0) as u64;
+    let runtime = match hours {
+        0 => format_time(seconds),
+        _ => format!("{}:{}", hours, format_time(seconds % 3600.0)),
+    };
+
+    fl!("TotalRuntime", count = count, runtime = runtime)
+}
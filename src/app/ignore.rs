@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Gitignore style exclusion rules for library roots and `.novaignore` files.
+
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the file that holds exclusion patterns for the folder it is in
+pub const IGNORE_FILE: &str = ".novaignore";
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Pattern {
+    /// Parses a single gitignore line, comments and blank lines give `None`
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        // A slash anywhere but the end ties the pattern to the folder the rules come from
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let regex = Regex::new(&format!("{}{}$", prefix, glob_to_regex(line))).ok()?;
+
+        Some(Pattern {
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::from("[");
+                if let Some(&'!') = chars.peek() {
+                    chars.next();
+                    class.push('^');
+                }
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                if closed {
+                    class.push(']');
+                    regex.push_str(&class);
+                } else {
+                    // Unterminated class, treat it as text like git does
+                    regex.push_str(&regex::escape(&class));
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex
+}
+
+/// Patterns that apply below `base`
+#[derive(Debug, Clone)]
+struct RuleSet {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl RuleSet {
+    fn new<'a>(base: PathBuf, lines: impl Iterator<Item = &'a str>) -> Self {
+        RuleSet {
+            base,
+            patterns: lines.filter_map(Pattern::parse).collect(),
+        }
+    }
+
+    /// `Some(true)` when the last matching pattern excludes the path
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?.to_string_lossy();
+
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| (!pattern.dir_only || is_dir) && pattern.regex.is_match(&relative))
+            .map(|pattern| !pattern.negated)
+    }
+}
+
+/// Exclusion rules of a library root, deeper `.novaignore` files take precedence over
+/// shallower ones and over the patterns configured for the root
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    sets: Vec<RuleSet>,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, patterns: &[String]) -> Self {
+        IgnoreRules {
+            sets: vec![RuleSet::new(
+                root.to_path_buf(),
+                patterns.iter().map(|a| a.trim()),
+            )],
+        }
+    }
+
+    /// Rules for the contents of `dir`, including its `.novaignore` if it has one
+    pub fn enter(&self, dir: &Path) -> Self {
+        let mut rules = self.clone();
+
+        if let Ok(contents) = fs::read_to_string(dir.join(IGNORE_FILE)) {
+            rules
+                .sets
+                .push(RuleSet::new(dir.to_path_buf(), contents.lines()));
+        }
+
+        rules
+    }
+
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.sets
+            .iter()
+            .rev()
+            .find_map(|set| set.matches(path, is_dir))
+            .unwrap_or(false)
+    }
+
+    /// Rules that apply to `path` when it was not reached by walking down from the root,
+    /// `None` when it or one of its parents is hidden or excluded
+    pub fn walk_to(&self, root: &Path, path: &Path) -> Option<Self> {
+        let relative = path.strip_prefix(root).ok()?;
+
+        let mut rules = self.enter(root);
+        let mut current = root.to_path_buf();
+        let mut components = relative.components().peekable();
+
+        while let Some(component) = components.next() {
+            current.push(component);
+            let last = components.peek().is_none();
+            let is_dir = !last || current.is_dir();
+
+            if is_hidden(&current) || rules.is_excluded(&current, is_dir) {
+                return None;
+            }
+            if !last {
+                rules = rules.enter(&current);
+            }
+        }
+
+        Some(rules)
+    }
+}
+
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(patterns: &[&str]) -> IgnoreRules {
+        let patterns = patterns.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        IgnoreRules::new(Path::new("/music"), &patterns)
+    }
+
+    fn excluded(rules: &IgnoreRules, path: &str) -> bool {
+        rules.is_excluded(Path::new(path), false)
+    }
+
+    fn excluded_dir(rules: &IgnoreRules, path: &str) -> bool {
+        rules.is_excluded(Path::new(path), true)
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        let rules = rules(&["*.log", "# a comment", "", "Scans"]);
+        assert!(excluded(&rules, "/music/rip.log"));
+        assert!(excluded(&rules, "/music/Artist/Album/rip.log"));
+        assert!(excluded_dir(&rules, "/music/Artist/Scans"));
+        assert!(!excluded(&rules, "/music/Artist/rip.flac"));
+        assert!(!excluded(&rules, "/music/# a comment"));
+    }
+
+    #[test]
+    fn slashes_anchor_and_mark_directories() {
+        let rules = rules(&["/Demos", "Live/", "Artist/*/Bonus"]);
+        assert!(excluded_dir(&rules, "/music/Demos"));
+        assert!(!excluded_dir(&rules, "/music/Artist/Demos"));
+        assert!(excluded_dir(&rules, "/music/Artist/Live"));
+        assert!(!excluded(&rules, "/music/Artist/Live"));
+        assert!(excluded_dir(&rules, "/music/Artist/Album/Bonus"));
+        assert!(!excluded_dir(&rules, "/music/Artist/Album/Disc 1/Bonus"));
+    }
+
+    #[test]
+    fn globs() {
+        let rules = rules(&["**/tmp/**", "track?.wav", "[!0-9]*.mp3", "[abc.ogg", "\\#*"]);
+        assert!(excluded(&rules, "/music/tmp/a.flac"));
+        assert!(excluded(&rules, "/music/a/tmp/b/c.flac"));
+        assert!(excluded(&rules, "/music/track1.wav"));
+        assert!(!excluded(&rules, "/music/track10.wav"));
+        assert!(excluded(&rules, "/music/intro.mp3"));
+        assert!(!excluded(&rules, "/music/01 intro.mp3"));
+        // An unterminated class is plain text
+        assert!(excluded(&rules, "/music/[abc.ogg"));
+        assert!(excluded(&rules, "/music/#draft.flac"));
+    }
+
+    #[test]
+    fn later_patterns_win() {
+        let rules = rules(&["*.flac", "!keep*.flac", "keep-not.flac"]);
+        assert!(excluded(&rules, "/music/a.flac"));
+        assert!(!excluded(&rules, "/music/keep.flac"));
+        assert!(excluded(&rules, "/music/keep-not.flac"));
+    }
+
+    #[test]
+    fn ignore_files_below_take_precedence() {
+        let root = std::env::temp_dir().join(format!("nova_ignore_test_{}", std::process::id()));
+        let sub = root.join("Album");
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(sub.join(IGNORE_FILE), "!*.log\n").unwrap();
+
+        let rules = IgnoreRules::new(&root, &["*.log".to_string()]);
+        assert!(rules.is_excluded(&root.join("rip.log"), false));
+        assert!(!rules.enter(&sub).is_excluded(&sub.join("rip.log"), false));
+
+        let walked = rules.walk_to(&root, &sub.join("rip.log"));
+        assert!(walked.is_some_and(|rules| !rules.is_excluded(&sub.join("rip.log"), false)));
+        assert!(rules.walk_to(&root, &root.join(".hidden/a.flac")).is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,595 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::app::Message;
+use crate::database::{open_connection, unanalyzed_albums, write_loudness, Loudness};
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures::executor::block_on;
+use futures_util::SinkExt;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use symphonia::core::audio::{Channels, SampleBuffer, SignalSpec};
+use symphonia::core::errors::Error;
+use symphonia::core::probe::Hint;
+use symphonia::default::{get_codecs, get_probe};
+
+/// Gating blocks quieter than this are silence, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Gating blocks this far below the loudness of the ones above the absolute gate are left out
+const RELATIVE_GATE: f64 = -10.0;
+/// Gating blocks are 400 ms long and overlap by 75 %, so one ends every 100 ms step
+const STEPS_PER_BLOCK: usize = 4;
+/// Filter taps the true peak interpolation uses for each interpolated sample
+const TAPS_PER_PHASE: usize = 12;
+
+#[derive(Debug, Clone, Default)]
+pub struct LoudnessProgress {
+    /// Tracks on the albums that are measured
+    pub found: u32,
+    pub done: u32,
+}
+
+/// Measures the loudness of tracks without ReplayGain tags following EBU R128, album by album.
+/// An album is stored once all of its tracks are measured, so setting `cancel` loses at most the
+/// album in progress and the next analysis continues where this one stopped.
+pub async fn analyze_loudness(cancel: Arc<AtomicBool>, tx: &mut Sender<Message>) {
+    let mut tx = tx.clone();
+    if let Err(err) = tokio::task::spawn_blocking(move || analyze_albums(&cancel, &mut tx)).await {
+        log::error!("Loudness analysis stopped: {}", err);
+    }
+}
+
+fn analyze_albums(cancel: &AtomicBool, tx: &mut Sender<Message>) {
+    let mut conn = match open_connection() {
+        Ok(conn) => conn,
+        Err(err) => {
+            let _ = block_on(tx.send(Message::ToastError(err.to_string())));
+            return;
+        }
+    };
+
+    let albums = match unanalyzed_albums(&conn) {
+        Ok(albums) => albums,
+        Err(err) => {
+            log::error!("Could not find tracks to analyze: {}", err);
+            let _ = block_on(tx.send(Message::ToastError(err.to_string())));
+            return;
+        }
+    };
+
+    let mut progress = LoudnessProgress {
+        found: albums.iter().map(|album| album.tracks.len() as u32).sum(),
+        done: 0,
+    };
+    let _ = block_on(tx.send(Message::LoudnessProgress(progress.clone())));
+
+    for album in albums {
+        let measured = album
+            .tracks
+            .par_iter()
+            .map(|(_, path)| match measure_file(path, cancel) {
+                Ok(measurement) => Some(measurement),
+                Err(MeasureError::Cancelled) => None,
+                Err(MeasureError::Failed(err)) => {
+                    log::warn!(
+                        "Could not measure the loudness of {}: {}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect::<Vec<Option<Measurement>>>();
+
+        if cancel.load(Ordering::Relaxed) {
+            log::info!("Loudness analysis cancelled");
+            return;
+        }
+
+        // Tracks that could not be decoded stay unanalyzed, so the next analysis tries them again
+        let tracks = album
+            .tracks
+            .iter()
+            .zip(&measured)
+            .filter_map(|((id, _), measurement)| {
+                measurement
+                    .as_ref()
+                    .map(|measurement| (*id, measurement.loudness()))
+            })
+            .collect::<Vec<(u64, Option<Loudness>)>>();
+        // The album loudness is only known once every one of its tracks is measured
+        let album_loudness = if tracks.len() == album.tracks.len() {
+            Measurement::combine(measured.iter().flatten()).loudness()
+        } else {
+            None
+        };
+
+        if let Err(err) = write_loudness(&mut conn, album.album_id, &tracks, album_loudness) {
+            log::error!("Could not store the measured loudness: {}", err);
+        }
+
+        progress.done += album.tracks.len() as u32;
+        let _ = block_on(tx.send(Message::LoudnessProgress(progress.clone())));
+    }
+}
+
+/// Cancelling is not a failure to measure, it is left out of the warnings
+enum MeasureError {
+    Cancelled,
+    Failed(String),
+}
+
+impl From<String> for MeasureError {
+    fn from(err: String) -> Self {
+        MeasureError::Failed(err)
+    }
+}
+
+/// Decodes a whole file and measures it
+fn measure_file(path: &Path, cancel: &AtomicBool) -> Result<Measurement, MeasureError> {
+    let file = fs::File::open(path).map_err(|err| err.to_string())?;
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension() {
+        hint.with_extension(&extension.to_string_lossy());
+    }
+
+    let mut reader = get_probe()
+        .format(&hint, mss, &Default::default(), &Default::default())
+        .map_err(|err| err.to_string())?
+        .format;
+    let track = reader
+        .default_track()
+        .ok_or_else(|| "No audio track".to_string())?;
+    let track_id = track.id;
+    let mut decoder = get_codecs()
+        .make(&track.codec_params, &Default::default())
+        .map_err(|err| err.to_string())?;
+
+    let mut meter: Option<Meter> = None;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(MeasureError::Cancelled);
+        }
+
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(err.to_string().into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet is skipped, like playback does
+            Err(Error::DecodeError(err)) => {
+                log::warn!("Skipping a packet of {}: {}", path.display(), err);
+                continue;
+            }
+            Err(err) => return Err(err.to_string().into()),
+        };
+
+        let spec = *decoded.spec();
+        let frames = decoded.capacity();
+        let meter = meter.get_or_insert_with(|| Meter::new(&spec));
+        // Packets are decoded into the same buffer unless one is too large for it
+        if !matches!(&samples, Some(buffer) if buffer.capacity() >= frames * spec.channels.count())
+        {
+            samples = Some(SampleBuffer::new(frames as u64, spec));
+        }
+        let Some(buffer) = &mut samples else {
+            continue;
+        };
+
+        buffer.copy_interleaved_ref(decoded);
+        meter.process(buffer.samples());
+    }
+
+    meter
+        .map(Meter::finish)
+        .ok_or_else(|| MeasureError::Failed("No audio could be decoded".to_string()))
+}
+
+/// Mean square of every gating block along with the highest true peak, enough to work out the
+/// integrated loudness of a track or of several at once
+#[derive(Debug, Default)]
+struct Measurement {
+    blocks: Vec<f64>,
+    true_peak: f64,
+}
+
+impl Measurement {
+    /// Album loudness is gated over the blocks of all its tracks, not averaged from theirs
+    fn combine<'a>(measurements: impl Iterator<Item = &'a Measurement>) -> Measurement {
+        let mut combined = Measurement::default();
+        for measurement in measurements {
+            combined.blocks.extend(&measurement.blocks);
+            combined.true_peak = combined.true_peak.max(measurement.true_peak);
+        }
+        combined
+    }
+
+    /// `None` for silence, which has no loudness to correct
+    fn loudness(&self) -> Option<Loudness> {
+        let audible = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|power| block_loudness(*power) > ABSOLUTE_GATE)
+            .collect::<Vec<f64>>();
+        if audible.is_empty() {
+            return None;
+        }
+
+        let threshold = block_loudness(mean(&audible)) + RELATIVE_GATE;
+        let gated = audible
+            .into_iter()
+            .filter(|power| block_loudness(*power) > threshold)
+            .collect::<Vec<f64>>();
+        if gated.is_empty() {
+            return None;
+        }
+
+        Some(Loudness {
+            integrated: block_loudness(mean(&gated)),
+            true_peak: self.true_peak,
+        })
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Runs the samples of a stream through the K-weighting filters and collects its gating blocks
+struct Meter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    peaks: Vec<TruePeak>,
+    step_frames: usize,
+    /// Frames and weighted sum of squares of the step in progress
+    step: (usize, f64),
+    /// Sums of the last steps, a block is complete once there are enough of them
+    steps: VecDeque<f64>,
+    measurement: Measurement,
+}
+
+impl Meter {
+    fn new(spec: &SignalSpec) -> Meter {
+        let rate = spec.rate as f64;
+        let channels = spec.channels.count().max(1);
+
+        Meter {
+            channels,
+            weights: spec.channels.iter().map(channel_weight).collect(),
+            filters: (0..channels)
+                .map(|_| [Biquad::high_shelf(rate), Biquad::high_pass(rate)])
+                .collect(),
+            peaks: (0..channels).map(|_| TruePeak::new(spec.rate)).collect(),
+            step_frames: ((rate / 10.0).round() as usize).max(1),
+            step: (0, 0.0),
+            steps: VecDeque::with_capacity(STEPS_PER_BLOCK),
+            measurement: Measurement::default(),
+        }
+    }
+
+    /// Interleaved samples, as symphonia copies them out
+    fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mut sum = 0.0;
+            for (channel, sample) in frame.iter().enumerate() {
+                let sample = *sample as f64;
+                let [shelf, pass] = &mut self.filters[channel];
+                let filtered = pass.process(shelf.process(sample));
+                sum += self.weights.get(channel).copied().unwrap_or(1.0) * filtered * filtered;
+
+                let peak = self.peaks[channel].process(sample);
+                self.measurement.true_peak = self.measurement.true_peak.max(peak);
+            }
+
+            self.step.0 += 1;
+            self.step.1 += sum;
+            if self.step.0 == self.step_frames {
+                self.steps.push_back(self.step.1);
+                self.step = (0, 0.0);
+
+                if self.steps.len() > STEPS_PER_BLOCK {
+                    self.steps.pop_front();
+                }
+                if self.steps.len() == STEPS_PER_BLOCK {
+                    let block = self.steps.iter().sum::<f64>()
+                        / (STEPS_PER_BLOCK * self.step_frames) as f64;
+                    self.measurement.blocks.push(block);
+                }
+            }
+        }
+    }
+
+    /// A block cut off by the end of the stream is left out
+    fn finish(self) -> Measurement {
+        self.measurement
+    }
+}
+
+/// Surround channels count for more than the front ones and LFE is not counted at all
+fn channel_weight(channel: Channels) -> f64 {
+    let surround =
+        Channels::REAR_LEFT | Channels::REAR_RIGHT | Channels::SIDE_LEFT | Channels::SIDE_RIGHT;
+
+    if channel.intersects(Channels::LFE1 | Channels::LFE2) {
+        0.0
+    } else if channel.intersects(surround) {
+        1.41
+    } else {
+        1.0
+    }
+}
+
+/// Second order section of the K-weighting filter, in transposed direct form II
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    /// First stage, models the acoustic effect of the head. The coefficients in BS.1770 are for
+    /// 48 kHz, so the filter is designed again for the rate of the stream.
+    fn high_shelf(rate: f64) -> Biquad {
+        let frequency = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * frequency / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    /// Second stage, the RLB high pass
+    fn high_pass(rate: f64) -> Biquad {
+        let frequency = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * frequency / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        let output = self.b[0] * sample + self.state[0];
+        self.state[0] = self.b[1] * sample - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * sample - self.a[1] * output;
+        output
+    }
+}
+
+/// Peak of the signal between its samples, found by oversampling it to at least 176.4 kHz with a
+/// windowed sinc split into one phase per interpolated sample
+struct TruePeak {
+    phases: Vec<[f64; TAPS_PER_PHASE]>,
+    history: [f64; TAPS_PER_PHASE],
+    position: usize,
+}
+
+impl TruePeak {
+    fn new(rate: u32) -> TruePeak {
+        let factor = match rate {
+            rate if rate < 96000 => 4,
+            rate if rate < 192000 => 2,
+            _ => 1,
+        };
+        let length = factor * TAPS_PER_PHASE;
+        let center = (length - 1) as f64 / 2.0;
+
+        let phases = (0..factor)
+            .map(|phase| {
+                let mut taps = [0.0; TAPS_PER_PHASE];
+                for (tap, coefficient) in taps.iter_mut().enumerate() {
+                    let n = tap * factor + phase;
+                    let t = (n as f64 - center) / factor as f64;
+                    let sinc = match t == 0.0 {
+                        true => 1.0,
+                        false => (PI * t).sin() / (PI * t),
+                    };
+                    let window =
+                        0.5 - 0.5 * (2.0 * PI * (n as f64 + 1.0) / (length as f64 + 1.0)).cos();
+                    *coefficient = sinc * window;
+                }
+                taps
+            })
+            .collect();
+
+        TruePeak {
+            // Streams that are already oversampled enough only need their sample peak
+            phases: match factor {
+                1 => vec![],
+                _ => phases,
+            },
+            history: [0.0; TAPS_PER_PHASE],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.history[self.position] = sample;
+        self.position = (self.position + 1) % TAPS_PER_PHASE;
+
+        let mut peak = sample.abs();
+        for taps in &self.phases {
+            // The newest sample meets the first tap
+            let interpolated = taps
+                .iter()
+                .enumerate()
+                .map(|(tap, coefficient)| {
+                    let index = (self.position + TAPS_PER_PHASE - 1 - tap) % TAPS_PER_PHASE;
+                    coefficient * self.history[index]
+                })
+                .sum::<f64>();
+            peak = peak.max(interpolated.abs());
+        }
+
+        peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(channels: Channels, rate: u32, seconds: f64, amplitude: f64) -> Measurement {
+        let spec = SignalSpec::new(rate, channels);
+        let mut meter = Meter::new(&spec);
+        // 997 Hz, as the sines in EBU Tech 3341
+        let samples = (0..(rate as f64 * seconds) as usize)
+            .map(|frame| amplitude * (2.0 * PI * 997.0 * frame as f64 / rate as f64).sin())
+            .flat_map(|sample| vec![sample as f32; spec.channels.count()])
+            .collect::<Vec<f32>>();
+        meter.process(&samples);
+        meter.finish()
+    }
+
+    fn integrated(measurement: &Measurement) -> f64 {
+        measurement.loudness().unwrap().integrated
+    }
+
+    fn dbfs(level: f64) -> f64 {
+        10f64.powf(level / 20.0)
+    }
+
+    #[test]
+    fn sines_measure_as_in_bs1770() {
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+
+        let mono = measure(Channels::FRONT_LEFT, 48000, 5.0, 1.0);
+        assert!((integrated(&mono) + 3.01).abs() < 0.05);
+        let mono = measure(Channels::FRONT_LEFT, 44100, 5.0, 1.0);
+        assert!((integrated(&mono) + 3.01).abs() < 0.05);
+
+        let stereo = measure(stereo, 48000, 5.0, dbfs(-23.0));
+        assert!((integrated(&stereo) + 23.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn blocks_overlap() {
+        // Blocks are 400 ms long and one ends every 100 ms after the first
+        let measurement = measure(Channels::FRONT_LEFT, 48000, 1.05, 1.0);
+        assert_eq!(measurement.blocks.len(), 7);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        assert!(measure(Channels::FRONT_LEFT, 48000, 2.0, 0.0)
+            .loudness()
+            .is_none());
+        assert!(measure(Channels::FRONT_LEFT, 48000, 2.0, dbfs(-80.0))
+            .loudness()
+            .is_none());
+        assert!(Measurement::default().loudness().is_none());
+    }
+
+    #[test]
+    fn true_peak_is_found_between_samples() {
+        // A quarter of the sample rate, sampled 45° off its peaks every sample is at -3 dB
+        let mut peak = TruePeak::new(48000);
+        let true_peak = (0..480)
+            .map(|n| (PI / 2.0 * n as f64 + PI / 4.0).sin())
+            .map(|sample| peak.process(sample))
+            .fold(0.0, f64::max);
+        assert!((true_peak - 1.0).abs() < 0.05);
+
+        let mut peak = TruePeak::new(192000);
+        assert_eq!(peak.process(-0.5), 0.5);
+    }
+
+    #[test]
+    fn albums_are_gated_over_the_blocks_of_all_tracks() {
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        let loud = measure(stereo, 48000, 5.0, dbfs(-20.0));
+        let quiet = measure(stereo, 48000, 5.0, dbfs(-40.0));
+
+        // The quiet track is more than 10 LU below the album, so it is gated out
+        let album = Measurement::combine([&loud, &quiet].into_iter())
+            .loudness()
+            .unwrap();
+        assert!((album.integrated + 20.0).abs() < 0.05);
+        assert_eq!(album.true_peak, loud.true_peak);
+        assert!((integrated(&quiet) + 40.0).abs() < 0.05);
+    }
+
+    /// A second of 16 bit mono PCM at -20 dBFS
+    fn write_wav(path: &Path) {
+        let rate: u32 = 48000;
+        let data = (0..rate)
+            .map(|frame| dbfs(-20.0) * (2.0 * PI * 997.0 * frame as f64 / rate as f64).sin())
+            .flat_map(|sample| ((sample * i16::MAX as f64) as i16).to_le_bytes())
+            .collect::<Vec<u8>>();
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((36 + data.len() as u32).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(rate.to_le_bytes());
+        wav.extend((rate * 2).to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend((data.len() as u32).to_le_bytes());
+        wav.extend(data);
+        fs::write(path, wav).unwrap();
+    }
+
+    #[test]
+    fn cancelling_is_not_a_failure() {
+        let dir = std::env::temp_dir().join(format!("nova-loudness-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sine.wav");
+        write_wav(&path);
+
+        let measured = measure_file(&path, &AtomicBool::new(false));
+        assert!(
+            matches!(measured, Ok(measurement) if (integrated(&measurement) + 23.01).abs() < 0.1)
+        );
+        assert!(matches!(
+            measure_file(&path, &AtomicBool::new(true)),
+            Err(MeasureError::Cancelled)
+        ));
+        assert!(matches!(
+            measure_file(&dir.join("missing.wav"), &AtomicBool::new(false)),
+            Err(MeasureError::Failed(_))
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+/// Lyrics of the track playing, LRC lyrics are synced to it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lyrics {
+    Synced(Vec<LyricLine>),
+    Plain(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    /// In seconds from the start of the track
+    pub time: f64,
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Text with at least one timestamped line is read as LRC, anything else is shown as it is
+    pub fn parse(text: &str) -> Lyrics {
+        let mut lines = vec![];
+        // In milliseconds, positive values show the lyrics earlier
+        let mut offset = 0.0;
+
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = vec![];
+
+            // A line repeated in a song can carry several timestamps, metadata like `[ar:...]`
+            // takes up a line of its own
+            while let Some((tag, after)) = rest
+                .strip_prefix('[')
+                .and_then(|tagged| tagged.split_once(']'))
+            {
+                match parse_timestamp(tag) {
+                    Some(time) => times.push(time),
+                    None => {
+                        if let Some(value) = tag.strip_prefix("offset:") {
+                            offset = value.trim().parse().unwrap_or(0.0);
+                        }
+                    }
+                }
+                rest = after.trim_start();
+            }
+
+            let text = strip_word_timestamps(rest);
+            lines.extend(times.into_iter().map(|time| LyricLine {
+                time,
+                text: text.clone(),
+            }));
+        }
+
+        if lines.is_empty() {
+            return Lyrics::Plain(text.trim().to_string());
+        }
+
+        for line in &mut lines {
+            line.time = (line.time - offset / 1000.0).max(0.0);
+        }
+        lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Lyrics::Synced(lines)
+    }
+
+    /// Line being sung at `position`, `None` before the first one and for plain lyrics
+    pub fn current_line(&self, position: f64) -> Option<usize> {
+        match self {
+            Lyrics::Synced(lines) => lines
+                .partition_point(|line| line.time <= position)
+                .checked_sub(1),
+            Lyrics::Plain(_) => None,
+        }
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`, in seconds
+fn parse_timestamp(tag: &str) -> Option<f64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u32>().ok()?;
+    let seconds = match seconds.split_once(':') {
+        Some((seconds, hundredths)) => format!("{}.{}", seconds, hundredths),
+        None => seconds.to_string(),
+    };
+    let seconds = seconds.trim().parse::<f64>().ok()?;
+
+    (seconds.is_finite() && seconds >= 0.0).then_some(minutes as f64 * 60.0 + seconds)
+}
+
+/// Enhanced LRC times single words with `<mm:ss.xx>`, only whole lines are followed here
+fn strip_word_timestamps(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if parse_timestamp(&rest[start + 1..start + end]).is_some() => {
+                stripped.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                stripped.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    stripped.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(time: f64, text: &str) -> LyricLine {
+        LyricLine {
+            time,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn reads_timestamps() {
+        assert_eq!(parse_timestamp("01:02.50"), Some(62.5));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62.5));
+        assert_eq!(parse_timestamp("1:02"), Some(62.0));
+        assert_eq!(parse_timestamp("ar:Someone"), None);
+        assert_eq!(parse_timestamp("01:-2"), None);
+        assert_eq!(parse_timestamp("0102"), None);
+    }
+
+    #[test]
+    fn strips_word_timestamps_only() {
+        assert_eq!(
+            strip_word_timestamps("<00:01.00>Hello  <00:01.50>world"),
+            "Hello world"
+        );
+        assert_eq!(strip_word_timestamps("a <b> c < d"), "a <b> c < d");
+    }
+
+    #[test]
+    fn parses_lrc_with_offset_and_repeated_lines() {
+        let lyrics = Lyrics::parse(
+            "[ar:Someone]\n\
+             [offset:+500]\n\
+             [00:10.50][00:01.00]Chorus\n\
+             [00:05.00]<00:05.00>Verse <00:05.50>line\n",
+        );
+        assert_eq!(
+            lyrics,
+            Lyrics::Synced(vec![
+                line(0.5, "Chorus"),
+                line(4.5, "Verse line"),
+                line(10.0, "Chorus"),
+            ])
+        );
+
+        assert_eq!(lyrics.current_line(0.2), None);
+        assert_eq!(lyrics.current_line(5.0), Some(1));
+        assert_eq!(lyrics.current_line(600.0), Some(2));
+    }
+
+    #[test]
+    fn offsets_do_not_go_before_the_start() {
+        assert_eq!(
+            Lyrics::parse("[offset:2000]\n[00:01.00]First\n[00:03.00]Second"),
+            Lyrics::Synced(vec![line(0.0, "First"), line(1.0, "Second")])
+        );
+        // A negative offset shows the lyrics later
+        assert_eq!(
+            Lyrics::parse("[offset:-1000]\n[00:01.00]First"),
+            Lyrics::Synced(vec![line(2.0, "First")])
+        );
+    }
+
+    #[test]
+    fn text_without_timestamps_is_plain() {
+        let lyrics = Lyrics::parse("\nJust words\n[chorus]\n");
+        assert_eq!(lyrics, Lyrics::Plain("Just words\n[chorus]".to_string()));
+        assert_eq!(lyrics.current_line(10.0), None);
+    }
+}
@@ -7,6 +7,7 @@ use cosmic::widget::JustifyContent;
 use cosmic::{iced, Application, Element};
 use std::sync::Arc;
 
+use crate::app::home::{format_time, total_runtime};
 use crate::app::tracks::SearchResult;
 use crate::{app, fl};
 
@@ -34,6 +35,8 @@ pub struct FullPlaylist {
 pub struct PlaylistTrack {
     pub(crate) title: String,
     pub(crate) path: String,
+    /// From the library, `None` for files it does not know
+    pub(crate) duration: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -215,6 +218,10 @@ impl PlaylistPage {
                                 // Album Title and Author Column
                                 cosmic::widget::text::title2(playlist.playlist.title.as_str())
                                     .into(),
+                                cosmic::widget::text::caption(total_runtime(
+                                    playlist.tracks.iter().map(|track| track.duration),
+                                ))
+                                .into(),
                                 cosmic::widget::divider::horizontal::default().into(),
                                 cosmic::widget::row::with_children(vec![
                                     cosmic::widget::button::text(fl!("AddToQueue"))
@@ -281,12 +288,10 @@ impl PlaylistPage {
                     let mut playlists: Vec<Playlist> = vec![];
 
                     for each in search_results {
-                        if (0..=2).contains(&each.score) {
-                            match self.playlists.get(each.tracks_index) {
-                                None => {}
-                                Some(val) => {
-                                    playlists.push(val.clone());
-                                }
+                        match self.playlists.get(each.tracks_index) {
+                            None => {}
+                            Some(val) => {
+                                playlists.push(val.clone());
                             }
                         }
                     }
@@ -432,6 +437,10 @@ fn tracks_listify(tracks: &Vec<PlaylistTrack>) -> Element<'static, Message> {
                         cosmic::widget::row::with_children(vec![
                             cosmic::widget::text::heading(format!("{}", track.title,)).into(),
                             cosmic::widget::horizontal_space().into(),
+                            cosmic::widget::text::text(
+                                track.duration.map(format_time).unwrap_or_default(),
+                            )
+                            .into(),
                             cosmic::widget::button::icon(cosmic::widget::icon::from_name(
                                 "media-playback-start-symbolic",
                             ))
@@ -1,35 +1,675 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use crate::app::ignore::{is_hidden, IgnoreRules};
+use crate::app::watcher::LibraryChange;
 use crate::app::Message;
+use crate::config::LibraryRoot;
+use crate::database::{
+    claim_tracks, file_signature, known_files, lyrics_sidecar, open_connection, prune_artwork,
+    read_tags, record_root_device, record_scan_errors, remove_missing_files, remove_path,
+    remove_track, root_device, write_track, AudioProperties, ProbedTrack, TagOptions,
+};
+use crate::fl;
 use cosmic::iced::futures::channel::mpsc::Sender;
+use cosmic::Application;
+use futures::executor::block_on;
 use futures_util::SinkExt;
-use std::path::PathBuf;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rusqlite::{Connection, TransactionBehavior};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use symphonia::core::meta::MetadataRevision;
+use symphonia::default::get_probe;
 
-pub async fn scan_directory(path: PathBuf, tx: &mut Sender<Message>) {
-    let mut index = 0;
-    read_dir(path, tx, &mut index).await
+/// Extensions that are probed, everything else in a library root is skipped before probing.
+/// Playlists are kept so they can be copied into the playlists directory.
+const SCANNED_EXTENSIONS: &[&str] = &[
+    "aac", "adts", "aif", "aifc", "aiff", "caf", "flac", "m4a", "m4b", "mka", "mkv", "mp1", "mp2",
+    "mp3", "mp4", "oga", "ogg", "opus", "wav", "wave", "webm", "m3u", "m3u8",
+];
+
+pub fn is_scanned_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| SCANNED_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Files written per transaction
+const WRITE_BATCH_SIZE: usize = 250;
+/// Longest a probed file waits for its batch to fill up before it is written anyway
+const WRITE_BATCH_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanProgress {
+    /// Files in the library roots that can be added
+    pub found: u32,
+    /// Files that are in the library, unchanged ones count as done right after the walk
+    pub done: u32,
+    /// Files written per second since probing started
+    pub rate: Option<f32>,
+    /// Time left at the current rate
+    pub eta: Option<Duration>,
+}
+
+/// Result of probing a single file
+enum Probed {
+    Track(Box<ProbedTrack>),
+    Playlist,
+    /// Could not be read, with the reason shown under scan problems
+    Failed(String),
+    /// Deleted again before it was probed
+    Gone,
+}
+
+/// Walks every library root and only sends files that are new or changed since the last scan
+/// to be probed, or every file when `reprobe` is set. Tracks whose files were not found anymore
+/// are removed once a root is walked, unless it could not be read completely or looks like a
+/// drive that is not mounted. Returns whether every file that was found got read.
+pub async fn scan_directory(
+    roots: Vec<LibraryRoot>,
+    options: TagOptions,
+    reprobe: bool,
+    tx: &mut Sender<Message>,
+) -> bool {
+    let known = match reprobe {
+        true => HashMap::new(),
+        false => match known_files() {
+            Ok(known) => known,
+            Err(err) => {
+                log::error!("Could not read the library to scan: {}", err);
+                let _ = tx.send(Message::ToastError(err.to_string())).await;
+                return false;
+            }
+        },
+    };
+    let mut progress = ScanProgress::default();
+    let mut changed = vec![];
+    let mut complete = true;
+
+    for root in roots {
+        let path = PathBuf::from(&root.path);
+        let rules = IgnoreRules::new(&path, &root.exclude);
+        let mut visited = HashSet::new();
+        let mut seen = HashSet::new();
+
+        if let Err(err) = claim_tracks(&path) {
+            log::error!("Could not assign tracks to {}: {}", path.display(), err);
+        }
+        let walked = read_dir(
+            path.clone(),
+            &path,
+            &rules,
+            &mut visited,
+            tx,
+            &known,
+            &mut seen,
+            &mut changed,
+        )
+        .await;
+        progress.found += seen.len() as u32;
+
+        if walked.is_err() {
+            log::warn!(
+                "Keeping the tracks of {}, it could not be read",
+                path.display()
+            );
+            complete = false;
+            continue;
+        }
+
+        // An unmounted drive leaves its mount point behind, an empty directory on the device
+        // it is mounted on
+        let device = fs::metadata(&path).map(|metadata| metadata.dev()).ok();
+        let known_device = root_device(&path).unwrap_or_else(|err| {
+            log::error!("Could not read the device of {}: {}", path.display(), err);
+            None
+        });
+
+        let unavailable = if seen.is_empty() {
+            Some(fl!("RootEmpty", path = root.path.clone()))
+        } else if known_device.is_some() && known_device != device {
+            Some(fl!("RootDeviceChanged", path = root.path.clone()))
+        } else {
+            None
+        };
+
+        if let Some(reason) = unavailable {
+            log::warn!("Keeping the tracks of {}: {}", path.display(), reason);
+            let _ = tx.send(Message::ToastError(reason)).await;
+            continue;
+        }
+
+        if let Err(err) = remove_missing_files(&path, &seen) {
+            log::error!(
+                "Could not remove missing files of {}: {}",
+                path.display(),
+                err
+            );
+            let _ = tx.send(Message::ToastError(err.to_string())).await;
+            continue;
+        }
+        if let Some(device) = device {
+            if let Err(err) = record_root_device(&path, device) {
+                log::error!("Could not record the device of {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    progress.done = progress.found - changed.len() as u32;
+    let _ = tx.send(Message::ScanProgress(progress.clone())).await;
+
+    let mut tx = tx.clone();
+    match tokio::task::spawn_blocking(move || {
+        let imported = import_files(changed, options, Some(progress), &mut tx);
+        // Albums that are gone or got another cover leave theirs behind in the store
+        if let Err(err) = prune_artwork() {
+            log::error!("Could not remove unused covers: {}", err);
+        }
+        imported
+    })
+    .await
+    {
+        Ok(imported) => complete && imported,
+        Err(err) => {
+            log::error!("Scan stopped: {}", err);
+            false
+        }
+    }
+}
+
+/// Collects the files below `path`, fails when a directory could not be read so the files that
+/// were not seen are not taken for deleted ones
+#[allow(clippy::too_many_arguments)]
+async fn read_dir(
+    path: PathBuf,
+    root: &Path,
+    rules: &IgnoreRules,
+    visited: &mut HashSet<PathBuf>,
+    tx: &mut Sender<Message>,
+    known: &HashMap<PathBuf, (u64, i64)>,
+    seen: &mut HashSet<PathBuf>,
+    changed: &mut Vec<(PathBuf, PathBuf)>,
+) -> std::io::Result<()> {
+    // Symlinks can lead back up the tree or into a folder that was already walked
+    let first_visit = fs::canonicalize(&path)
+        .map(|canonical| visited.insert(canonical))
+        .unwrap_or(false);
+    if !first_visit {
+        log::warn!("Skipping {}, it was already scanned", path.display());
+        return Ok(());
+    }
+
+    let rules = rules.enter(&path);
+    let mut walked = Ok(());
+
+    match path.read_dir() {
+        Ok(dir) => {
+            for entry in dir {
+                if let Ok(entry) = entry {
+                    let path = entry.path();
+                    if is_hidden(&path) {
+                        continue;
+                    }
+
+                    // Follows symlinks, a link is scanned as whatever it points to
+                    if let Ok(entry) = fs::metadata(&path) {
+                        if rules.is_excluded(&path, entry.is_dir()) {
+                            continue;
+                        }
+
+                        if entry.is_dir() {
+                            // The rest of the tree is still walked for new and changed files
+                            let subdir = Box::pin(read_dir(
+                                path, root, &rules, visited, tx, known, seen, changed,
+                            ))
+                            .await;
+                            if subdir.is_err() {
+                                walked = subdir;
+                            }
+                        } else if is_scanned_file(&path) {
+                            // Unchanged files were already probed by an earlier scan
+                            if known.get(&path) != Some(&file_signature(&path, &entry)) {
+                                changed.push((root.to_path_buf(), path.clone()));
+                            }
+
+                            seen.insert(path);
+                        }
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            let _ = tx
+                .send(Message::ToastError(String::from(format!(
+                    "Error at path: {}",
+                    path.to_string_lossy().to_string()
+                ))))
+                .await;
+            return Err(err);
+        }
+    }
+
+    walked
+}
+
+/// Brings the library in line with changes the watcher saw on disk, without touching the
+/// scan progress shown in settings
+pub async fn apply_library_changes(
+    root: LibraryRoot,
+    changes: Vec<LibraryChange>,
+    options: TagOptions,
+    tx: &mut Sender<Message>,
+) {
+    let known = match known_files() {
+        Ok(known) => known,
+        Err(err) => {
+            log::error!("Could not read the library to update: {}", err);
+            let _ = tx.send(Message::ToastError(err.to_string())).await;
+            return;
+        }
+    };
+    let path = PathBuf::from(&root.path);
+    let rules = IgnoreRules::new(&path, &root.exclude);
+
+    // Sidecar lyrics are not files of their own, the tracks they belong to are probed again
+    let (lyrics, changes): (Vec<_>, Vec<_>) = changes.into_iter().partition(|change| {
+        let (LibraryChange::Updated(changed) | LibraryChange::Removed(changed)) = change;
+        changed
+            .extension()
+            .is_some_and(|extension| extension == "lrc")
+    });
+
+    // Removals first, so a directory that was replaced does not take its new files with it
+    let (removed, updated): (Vec<_>, Vec<_>) = changes
+        .into_iter()
+        .partition(|change| matches!(change, LibraryChange::Removed(_)));
+
+    let mut updated_files = vec![];
+    for change in removed.into_iter().chain(updated) {
+        match change {
+            LibraryChange::Removed(changed) => {
+                if let Err(err) = remove_path(&changed) {
+                    log::error!("Could not remove {}: {}", changed.display(), err);
+                }
+            }
+            LibraryChange::Updated(changed) => {
+                let Some(rules) = rules.walk_to(&path, &changed) else {
+                    continue;
+                };
+
+                let mut files = vec![];
+                collect_files(changed, &rules, &mut HashSet::new(), &mut files);
+
+                updated_files.extend(
+                    files
+                        .into_iter()
+                        .filter(|(file, signature)| known.get(file) != Some(signature))
+                        .map(|(file, _)| (path.clone(), file)),
+                );
+            }
+        }
+    }
+
+    for change in lyrics {
+        let (LibraryChange::Updated(sidecar) | LibraryChange::Removed(sidecar)) = change;
+        for file in known.keys().filter(|file| lyrics_sidecar(file) == sidecar) {
+            if !updated_files.iter().any(|(_, updated)| updated == file) {
+                updated_files.push((path.clone(), file.clone()));
+            }
+        }
+    }
+
+    let mut tx = tx.clone();
+    if let Err(err) =
+        tokio::task::spawn_blocking(move || import_files(updated_files, options, None, &mut tx))
+            .await
+    {
+        log::error!("Library update stopped: {}", err);
+    }
 }
 
-async fn read_dir(path: PathBuf, tx: &mut Sender<Message>, index: &mut u32) {
-    if let Ok(dir) = path.read_dir() {
-        for entry in dir {
-            if let Ok(entry) = entry {
+/// Files at or below `path` that a scan would pick up, a directory that was moved in shows
+/// up as a single change
+fn collect_files(
+    path: PathBuf,
+    rules: &IgnoreRules,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<(PathBuf, (u64, i64))>,
+) {
+    let Ok(metadata) = fs::metadata(&path) else {
+        return;
+    };
+
+    if metadata.is_dir() {
+        let first_visit = fs::canonicalize(&path)
+            .map(|canonical| visited.insert(canonical))
+            .unwrap_or(false);
+        if !first_visit {
+            return;
+        }
+
+        let rules = rules.enter(&path);
+        if let Ok(dir) = path.read_dir() {
+            for entry in dir.flatten() {
                 let path = entry.path();
-                if let Ok(entry) = entry.metadata() {
-                    if entry.is_dir() {
-                        Box::pin(read_dir(path, tx, index)).await;
-                    } else {
-                        tx.send(Message::UpdateScanDirSize).await.unwrap();
-                        tx.send(Message::AddToDatabase(path.clone())).await.unwrap();
+                if !is_hidden(&path) && !rules.is_excluded(&path, path.is_dir()) {
+                    collect_files(path, &rules, visited, files);
+                }
+            }
+        }
+    } else if is_scanned_file(&path) {
+        let signature = file_signature(&path, &metadata);
+        files.push((path, signature));
+    }
+}
+
+/// Probes `files`, pairs of library root and file, on the rayon pool and writes them from a
+/// single connection in batched transactions. Progress is only reported when a scan passes its
+/// counts from walking the roots. Returns false when the library could not be opened to write
+/// them.
+fn import_files(
+    files: Vec<(PathBuf, PathBuf)>,
+    options: TagOptions,
+    mut progress: Option<ScanProgress>,
+    tx: &mut Sender<Message>,
+) -> bool {
+    if files.is_empty() {
+        return true;
+    }
+
+    let mut conn = match open_connection() {
+        Ok(conn) => conn,
+        Err(err) => {
+            let _ = block_on(tx.send(Message::ToastError(err.to_string())));
+            return false;
+        }
+    };
+
+    let total = files.len();
+    let (probed_tx, probed) = mpsc::sync_channel(WRITE_BATCH_SIZE);
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            files
+                .into_par_iter()
+                .for_each_with(probed_tx, |probed_tx, (root, path)| {
+                    let result = probe_file(&root, &path, &options);
+                    // The writer only goes away when the app is closing
+                    let _ = probed_tx.send((root, path, result));
+                });
+        });
+
+        let started = Instant::now();
+        let mut processed = 0;
+
+        while let Ok(first) = probed.recv() {
+            let mut batch = vec![first];
+            let deadline = Instant::now() + WRITE_BATCH_TIMEOUT;
+            while batch.len() < WRITE_BATCH_SIZE {
+                match probed.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(next) => batch.push(next),
+                    Err(_) => break,
+                }
+            }
+
+            let batch_size = batch.len();
+            let failed = write_batch(&mut conn, batch);
+            processed += batch_size;
+
+            if let Some(progress) = &mut progress {
+                // Files that could not be added do not count towards the library
+                progress.found -= failed as u32;
+                progress.done += (batch_size - failed) as u32;
+
+                let rate = processed as f32 / started.elapsed().as_secs_f32().max(0.001);
+                progress.rate = Some(rate);
+                progress.eta = Some(Duration::from_secs_f32((total - processed) as f32 / rate));
+
+                let _ = block_on(tx.send(Message::ScanProgress(progress.clone())));
+            }
+        }
+    });
+
+    true
+}
+
+/// Writes a batch in one transaction and returns how many of its files could not be added
+fn write_batch(conn: &mut Connection, batch: Vec<(PathBuf, PathBuf, Probed)>) -> usize {
+    let batch_size = batch.len();
+    let mut failed = 0;
+
+    // Taking the write lock up front, a transaction that reads first can not wait for it later
+    let mut transaction = match conn.transaction_with_behavior(TransactionBehavior::Immediate) {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            log::error!("Could not start writing scanned files: {}", err);
+            return batch_size;
+        }
+    };
+
+    for (root, path, probed) in batch {
+        match probed {
+            Probed::Track(track) => {
+                // A savepoint per file, so a file that cannot be written does not take the
+                // rest of the batch with it
+                let written = transaction.savepoint().and_then(|savepoint| {
+                    record_scan_errors(&savepoint, &path, &root, &track.problems)?;
+                    write_track(&savepoint, *track, &path, &root)?;
+                    savepoint.commit()
+                });
+
+                if let Err(err) = written {
+                    log::error!("Could not add {}: {}", path.display(), err);
+                    failed += 1;
+                    let reason = fl!("ScanWriteFailed", error = err.to_string());
+                    if let Err(err) = record_scan_errors(&transaction, &path, &root, &[reason]) {
+                        log::error!(
+                            "Could not record scan problem of {}: {}",
+                            path.display(),
+                            err
+                        );
                     }
                 }
             }
+            Probed::Playlist => {
+                if let Err(err) = record_scan_errors(&transaction, &path, &root, &[]) {
+                    log::error!(
+                        "Could not clear scan problems of {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+            Probed::Failed(reason) => {
+                failed += 1;
+                // Whatever was recorded for the file before no longer matches what is on disk
+                let recorded = transaction.savepoint().and_then(|savepoint| {
+                    remove_track(&savepoint, &path)?;
+                    record_scan_errors(&savepoint, &path, &root, &[reason])?;
+                    savepoint.commit()
+                });
+                if let Err(err) = recorded {
+                    log::error!(
+                        "Could not record scan problem of {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+            Probed::Gone => failed += 1,
         }
-    } else {
-        tx.send(Message::ToastError(String::from(format!(
-            "Error at path: {}",
-            path.to_string_lossy().to_string()
-        ))))
-        .await;
+    }
+
+    match transaction.commit() {
+        Ok(_) => failed,
+        Err(err) => {
+            log::error!("Could not write scanned files: {}", err);
+            batch_size
+        }
+    }
+}
+
+/// Probes a file and reads its tags, playlists are copied into the playlists directory.
+/// Runs on the rayon pool, nothing here may touch the database.
+fn probe_file(root: &Path, path: &Path, options: &TagOptions) -> Probed {
+    // The watcher can hand over files that are already gone again
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Probed::Gone,
+        Err(err) => return Probed::Failed(fl!("ScanOpenFailed", error = err.to_string())),
+    };
+    let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    let probe = get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(file), Default::default());
+
+    match probe.format(
+        &Default::default(),
+        mss,
+        &Default::default(),
+        &Default::default(),
+    ) {
+        Ok(mut reader) => {
+            // A file without any tags still becomes a track, a panic here would stop the whole
+            // scan
+            // Tags without a standard key are kept for the ones read by name, like `WORK`
+            let all_tags = |revision: &MetadataRevision| revision.tags().to_vec();
+            let tags = match reader.metadata.get() {
+                Some(mdat) => mdat.current().map(all_tags),
+                None => reader.format.metadata().current().map(all_tags),
+            }
+            .unwrap_or_default();
+            let path_tags = options
+                .fallback
+                .as_ref()
+                .map(|pattern| pattern.apply(root, path));
+            let properties = reader
+                .format
+                .default_track()
+                .map(|track| AudioProperties::new(&track.codec_params, size))
+                .unwrap_or_default();
+            Probed::Track(Box::new(read_tags(
+                tags,
+                &path.to_path_buf(),
+                path_tags,
+                &options.separators,
+                &options.genres,
+                &options.cover_names,
+                properties,
+            )))
+        }
+        Err(_) if path.with_extension("m3u") == path || path.with_extension("m3u8") == path => {
+            let dir = dirs::data_local_dir()
+                .unwrap()
+                .join(crate::app::AppModel::APP_ID)
+                .join("Playlists");
+
+            if !dir.exists() {
+                if let Err(err) = std::fs::create_dir(&dir) {
+                    log::error!("Could not create {}: {}", dir.display(), err);
+                    return Probed::Failed(fl!("PlaylistCopyFailed", error = err.to_string()));
+                }
+            }
+
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            match fs::copy(path, dir.join(name)) {
+                Ok(_) => Probed::Playlist,
+                Err(err) => {
+                    log::error!("Could not copy playlist {}: {}", path.display(), err);
+                    Probed::Failed(fl!("PlaylistCopyFailed", error = err.to_string()))
+                }
+            }
+        }
+        Err(err) => {
+            log::info!(
+                "ERROR: Probe failure \nErred Path: {}",
+                path.to_string_lossy()
+            );
+            Probed::Failed(fl!("ScanProbeFailed", error = err.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate_database;
+    use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+    fn probed(title: &str, artist: &str) -> (PathBuf, PathBuf, Probed) {
+        let path = PathBuf::from(format!("/music/{}.flac", title));
+        let tags = vec![
+            Tag::new(Some(StandardTagKey::TrackTitle), "", Value::from(title)),
+            Tag::new(Some(StandardTagKey::Artist), "", Value::from(artist)),
+        ];
+        let track = read_tags(
+            tags,
+            &path,
+            None,
+            &Default::default(),
+            &Default::default(),
+            &[],
+            AudioProperties::default(),
+        );
+        (
+            PathBuf::from("/music"),
+            path,
+            Probed::Track(Box::new(track)),
+        )
+    }
+
+    fn names(conn: &Connection, query: &str) -> Vec<String> {
+        conn.prepare(query)
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn files_that_can_not_be_written_leave_the_batch_alone() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrate_database(&conn).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TEMP TRIGGER broken BEFORE INSERT ON track WHEN new.name = 'Broken' BEGIN
+                SELECT RAISE(ABORT, 'broken');
+            END;
+            ",
+        )
+        .unwrap();
+
+        let root = PathBuf::from("/music");
+        let batch = vec![
+            probed("Song", "Band"),
+            probed("Broken", "Other Band"),
+            (
+                root.clone(),
+                root.join("Unreadable.flac"),
+                Probed::Failed("Unreadable".to_string()),
+            ),
+            (root.clone(), root.join("Gone.flac"), Probed::Gone),
+        ];
+        assert_eq!(write_batch(&mut conn, batch), 3);
+
+        assert_eq!(names(&conn, "SELECT name FROM track"), ["Song"]);
+        // Everything the broken file wrote before it failed went with its savepoint
+        assert_eq!(names(&conn, "SELECT name FROM artists"), ["Band"]);
+        assert_eq!(
+            names(&conn, "SELECT path FROM scan_errors ORDER BY path"),
+            ["/music/Broken.flac", "/music/Unreadable.flac"]
+        );
+        assert_eq!(
+            names(
+                &conn,
+                "SELECT reason FROM scan_errors WHERE path = '/music/Unreadable.flac'"
+            ),
+            ["Unreadable"]
+        );
     }
 }
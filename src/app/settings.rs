@@ -1,8 +1,12 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use crate::app::{AppModel, Message};
-use crate::config::AppTheme;
+use crate::config::{
+    AppTheme, GenreMapping, ReplayGainMode, DEFAULT_ARTIST_SEPARATORS, DEFAULT_COVER_NAMES,
+    DEFAULT_FALLBACK_PATTERN, DEFAULT_FEATURING_SEPARATORS, DEFAULT_GENRE_SEPARATORS,
+};
 use crate::fl;
+use cosmic::iced::Alignment;
 use cosmic::widget::settings::Section;
 use cosmic::widget::text;
 use cosmic::{theme, widget, Element};
@@ -18,28 +22,174 @@ impl AppModel {
         let player_settings: Section<Message> = cosmic::widget::settings::section();
         let ui_settings: Section<Message> = cosmic::widget::settings::section();
 
+        let mut library_roots: Section<Message> =
+            cosmic::widget::settings::section().title(fl!("LibraryFolders"));
+
+        for (index, root) in self.config.library_roots.iter().enumerate() {
+            library_roots = library_roots.add(
+                widget::settings::item::builder(root.path.clone()).control(
+                    widget::row::Row::with_children([
+                        cosmic::widget::toggler(root.enabled)
+                            .on_toggle(move |val| Message::ToggleLibraryRoot(index, val))
+                            .into(),
+                        match self.rescan_available {
+                            true => widget::button::destructive(fl!("RemoveLibraryFolder"))
+                                .on_press(Message::RemoveLibraryRoot(index)),
+                            false => widget::button::destructive(fl!("RemoveLibraryFolder")),
+                        }
+                        .into(),
+                    ])
+                    .align_y(Alignment::Center)
+                    .spacing(space_s),
+                ),
+            );
+            library_roots = library_roots.add(
+                widget::settings::item::builder(fl!("ExcludePatterns"))
+                    .description(fl!("ExcludePatternsDescription"))
+                    .control(
+                        widget::text_input(
+                            fl!("ExcludePatternsPlaceholder"),
+                            root.exclude.join(","),
+                        )
+                        .on_input(move |val| Message::LibraryRootExclusions(index, val)),
+                    ),
+            );
+        }
+
+        library_roots = library_roots.add(
+            widget::settings::item::builder(fl!("AddLibraryFolder"))
+                .description(fl!("firsttimebody"))
+                .control(match self.rescan_available {
+                    true => widget::button::suggested(fl!("folderselect"))
+                        .on_press(Message::ChooseFolder),
+                    false => widget::button::suggested(fl!("folderselect")),
+                }),
+        );
+
+        let mut genre_mappings: Section<Message> =
+            cosmic::widget::settings::section().title(fl!("GenreMappings"));
+
+        for (index, mapping) in self.config.genre_mappings.iter().enumerate() {
+            genre_mappings = genre_mappings.add(
+                widget::row::Row::with_children([
+                    widget::text_input(fl!("GenreMappingGenre"), mapping.genre.as_str())
+                        .on_input(move |genre| {
+                            Message::GenreMappingEdit(
+                                index,
+                                GenreMapping {
+                                    genre,
+                                    ..mapping.clone()
+                                },
+                            )
+                        })
+                        .into(),
+                    widget::text_input(fl!("GenreMappingAliases"), mapping.aliases.join(","))
+                        .on_input(move |aliases| {
+                            Message::GenreMappingEdit(
+                                index,
+                                GenreMapping {
+                                    aliases: aliases.split(',').map(|a| a.to_string()).collect(),
+                                    ..mapping.clone()
+                                },
+                            )
+                        })
+                        .into(),
+                    widget::text_input(fl!("GenreMappingParent"), mapping.parent.as_str())
+                        .on_input(move |parent| {
+                            Message::GenreMappingEdit(
+                                index,
+                                GenreMapping {
+                                    parent,
+                                    ..mapping.clone()
+                                },
+                            )
+                        })
+                        .into(),
+                    widget::button::destructive(fl!("RemoveGenreMapping"))
+                        .on_press(Message::RemoveGenreMapping(index))
+                        .into(),
+                ])
+                .align_y(Alignment::Center)
+                .spacing(space_s),
+            );
+        }
+
+        genre_mappings = genre_mappings.add(
+            widget::settings::item::builder(fl!("AddGenreMapping"))
+                .description(fl!("AddGenreMappingDescription"))
+                .control(
+                    widget::button::standard(fl!("AddGenreMapping"))
+                        .on_press(Message::AddGenreMapping),
+                ),
+        );
+
         let contain = widget::Container::new(
             widget::column::Column::with_children([
                 cosmic::widget::toaster(&self.toasts, widget::horizontal_space()).into(),
+                library_roots.into(),
                 editable_settings
                     .title(fl!("MusicScanning"))
-                    .add(widget::Row::with_children([
-                        text::heading(fl!("MusicDirectory")).into(),
-                        widget::horizontal_space().into(),
-                        text::text(&self.config.scan_dir).into(),
-                    ]))
                     .add(
-                        widget::settings::item::builder(fl!("MusicDirectory"))
-                            .description(fl!("firsttimebody"))
-                            .control(match self.rescan_available {
-                                true => widget::button::suggested(fl!("folderselect"))
-                                    .on_press(Message::ChooseFolder),
-                                false => widget::button::suggested(fl!("folderselect")),
-                            }),
+                        widget::settings::item::builder(fl!("FallbackPattern"))
+                            .description(fl!(
+                                "FallbackPatternDescription",
+                                example = DEFAULT_FALLBACK_PATTERN
+                            ))
+                            .control(
+                                widget::text_input(
+                                    DEFAULT_FALLBACK_PATTERN,
+                                    self.config.fallback_pattern.as_str(),
+                                )
+                                .on_input(Message::FallbackPatternEdit),
+                            ),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!("ArtistSeparators"))
+                            .description(fl!("ArtistSeparatorsDescription"))
+                            .control(
+                                widget::text_input(
+                                    DEFAULT_ARTIST_SEPARATORS,
+                                    self.config.artist_separators.as_str(),
+                                )
+                                .on_input(Message::ArtistSeparatorsEdit),
+                            ),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!("FeaturingSeparators"))
+                            .description(fl!("FeaturingSeparatorsDescription"))
+                            .control(
+                                widget::text_input(
+                                    DEFAULT_FEATURING_SEPARATORS,
+                                    self.config.featuring_separators.as_str(),
+                                )
+                                .on_input(Message::FeaturingSeparatorsEdit),
+                            ),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!("GenreSeparators"))
+                            .description(fl!("GenreSeparatorsDescription"))
+                            .control(
+                                widget::text_input(
+                                    DEFAULT_GENRE_SEPARATORS,
+                                    self.config.genre_separators.as_str(),
+                                )
+                                .on_input(Message::GenreSeparatorsEdit),
+                            ),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!("CoverNames"))
+                            .description(fl!("CoverNamesDescription"))
+                            .control(
+                                widget::text_input(
+                                    DEFAULT_COVER_NAMES,
+                                    self.config.cover_names.as_str(),
+                                )
+                                .on_input(Message::CoverNamesEdit),
+                            ),
                     )
                     .add(
                         widget::settings::item::builder(fl!("FullRescan")).control(
-                            match self.rescan_available && !self.config.scan_dir.is_empty() {
+                            match self.rescan_available && !self.config.library_roots.is_empty() {
                                 true => widget::button::text(fl!("Rescan"))
                                     .class(widget::button::ButtonClass::Destructive)
                                     .on_press(Message::RescanDir),
@@ -70,9 +220,50 @@ impl AppModel {
                             .height(space_s)
                             .into(),
                         ])
+                        .push_maybe(self.scan_progress.as_ref().and_then(|progress| {
+                            let eta = progress.eta?.as_secs();
+                            Some(text::caption(fl!(
+                                "ScanThroughput",
+                                rate = format!("{:.0}", progress.rate?),
+                                eta = format!("{}:{:02}", eta / 60, eta % 60)
+                            )))
+                        }))
+                        .spacing(space_xxs),
+                    )
+                    .add(
+                        widget::column::Column::with_children([widget::settings::item::builder(
+                            fl!("LoudnessAnalysis"),
+                        )
+                        .description(fl!("LoudnessAnalysisDescription"))
+                        .control(match self.loudness_cancel {
+                            Some(_) => widget::button::standard(fl!("Cancel"))
+                                .on_press(Message::CancelLoudnessAnalysis),
+                            None => widget::button::standard(fl!("Analyze"))
+                                .on_press(Message::AnalyzeLoudness),
+                        })
+                        .into()])
+                        .push_maybe(self.loudness_progress.as_ref().map(|progress| {
+                            widget::column::Column::with_children([
+                                widget::row::Row::with_children([
+                                    text::heading(fl!("LoudnessProgress")).into(),
+                                    widget::horizontal_space().into(),
+                                    text::caption(format!("{}/{}", progress.done, progress.found))
+                                        .into(),
+                                ])
+                                .into(),
+                                widget::progress_bar(
+                                    0.0..=progress.found.max(1) as f32,
+                                    progress.done as f32,
+                                )
+                                .height(space_s)
+                                .into(),
+                            ])
+                            .spacing(space_xxs)
+                        }))
                         .spacing(space_xxs),
                     )
                     .into(),
+                genre_mappings.into(),
                 ui_settings
                     .title(fl!("UserInterface"))
                     .add(widget::settings::item::builder(fl! {"Theme"}).control(
@@ -119,9 +310,50 @@ impl AppModel {
                             |a| Message::VolumeSliderChange(a),
                         )),
                     )
+                    .add(
+                        widget::settings::item::builder(fl!("ReplayGain"))
+                            .description(fl!("ReplayGainDescription"))
+                            .control(cosmic::widget::dropdown(
+                                ReplayGainMode::ALL.map(replay_gain_label).to_vec(),
+                                ReplayGainMode::ALL
+                                    .iter()
+                                    .position(|mode| *mode == self.config.replay_gain),
+                                |index| Message::ReplayGainModeChange(ReplayGainMode::ALL[index]),
+                            )),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!(
+                            "ReplayGainPreamp",
+                            preamp = format!("{:+.1}", self.config.replay_gain_preamp)
+                        ))
+                        .control(
+                            cosmic::widget::slider(
+                                -15.0..=15.0,
+                                self.config.replay_gain_preamp,
+                                |a| Message::ReplayGainPreampChange(a),
+                            )
+                            .step(0.5),
+                        ),
+                    )
+                    .add(
+                        widget::settings::item::builder(fl!("PreventClipping"))
+                            .description(fl!("PreventClippingDescription"))
+                            .control(
+                                cosmic::widget::toggler(self.config.prevent_clipping)
+                                    .on_toggle(|val| Message::TogglePreventClipping(val)),
+                            ),
+                    )
                     .into(),
                 current_settings
                     .title(fl!("CurrentScanResults"))
+                    .add(
+                        widget::settings::item::builder(fl!("ScanProblems"))
+                            .description(fl!("ScanProblemsDescription"))
+                            .control(
+                                widget::button::standard(fl!("ShowScanProblems"))
+                                    .on_press(Message::ShowScanProblems),
+                            ),
+                    )
                     .add(widget::Row::with_children([
                         text::heading(fl!("FilesScanned")).into(),
                         widget::horizontal_space().into(),
@@ -154,4 +386,31 @@ impl AppModel {
         );
         contain.into()
     }
+
+    /// Files the scan problems drawer lists
+    pub fn scan_problems<'a>(&'a self) -> Element<'a, Message> {
+        if self.scan_problems.is_empty() {
+            return text::body(fl!("NoScanProblems")).into();
+        }
+
+        let mut problems: Section<Message> = cosmic::widget::settings::section();
+        for problem in &self.scan_problems {
+            problems = problems.add(
+                widget::settings::item::builder(problem.path.to_string_lossy().to_string())
+                    .description(problem.reason.clone())
+                    .control(widget::horizontal_space()),
+            );
+        }
+
+        problems.into()
+    }
+}
+
+fn replay_gain_label(mode: ReplayGainMode) -> String {
+    match mode {
+        ReplayGainMode::Off => fl!("ReplayGainOff"),
+        ReplayGainMode::Track => fl!("ReplayGainTrack"),
+        ReplayGainMode::Album => fl!("ReplayGainAlbum"),
+        ReplayGainMode::Auto => fl!("ReplayGainAuto"),
+    }
 }
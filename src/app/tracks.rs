@@ -2,11 +2,17 @@
 
 // SPDX-License-Identifier: GPL-2.0-or-later
 
-use crate::app::{AppTrack, Message};
-use crate::{app, fl};
+use crate::app::home::format_time;
+use crate::app::{AppModel, AppTrack, Message};
+use crate::query::Query;
+use crate::{app, fl, fuzzy};
 use cosmic::iced;
 use cosmic::iced::widget::scrollable::Viewport;
+use cosmic::iced::widget::text::Span;
+use cosmic::iced::widget::{rich_text, span};
 use cosmic::iced::{Alignment, Length};
+use std::collections::HashMap;
+use std::hash::Hash;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
@@ -34,6 +40,60 @@ pub struct SearchResult {
     pub score: u32,
 }
 
+/// Where each of `found` is in `items`, scored by the order they were found in. Unless they were
+/// `ranked` they keep the order of the page. Whatever the page does not show is left out.
+pub fn search_positions<T, K: Eq + Hash>(
+    items: &[T],
+    found: Vec<K>,
+    ranked: bool,
+    key: impl Fn(&T) -> K,
+) -> Vec<SearchResult> {
+    let positions = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (key(item), index))
+        .collect::<HashMap<K, usize>>();
+
+    let mut found = found
+        .into_iter()
+        .filter_map(|key| positions.get(&key).copied())
+        .collect::<Vec<usize>>();
+    if !ranked {
+        found.sort_unstable();
+    }
+
+    found
+        .into_iter()
+        .enumerate()
+        .map(|(score, tracks_index)| SearchResult {
+            tracks_index,
+            score: score as u32,
+        })
+        .collect()
+}
+
+/// Search field of a library page, with what is wrong with the query underneath it
+pub fn library_search_input<'a>(
+    placeholder: String,
+    model: &'a AppModel,
+) -> cosmic::Element<'a, Message> {
+    cosmic::widget::column::with_children(vec![cosmic::widget::search_input(
+        placeholder,
+        model.search_field.as_str(),
+    )
+    .on_input(|input| Message::UpdateSearch(input))
+    .into()])
+    .push_maybe(
+        model
+            .search_error
+            .as_deref()
+            .map(cosmic::widget::text::caption),
+    )
+    .spacing(cosmic::theme::spacing().space_xxxs)
+    .width(Length::FillPortion(1))
+    .into()
+}
+
 impl TrackPage {
     pub fn new() -> Self {
         TrackPage {
@@ -62,10 +122,7 @@ impl TrackPage {
                             cosmic::widget::horizontal_space()
                                 .width(Length::Shrink)
                                 .into(),
-                            cosmic::widget::search_input(fl!("TrackInputPlaceholder"), &model.search_field)
-                                .on_input(|a| Message::UpdateSearch(a))
-                                .width(Length::FillPortion(1))
-                                .into(),
+                            library_search_input(fl!("TrackInputPlaceholder"), model),
                         ])
                             .align_y(Alignment::Center)
                             .spacing(cosmic::theme::spacing().space_s)
@@ -95,7 +152,7 @@ impl TrackPage {
                                     .width(Length::Fill)
                                     .into(),
 
-                                false => track_list_display(&self.tracks),
+                                false => track_list_display(&self.tracks, &[]),
                             },
                             TrackPageState::Search => cosmic::widget::column::with_children(vec![
                                 cosmic::widget::container(
@@ -117,15 +174,7 @@ impl TrackPage {
                                     .padding(cosmic::theme::spacing().space_xxs)
                                     .class(cosmic::style::Container::Primary)
                                     .into(),
-                                search_list_display(
-                                    &self.search,
-                                    &self.tracks,
-                                    (
-                                        self.search_by_title,
-                                        self.search_by_album,
-                                        self.search_by_artist,
-                                    ),
-                                ),
+                                search_list_display(&self.search, &self.tracks, &model.search_field),
                             ])
                                 .spacing(cosmic::theme::spacing().space_m)
                                 .into(),
@@ -146,82 +195,67 @@ impl TrackPage {
     }
 }
 
+/// Tracks that were found, best match first, with what was searched for highlighted
 fn search_list_display<'a>(
-    search_result: &'a Vec<SearchResult>,
-    tracks: &'a Vec<AppTrack>,
-    settings: (bool, bool, bool),
+    search_result: &'a [SearchResult],
+    tracks: &'a [AppTrack],
+    search: &str,
 ) -> cosmic::Element<'a, Message> {
-    let mut title_vector: Vec<AppTrack> = vec![];
-    let mut album_vector: Vec<AppTrack> = vec![];
-    let mut artist_vector: Vec<AppTrack> = vec![];
-
-    for each in search_result {
-        if (0..=2).contains(&each.score) && settings.0 {
-            match tracks.get(each.tracks_index) {
-                None => {}
-                Some(val) => {
-                    title_vector.push(val.clone());
-                }
-            }
-        } else if (3..=5).contains(&each.score) && settings.1 {
-            match tracks.get(each.tracks_index) {
-                None => {}
-                Some(val) => {
-                    album_vector.push(val.clone());
-                }
-            }
-        } else if (6..=8).contains(&each.score) && settings.2 {
-            match tracks.get(each.tracks_index) {
-                None => {}
-                Some(val) => {
-                    artist_vector.push(val.clone());
-                }
-            }
-        }
-    }
+    let found = search_result
+        .iter()
+        .filter_map(|each| tracks.get(each.tracks_index).cloned())
+        .collect::<Vec<AppTrack>>();
+    let words = Query::parse(search)
+        .map(|query| query.words())
+        .unwrap_or_default();
 
-    let mut elem_vec: Vec<cosmic::Element<Message>> = Vec::with_capacity(3);
+    cosmic::widget::container(track_list_display(&found, &words))
+        .class(cosmic::theme::Container::Primary)
+        .padding(cosmic::theme::spacing().space_xxs)
+        .width(Length::Fill)
+        .into()
+}
 
-    if settings.0 {
-        elem_vec.push(search_group_display(&title_vector, fl!("title").as_str()));
+/// `text` with the parts matching `words` in bold
+fn highlighted_text<'a>(
+    text: &str,
+    words: &[String],
+    heading: bool,
+) -> cosmic::Element<'a, Message> {
+    let ranges = fuzzy::highlights(words, text);
+    if ranges.is_empty() {
+        return match heading {
+            true => cosmic::widget::text::heading(text.to_string()),
+            false => cosmic::widget::text::text(text.to_string()),
+        }
+        .width(Length::FillPortion(1))
+        .into();
     }
 
-    if settings.1 {
-        elem_vec.push(search_group_display(&album_vector, fl!("album").as_str()));
+    let font = match heading {
+        true => cosmic::font::semibold(),
+        false => cosmic::font::default(),
+    };
+    let mut spans: Vec<Span<'a>> = vec![];
+    let mut end = 0;
+    for range in ranges {
+        if range.start > end {
+            spans.push(span(text[end..range.start].to_string()).font(font));
+        }
+        spans.push(span(text[range.clone()].to_string()).font(cosmic::font::bold()));
+        end = range.end;
     }
-
-    if settings.2 {
-        elem_vec.push(search_group_display(&artist_vector, fl!("artist").as_str()));
+    if end < text.len() {
+        spans.push(span(text[end..].to_string()).font(font));
     }
 
-    cosmic::widget::column::with_children(elem_vec)
-        .spacing(cosmic::theme::spacing().space_s)
-        .width(Length::Fill)
-        .into()
+    rich_text(spans).width(Length::FillPortion(1)).into()
 }
 
-fn search_group_display<'a>(
+fn track_list_display<'a>(
     tracks: &Vec<AppTrack>,
-    search_title: &str,
-) -> cosmic::Element<'a, Message> {
-    cosmic::widget::container(cosmic::widget::column::with_children(vec![
-        cosmic::widget::row::with_children(vec![cosmic::widget::text::heading(fl!(
-            "SearchFilterSpecify",
-            filter = search_title
-        ))
-        .into()])
-        .padding(cosmic::theme::spacing().space_xxs)
-        .width(Length::Fill)
-        .into(),
-        cosmic::widget::divider::horizontal::light().into(),
-        track_list_display(&tracks),
-    ]))
-    .class(cosmic::theme::Container::Primary)
-    .padding(cosmic::theme::spacing().space_xxs)
-    .into()
-}
-
-fn track_list_display<'a>(tracks: &Vec<AppTrack>) -> cosmic::Element<'a, app::Message> {
+    words: &[String],
+) -> cosmic::Element<'a, app::Message> {
     let mut list_widget = Some(cosmic::widget::ListColumn::new());
 
     for track in tracks {
@@ -233,15 +267,20 @@ fn track_list_display<'a>(tracks: &Vec<AppTrack>) -> cosmic::Element<'a, app::Me
                     // ----CONTENT---- //
                     prev_list.add(cosmic::widget::container::Container::new(
                         cosmic::widget::row::with_children(vec![
-                            cosmic::widget::text::heading(format!("{}", track.title,))
-                                .width(Length::FillPortion(1))
-                                .into(),
-                            cosmic::widget::text::text(format!("{}", track.artist,))
-                                .width(Length::FillPortion(1))
-                                .into(),
-                            cosmic::widget::text::text(format!("{}", track.album_title,))
-                                .width(Length::FillPortion(1))
-                                .into(),
+                            highlighted_text(&track.title, words, true),
+                            highlighted_text(&track.artist, words, false),
+                            highlighted_text(&track.album_title, words, false),
+                            cosmic::widget::text::text(
+                                track.duration.map(format_time).unwrap_or_default(),
+                            )
+                            .into(),
+                            cosmic::widget::button::icon(cosmic::widget::icon::from_name(
+                                "document-properties-symbolic",
+                            ))
+                            .on_press(Message::ShowTrackProperties(
+                                track.path_buf.to_string_lossy().to_string(),
+                            ))
+                            .into(),
                             cosmic::widget::button::icon(cosmic::widget::icon::from_name(
                                 "media-playback-start-symbolic",
                             ))
@@ -261,3 +300,71 @@ fn track_list_display<'a>(tracks: &Vec<AppTrack>) -> cosmic::Element<'a, app::Me
 
     list_widget.unwrap().into_element()
 }
+
+impl AppModel {
+    /// Technical details of the track the properties drawer was opened for
+    pub fn track_properties(&self) -> cosmic::Element<'_, Message> {
+        let Some(track) = &self.track_properties else {
+            return cosmic::widget::text::body(fl!("Loading")).into();
+        };
+        let properties = &track.properties;
+
+        let rows = [
+            (fl!("title"), Some(track.title.clone())),
+            (fl!("artist"), Some(track.artist.clone())),
+            (fl!("album"), Some(track.album_title.clone())),
+            (fl!("Duration"), properties.duration.map(format_time)),
+            (fl!("Codec"), properties.codec.clone()),
+            (
+                fl!("SampleRate"),
+                properties
+                    .sample_rate
+                    .map(|rate| fl!("SampleRateValue", rate = rate as f64 / 1000.0)),
+            ),
+            (
+                fl!("BitDepth"),
+                properties
+                    .bit_depth
+                    .map(|depth| fl!("BitDepthValue", depth = depth)),
+            ),
+            (
+                fl!("Channels"),
+                properties
+                    .channels
+                    .map(|count| fl!("ChannelsValue", count = count)),
+            ),
+            (
+                fl!("Bitrate"),
+                properties
+                    .bitrate
+                    .map(|rate| fl!("BitrateValue", rate = rate)),
+            ),
+            (
+                fl!("FileSize"),
+                Some(fl!(
+                    "FileSizeValue",
+                    size = format!("{:.1}", track.size as f64 / 1_000_000.0)
+                )),
+            ),
+            (
+                fl!("FilePath"),
+                Some(track.path.to_string_lossy().to_string()),
+            ),
+        ];
+
+        let mut section = cosmic::widget::settings::section();
+        for (label, value) in rows {
+            section = section.add(
+                cosmic::widget::settings::item::builder(label)
+                    .description(
+                        value
+                            .filter(|value| !value.is_empty())
+                            .unwrap_or_else(|| fl!("Unknown")),
+                    )
+                    .control(cosmic::widget::horizontal_space()),
+            );
+        }
+
+        section.into()
+    }
+}
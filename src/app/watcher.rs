@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::app::Message;
+use futures_util::{SinkExt, StreamExt};
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a file has to stay untouched before it is probed, rips are written in many steps
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub enum LibraryChange {
+    /// A file or directory was created, written to or moved in
+    Updated(PathBuf),
+    /// A file or directory was deleted or moved out
+    Removed(PathBuf),
+}
+
+/// Watches a library root recursively and sends batches of changes once they settle
+pub fn watch_library(root: PathBuf) -> cosmic::iced::Subscription<Message> {
+    struct LibraryWatcher;
+
+    cosmic::iced::Subscription::run_with_id(
+        (TypeId::of::<LibraryWatcher>(), root.clone()),
+        cosmic::iced_futures::stream::channel(10, |mut tx| async move {
+            let (events_tx, mut events) = futures::channel::mpsc::unbounded();
+
+            let watcher = notify::recommended_watcher(move |event| {
+                let _ = events_tx.unbounded_send(event);
+            })
+            .and_then(|mut watcher| {
+                watcher.watch(&root, RecursiveMode::Recursive)?;
+                Ok(watcher)
+            });
+
+            // Dropping the watcher stops it, it has to live as long as the subscription
+            let _watcher = match watcher {
+                Ok(watcher) => watcher,
+                Err(err) => {
+                    log::error!("Could not watch {}: {}", root.display(), err);
+                    futures::future::pending::<()>().await;
+                    return;
+                }
+            };
+
+            let mut changes: HashMap<PathBuf, LibraryChange> = HashMap::new();
+
+            loop {
+                let event = if changes.is_empty() {
+                    events.next().await
+                } else {
+                    match tokio::time::timeout(SETTLE_TIME, events.next()).await {
+                        Ok(event) => event,
+                        Err(_) => {
+                            let batch = changes.drain().map(|(_, change)| change).collect();
+                            // The app is shutting down once nobody listens anymore
+                            if tx
+                                .send(Message::LibraryChanged(root.clone(), batch))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                            continue;
+                        }
+                    }
+                };
+
+                match event {
+                    Some(Ok(event)) => record_event(event, &mut changes),
+                    Some(Err(err)) => log::warn!("Library watcher error: {}", err),
+                    None => return,
+                }
+            }
+        }),
+    )
+}
+
+/// Later events for the same path replace earlier ones, a file deleted and written again is
+/// only an update
+fn record_event(event: Event, changes: &mut HashMap<PathBuf, LibraryChange>) {
+    match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Data(_))
+        | EventKind::Modify(ModifyKind::Any)
+        | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+        | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+            for path in event.paths {
+                changes.insert(path.clone(), LibraryChange::Updated(path));
+            }
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            for path in event.paths {
+                changes.insert(path.clone(), LibraryChange::Removed(path));
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            if let Some(from) = paths.next() {
+                changes.insert(from.clone(), LibraryChange::Removed(from));
+            }
+            paths.for_each(|to| {
+                changes.insert(to.clone(), LibraryChange::Updated(to));
+            });
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            for path in event.paths {
+                if path.exists() {
+                    changes.insert(path.clone(), LibraryChange::Updated(path));
+                } else {
+                    changes.insert(path.clone(), LibraryChange::Removed(path));
+                }
+            }
+        }
+        _ => {}
+    }
+}
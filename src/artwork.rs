@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::app::AppModel;
+use cosmic::Application;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Edge lengths covers are stored at in pixels. Grid items are 32 to 192 pixels wide depending
+/// on `grid_item_size`, the largest size is also what media controls are given.
+pub const SIZES: [u32; 4] = [64, 128, 256, 512];
+
+pub const LARGEST: u32 = SIZES[SIZES.len() - 1];
+
+const JPEG_QUALITY: u8 = 90;
+
+/// Files this new are not pruned, a cover can be stored for media controls while a scan ends
+const PRUNE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Covers are kept once per image, named by the SHA-256 of what was embedded or found in the
+/// album folder, so every track of an album shares one set of files. Covers are not kept when
+/// there is no data directory.
+fn artwork_dir() -> Option<PathBuf> {
+    Some(
+        dirs::data_local_dir()?
+            .join(AppModel::APP_ID)
+            .join("artwork"),
+    )
+}
+
+/// Stored copy of the cover `hash` that is at least `size` pixels wide, or the largest one
+pub fn path(hash: &str, size: u32) -> Option<PathBuf> {
+    let size = SIZES
+        .into_iter()
+        .find(|stored| *stored >= size)
+        .unwrap_or(LARGEST);
+    Some(artwork_dir()?.join(format!("{}-{}.jpg", hash, size)))
+}
+
+/// Cover `hash` for something `size` pixels wide, sharp on displays scaled up to twice.
+/// The file is only read once the image is drawn.
+pub fn handle(hash: &str, size: u32) -> cosmic::widget::image::Handle {
+    match path(hash, size * 2) {
+        Some(path) => cosmic::widget::image::Handle::from_path(path),
+        None => cosmic::widget::image::Handle::from_bytes(Vec::new()),
+    }
+}
+
+/// Adds a cover to the store and returns its hash, `None` when it is not an image that can be
+/// read. A cover that is already stored is not decoded again.
+pub fn store(image: &[u8]) -> Option<String> {
+    let Some(dir) = artwork_dir() else {
+        log::warn!("No data directory to store covers in");
+        return None;
+    };
+
+    store_in(&dir, image)
+}
+
+fn store_in(dir: &Path, image: &[u8]) -> Option<String> {
+    let hash = format!("{:x}", Sha256::digest(image));
+    let path = |size: u32| dir.join(format!("{}-{}.jpg", hash, size));
+    if SIZES.iter().all(|size| path(*size).exists()) {
+        return Some(hash);
+    }
+
+    let decoded = match image::load_from_memory(image) {
+        Ok(decoded) => decoded,
+        Err(err) => {
+            log::warn!("Could not read cover {}: {}", hash, err);
+            return None;
+        }
+    };
+
+    if let Err(err) = fs::create_dir_all(dir) {
+        log::error!("Could not create the artwork store: {}", err);
+        return None;
+    }
+
+    for size in SIZES {
+        // Small covers are not scaled up, every size is the same image then
+        let scaled = match decoded.width().max(decoded.height()) > size {
+            true => decoded.resize(size, size, FilterType::Lanczos3),
+            false => decoded.clone(),
+        };
+
+        if let Err(err) = write_jpeg(&path(size), &scaled) {
+            log::error!("Could not store cover {} at {}px: {}", hash, size, err);
+            return None;
+        }
+    }
+
+    Some(hash)
+}
+
+/// Tracks of an album store the same cover at the same time, each writes a file of its own and
+/// moves it in place when it is done
+fn write_jpeg(path: &Path, image: &DynamicImage) -> image::ImageResult<()> {
+    let partial = path.with_extension(format!("{:x}.part", rand::random::<u32>()));
+
+    let result = encode_jpeg(&partial, image).and_then(|_| Ok(fs::rename(&partial, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn encode_jpeg(path: &Path, image: &DynamicImage) -> image::ImageResult<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    image
+        .to_rgb8()
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY))?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Deletes every stored file that is not a copy of a cover in `in_use`. Files that are still
+/// being written, or were just now, belong to someone else storing a cover.
+pub fn prune(in_use: &HashSet<String>) {
+    if let Some(dir) = artwork_dir() {
+        prune_dir(&dir, in_use);
+    }
+}
+
+fn prune_dir(dir: &Path, in_use: &HashSet<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let used = name.ends_with(".jpg")
+            && name
+                .split_once('-')
+                .is_some_and(|(hash, _)| in_use.contains(hash));
+        let recent = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default() < PRUNE_AFTER)
+            .unwrap_or(true);
+
+        if !used && !recent {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                log::warn!("Could not remove unused cover {}: {}", name, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+    use std::time::SystemTime;
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("nova_artwork_test_{}_{}", name, std::process::id()))
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 40, 40])))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn covers_are_stored_once_at_every_size() {
+        let dir = test_dir("store");
+        let stored = |hash: &str, size: u32| {
+            let image = image::open(dir.join(format!("{}-{}.jpg", hash, size))).unwrap();
+            (image.width(), image.height())
+        };
+
+        let hash = store_in(&dir, &png(600, 300)).unwrap();
+        for size in SIZES {
+            assert_eq!(stored(&hash, size), (size, size / 2));
+        }
+        assert_eq!(store_in(&dir, &png(600, 300)), Some(hash));
+
+        // Small covers are not scaled up
+        let small = store_in(&dir, &png(40, 40)).unwrap();
+        assert_eq!(stored(&small, LARGEST), (40, 40));
+
+        assert_eq!(store_in(&dir, b"not an image"), None);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2 * SIZES.len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_covers_in_use_and_recent_files() {
+        let dir = test_dir("prune");
+        fs::create_dir_all(&dir).unwrap();
+        let old = SystemTime::now() - PRUNE_AFTER * 2;
+        for name in [
+            "used-64.jpg",
+            "unused-64.jpg",
+            "unused-128.jpg",
+            "unused-64.1f.part",
+            "new-64.jpg",
+            "new-64.2e.part",
+        ] {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            if !name.starts_with("new") {
+                file.set_modified(old).unwrap();
+            }
+        }
+
+        prune_dir(&dir, &HashSet::from(["used".to_string()]));
+
+        let mut left = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        left.sort();
+        assert_eq!(left, ["new-64.2e.part", "new-64.jpg", "used-64.jpg"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
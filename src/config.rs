@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use crate::app;
+use crate::database::{ArtistSeparators, GenreOptions, PathPattern, TagOptions};
 use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
 use cosmic::Application;
 use serde::{Deserialize, Serialize};
@@ -22,17 +23,134 @@ impl AppTheme {
     }
 }
 
+/// Order of the album grid
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AlbumSort {
+    Added,
+    Title,
+    Artist,
+    /// Oldest first, split into decades
+    Year,
+}
+
+impl AlbumSort {
+    pub const ALL: [AlbumSort; 4] = [
+        AlbumSort::Added,
+        AlbumSort::Title,
+        AlbumSort::Artist,
+        AlbumSort::Year,
+    ];
+}
+
+/// Which ReplayGain tags playback is corrected by
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReplayGainMode {
+    Off,
+    Track,
+    Album,
+    /// Album gain while an album plays in order, track gain otherwise
+    Auto,
+}
+
+impl ReplayGainMode {
+    pub const ALL: [ReplayGainMode; 4] = [
+        ReplayGainMode::Off,
+        ReplayGainMode::Track,
+        ReplayGainMode::Album,
+        ReplayGainMode::Auto,
+    ];
+}
+
+pub const DEFAULT_FALLBACK_PATTERN: &str = "{albumartist}/{album}/{track} - {title}";
+/// `&` is not among them, it is part of names like "Simon & Garfunkel" as often as it joins two
+pub const DEFAULT_ARTIST_SEPARATORS: &str = ";";
+pub const DEFAULT_FEATURING_SEPARATORS: &str = "feat. ft. featuring";
+pub const DEFAULT_GENRE_SEPARATORS: &str = "; /";
+pub const DEFAULT_COVER_NAMES: &str = "cover folder front album albumart";
+
+/// A folder the library is scanned from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LibraryRoot {
+    pub path: String,
+    /// Disabled roots are neither scanned nor watched, their tracks stay as last scanned
+    pub enabled: bool,
+    /// Gitignore style patterns, relative to the root
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl LibraryRoot {
+    pub fn new(path: String) -> Self {
+        LibraryRoot {
+            path,
+            enabled: true,
+            exclude: vec![],
+        }
+    }
+}
+
+/// Genre tags in `aliases` are read as `genre`, which is listed under `parent` when it is set
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenreMapping {
+    pub genre: String,
+    /// Other spellings of the genre, like `Hip Hop` for `Hip-Hop`
+    pub aliases: Vec<String>,
+    /// Broader genre, browsing it includes this one
+    pub parent: String,
+}
+
+/// Settings tags are read with, stored as the scanned ones once a scan read every file with them
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagSettings {
+    pub fallback_pattern: String,
+    pub artist_separators: String,
+    pub featuring_separators: String,
+    pub genre_separators: String,
+    pub genre_mappings: Vec<GenreMapping>,
+    pub cover_names: String,
+}
+
 #[derive(Debug, Clone, CosmicConfigEntry, PartialEq)]
 #[version = 1]
 pub struct Config {
+    /// Single scan directory of older versions, moved into `library_roots` on load
     pub scan_dir: String,
+    pub library_roots: Vec<LibraryRoot>,
+    /// Where untagged files get their tags from, relative to their library root
+    pub fallback_pattern: String,
+    /// Pattern the library was last scanned with, every file is probed again when it changes
+    pub scanned_fallback_pattern: String,
+    /// Space separated, each of them splits artist tags into several artists
+    pub artist_separators: String,
+    /// Space separated, like `artist_separators` but the artists after them are featured
+    pub featuring_separators: String,
+    /// Separators the library was last scanned with, as with `scanned_fallback_pattern`
+    pub scanned_artist_separators: String,
+    pub scanned_featuring_separators: String,
+    /// Space separated, each of them splits genre tags into several genres
+    pub genre_separators: String,
+    pub genre_mappings: Vec<GenreMapping>,
+    /// As with `scanned_fallback_pattern`
+    pub scanned_genre_separators: String,
+    pub scanned_genre_mappings: Vec<GenreMapping>,
+    /// Space separated names of images next to the files, in the order they are preferred as the
+    /// cover of albums without embedded art
+    pub cover_names: String,
+    /// Names the library was last scanned with, as with `scanned_fallback_pattern`
+    pub scanned_cover_names: String,
     pub app_theme: AppTheme,
+    pub album_sort: AlbumSort,
     pub grid_item_size: u32,
     pub num_files_found: u32,
     pub files_scanned: u32,
     pub tracks_found: u32,
     pub albums_found: u32,
     pub volume: f32,
+    pub replay_gain: ReplayGainMode,
+    /// In dB, added to the gain of every tagged track
+    pub replay_gain_preamp: f32,
+    /// Lowers the gain of tracks that would clip at their tagged peak
+    pub prevent_clipping: bool,
     pub footer: bool,
 }
 
@@ -40,10 +158,21 @@ impl Config {
     pub fn load() -> (Option<cosmic_config::Config>, Config) {
         match cosmic_config::Config::new(app::AppModel::APP_ID, 1) {
             Ok(config_handler) => {
-                let config = Config::get_entry(&config_handler).unwrap_or_else(|(errs, conf)| {
-                    log::error!("Config failed to get entry: {:?}", errs);
-                    conf
-                });
+                let mut config =
+                    Config::get_entry(&config_handler).unwrap_or_else(|(errs, conf)| {
+                        log::error!("Config failed to get entry: {:?}", errs);
+                        conf
+                    });
+
+                if config.library_roots.is_empty() && !config.scan_dir.is_empty() {
+                    let roots = vec![LibraryRoot::new(config.scan_dir.clone())];
+                    if let Err(err) = config.set_library_roots(&config_handler, roots) {
+                        log::error!("Failed to migrate scan directory: {}", err);
+                    } else {
+                        let _ = config.set_scan_dir(&config_handler, String::new());
+                    }
+                }
+
                 (Some(config_handler), config)
             }
             Err(e) => {
@@ -52,18 +181,113 @@ impl Config {
             }
         }
     }
+
+    /// What a scan started now reads tags with
+    pub fn tag_options(&self) -> TagOptions {
+        TagOptions {
+            fallback: PathPattern::new(&self.fallback_pattern),
+            separators: ArtistSeparators::new(
+                &split_separators(&self.artist_separators),
+                &split_separators(&self.featuring_separators),
+            ),
+            genres: GenreOptions::new(
+                &split_separators(&self.genre_separators),
+                &self
+                    .genre_mappings
+                    .iter()
+                    .flat_map(|mapping| {
+                        mapping
+                            .aliases
+                            .iter()
+                            .map(|alias| (alias.clone(), mapping.genre.clone()))
+                    })
+                    .collect::<Vec<(String, String)>>(),
+                &self
+                    .genre_mappings
+                    .iter()
+                    .map(|mapping| (mapping.genre.clone(), mapping.parent.clone()))
+                    .collect::<Vec<(String, String)>>(),
+            ),
+            cover_names: split_separators(&self.cover_names),
+        }
+    }
+
+    /// What `tag_options` are made of, to be stored once a scan is done with them
+    pub fn tag_settings(&self) -> TagSettings {
+        TagSettings {
+            fallback_pattern: self.fallback_pattern.clone(),
+            artist_separators: self.artist_separators.clone(),
+            featuring_separators: self.featuring_separators.clone(),
+            genre_separators: self.genre_separators.clone(),
+            genre_mappings: self.genre_mappings.clone(),
+            cover_names: self.cover_names.clone(),
+        }
+    }
+
+    /// Remembers what the library was scanned with, so unchanged files are not read again
+    pub fn set_scanned_tag_settings(
+        &mut self,
+        config: &cosmic_config::Config,
+        settings: TagSettings,
+    ) -> Result<(), cosmic_config::Error> {
+        self.set_scanned_fallback_pattern(config, settings.fallback_pattern)?;
+        self.set_scanned_artist_separators(config, settings.artist_separators)?;
+        self.set_scanned_featuring_separators(config, settings.featuring_separators)?;
+        self.set_scanned_genre_separators(config, settings.genre_separators)?;
+        self.set_scanned_genre_mappings(config, settings.genre_mappings)?;
+        self.set_scanned_cover_names(config, settings.cover_names)?;
+        Ok(())
+    }
+
+    /// Files that did not change still have to be read again when the options they were
+    /// read with changed
+    pub fn tag_options_changed(&self) -> bool {
+        self.fallback_pattern != self.scanned_fallback_pattern
+            || split_separators(&self.artist_separators)
+                != split_separators(&self.scanned_artist_separators)
+            || split_separators(&self.featuring_separators)
+                != split_separators(&self.scanned_featuring_separators)
+            || split_separators(&self.genre_separators)
+                != split_separators(&self.scanned_genre_separators)
+            || self.genre_mappings != self.scanned_genre_mappings
+            || split_separators(&self.cover_names) != split_separators(&self.scanned_cover_names)
+    }
+}
+
+fn split_separators(separators: &str) -> Vec<String> {
+    separators.split_whitespace().map(String::from).collect()
 }
 impl Default for Config {
     fn default() -> Self {
         Config {
             app_theme: AppTheme::System,
             scan_dir: "".to_string(),
+            library_roots: vec![],
+            fallback_pattern: DEFAULT_FALLBACK_PATTERN.to_string(),
+            // Libraries scanned before the fallback existed are probed again once
+            scanned_fallback_pattern: String::new(),
+            artist_separators: DEFAULT_ARTIST_SEPARATORS.to_string(),
+            featuring_separators: DEFAULT_FEATURING_SEPARATORS.to_string(),
+            scanned_artist_separators: String::new(),
+            scanned_featuring_separators: String::new(),
+            genre_separators: DEFAULT_GENRE_SEPARATORS.to_string(),
+            genre_mappings: vec![],
+            // Genres were stored as tagged before, so every file is probed again once
+            scanned_genre_separators: String::new(),
+            scanned_genre_mappings: vec![],
+            cover_names: DEFAULT_COVER_NAMES.to_string(),
+            // Folder covers were never looked for before, so every file is probed again once
+            scanned_cover_names: String::new(),
+            album_sort: AlbumSort::Added,
             grid_item_size: 3,
             num_files_found: 0,
             files_scanned: 0,
             tracks_found: 0,
             albums_found: 0,
             volume: 100.0,
+            replay_gain: ReplayGainMode::Auto,
+            replay_gain_preamp: 0.0,
+            prevent_clipping: true,
             footer: true,
         }
     }
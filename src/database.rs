@@ -1,18 +1,21 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use crate::{artwork, fl};
 use colored::Colorize;
 use cosmic::Application;
 use regex::{Match, Regex};
 use rusqlite::fallible_iterator::FallibleIterator;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 use cosmic::dialog::file_chooser::open::file;
-use rusqlite::Connection;
-use symphonia::core::meta::{StandardTagKey, Tag, Value};
-use symphonia::default::get_probe;
+use rusqlite::{Connection, OptionalExtension};
+use symphonia::core::codecs::CodecParameters;
+use symphonia::core::meta::{StandardTagKey, StandardVisualKey, Tag, Value, Visual};
+use symphonia::default::{get_codecs, get_probe};
 
 struct Artist {
-    id: u32,
     name: Option<String>,
 }
 
@@ -22,12 +25,121 @@ struct Album {
     artist_id: Option<u32>,
     num_of_discs: u32,
     num_of_tracks: u64,
+    compilation: bool,
+    /// Normalized as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, see [`parse_date`]
+    release_date: Option<String>,
+    /// First release of a reissue or remaster, albums are dated by it when known
+    original_date: Option<String>,
 }
 
+/// Album artist compilations are grouped under, whatever their tags say
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
 struct Track {
     id: u64,
     genres: Option<Vec<String>>,
     name: Option<String>,
+    /// Classical works, the movements of one share it
+    work: Option<String>,
+    opus: Option<String>,
+    movement_name: Option<String>,
+    movement_number: Option<u32>,
+    part: Option<String>,
+    replay_gain: ReplayGain,
+    /// Plain or LRC text, from the tags or a sidecar `.lrc` file
+    lyrics: Option<String>,
+}
+
+/// Loudness correction written by a ReplayGain scanner, gains are in dB relative to the
+/// ReplayGain reference and peaks are linear, 1.0 being full scale
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGain {
+    /// Factor the samples are scaled by, one of the gains falls back to the other. Tracks
+    /// without any gain are played as they are, the preamp only raises or lowers tagged ones.
+    pub fn factor(&self, album: bool, preamp: f64, prevent_clipping: bool) -> f32 {
+        let (gain, peak) = match album {
+            true => (
+                self.album_gain.or(self.track_gain),
+                self.album_peak.or(self.track_peak),
+            ),
+            false => (
+                self.track_gain.or(self.album_gain),
+                self.track_peak.or(self.album_peak),
+            ),
+        };
+
+        let Some(gain) = gain else {
+            return 1.0;
+        };
+        let mut factor = 10f64.powf((gain + preamp) / 20.0);
+
+        if prevent_clipping {
+            if let Some(peak) = peak.filter(|peak| *peak > 0.0) {
+                factor = factor.min(1.0 / peak);
+            }
+        }
+
+        factor as f32
+    }
+
+    /// Fills in the gains the tags left out from the measured loudness, for tracks that were
+    /// never run through a ReplayGain scanner
+    pub fn with_analysis(mut self, track: Option<Loudness>, album: Option<Loudness>) -> Self {
+        if self.track_gain.is_none() {
+            if let Some(track) = track {
+                self.track_gain = Some(track.gain());
+                self.track_peak = Some(track.true_peak);
+            }
+        }
+        if self.album_gain.is_none() {
+            if let Some(album) = album {
+                self.album_gain = Some(album.gain());
+                self.album_peak = Some(album.true_peak);
+            }
+        }
+
+        self
+    }
+}
+
+/// Measured by the loudness analysis, the integrated loudness is in LUFS and the true peak is
+/// linear like a ReplayGain peak
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    pub integrated: f64,
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Loudness ReplayGain 2.0 brings every track to
+    const REFERENCE: f64 = -18.0;
+
+    pub fn gain(&self) -> f64 {
+        Self::REFERENCE - self.integrated
+    }
+
+    /// Both columns of a row, `None` when the track or album has not been measured or is silent
+    pub fn from_columns(integrated: Option<f64>, true_peak: Option<f64>) -> Option<Loudness> {
+        Some(Loudness {
+            integrated: integrated?,
+            true_peak: true_peak?,
+        })
+    }
+}
+
+/// Tracks the loudness analysis has yet to measure. The album's other tracks are measured along
+/// with them, as the album loudness is measured over all of its tracks at once.
+pub struct AnalysisAlbum {
+    /// `None` for a track that is not on any album
+    pub album_id: Option<u64>,
+    pub tracks: Vec<(u64, PathBuf)>,
 }
 
 struct AlbumTracks {
@@ -35,65 +147,145 @@ struct AlbumTracks {
     disc_number: u64,
 }
 
-pub fn create_database() {
-    let conn = rusqlite::Connection::open(
-        dirs::data_local_dir()
+/// How long a connection waits for another one to finish writing before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens the library database with foreign keys enforced, every connection has to do this
+/// itself as sqlite does not store the setting in the database
+pub fn open_connection() -> rusqlite::Result<Connection> {
+    open_database(
+        &dirs::data_local_dir()
             .unwrap()
             .join(crate::app::AppModel::APP_ID)
             .join("nova_music.db"),
     )
-    .unwrap();
+}
 
-    conn.execute_batch(
-        "
-        DROP TABLE IF EXISTS temp_album;
-        DROP TABLE IF EXISTS album;
-        DROP TABLE IF EXISTS album_tracks;
-        DROP TABLE IF EXISTS track;
-        DROP TABLE IF EXISTS genres;
-        DROP TABLE IF EXISTS track_genres;
-        DROP TABLE IF EXISTS single
-    ",
-    )
-    .unwrap();
+/// The scan writer, the watcher and the UI all use the database at once.
+/// In WAL mode readers do not block the writer or each other, and a writer waits for another
+/// one instead of failing with SQLITE_BUSY.
+fn open_database(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // Answers with the mode it ended up in, which stays the default on file systems without
+    // shared memory
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(conn)
+}
 
+/// Schema changes in the order they were made. Each one runs once, in its own transaction,
+/// and the database remembers the last one it got in `schema_version`.
+/// Append new migrations to the end, never edit or reorder released ones.
+const MIGRATIONS: &[fn(&Connection) -> rusqlite::Result<()>] = &[
+    migration_initial_schema,
+    migration_foreign_keys,
+    migration_indexes,
+    migration_scan_errors,
+    migration_compilations,
+    migration_track_artists,
+    migration_classical,
+    migration_release_dates,
+    migration_audio_properties,
+    migration_replay_gain,
+    migration_loudness,
+    migration_lyrics,
+    migration_artwork,
+    migration_genres,
+    migration_search,
+    migration_search_vocab,
+    migration_library_roots,
+    migration_album_artists,
+    migration_search_renames,
+];
+
+/// Brings the database up to the latest schema, called once at startup
+pub fn migrate_database(conn: &Connection) -> rusqlite::Result<()> {
     conn.execute(
+        "CREATE TABLE if not exists schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+
+    let current: usize = conn
+        .query_row("SELECT max(version) FROM schema_version", [], |row| {
+            row.get::<usize, Option<usize>>(0)
+        })?
+        .unwrap_or(0);
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        log::info!("Migrating database to schema version {}", version);
+
+        // Tables are rebuilt by some migrations, which foreign keys would cascade into.
+        // The pragma can not change inside a transaction, so it is toggled around it.
+        conn.pragma_update(None, "foreign_keys", false)?;
+        conn.execute_batch("BEGIN")?;
+
+        let result = migration(conn).and_then(|_| {
+            conn.execute("DELETE FROM schema_version", [])?;
+            conn.execute("INSERT INTO schema_version (version) VALUES (?)", [version])?;
+            conn.execute_batch("COMMIT")
+        });
+
+        if let Err(err) = result {
+            log::error!("Migration to schema version {} failed: {}", version, err);
+            let _ = conn.execute_batch("ROLLBACK");
+            conn.pragma_update(None, "foreign_keys", true)?;
+            return Err(err);
+        }
+
+        conn.pragma_update(None, "foreign_keys", true)?;
+    }
+
+    // Dropped columns keep their pages until the file is rebuilt, which can not happen in a
+    // transaction
+    if current < MIGRATIONS.len() {
+        conn.execute_batch("VACUUM")?;
+    }
+
+    Ok(())
+}
+
+/// The tables as they were before the schema was versioned
+fn migration_initial_schema(conn: &Connection) -> rusqlite::Result<()> {
+    // Databases from before incremental scanning have no file signatures to compare against,
+    // so their scan tables are rebuilt once. Artists are kept to preserve their pictures.
+    if has_column(conn, "track", "id") && !has_column(conn, "track", "mtime") {
+        log::warn!("Track table has no file signatures, rebuilding scan tables");
+        conn.execute_batch(
+            "
+            DROP TABLE IF EXISTS temp_album;
+            DROP TABLE IF EXISTS album;
+            DROP TABLE IF EXISTS album_tracks;
+            DROP TABLE IF EXISTS track;
+            DROP TABLE IF EXISTS genres;
+            DROP TABLE IF EXISTS track_genres;
+            DROP TABLE IF EXISTS single
+        ",
+        )?;
+    }
+
+    conn.execute_batch(
         "
     CREATE TABLE if not exists artists (
         id INTEGER PRIMARY KEY,
         name TEXT UNIQUE,
         artistpfp BLOB
-    )
-    ",
-        [],
-    )
-    .unwrap();
+    );
 
-    conn.execute(
-        "
-    CREATE TABLE genres (
+    CREATE TABLE if not exists genres (
         id INTEGER PRIMARY KEY,
         name TEXT UNIQUE
-    )",
-        [],
-    )
-    .unwrap();
+    );
 
-    conn.execute(
-        "
-    CREATE TABLE track_genres(
+    CREATE TABLE if not exists track_genres(
         id INTEGER PRIMARY KEY,
         track_id INTEGER,
         genre_id INTEGER,
         FOREIGN KEY(genre_id) REFERENCES genres(id)
-    )",
-        [],
-    )
-    .unwrap();
+    );
 
-    conn.execute(
-        "
-    CREATE TABLE album_tracks (
+    CREATE TABLE if not exists album_tracks (
         id INTEGER PRIMARY KEY,
         album_id INTEGER,
         track_id INTEGER,
@@ -101,66 +293,1209 @@ pub fn create_database() {
         disc_number INTEGER,
         FOREIGN KEY(album_id) REFERENCES album(id),
         FOREIGN KEY(track_id) REFERENCES tracks(id)
-    )",
-        [],
-    )
-    .unwrap();
+    );
 
-    conn.execute(
-        "
-    CREATE TABLE track (
+    CREATE TABLE if not exists track (
         id INTEGER PRIMARY KEY,
         name TEXT,
-        path TEXT,
+        path TEXT UNIQUE,
         artist_id INTEGER,
+        size INTEGER,
+        mtime INTEGER,
+        root TEXT,
         FOREIGN KEY(artist_id) REFERENCES artist(id)
-    )",
-        [],
+    );
+
+    CREATE TABLE if not exists album (
+        id INTEGER PRIMARY KEY,
+        name TEXT,
+        artist_id INTEGER,
+        disc_number INTEGER,
+        track_number INTEGER,
+        album_cover BLOB,
+        FOREIGN KEY(artist_id) REFERENCES artist(id)
+    );
+
+    CREATE TABLE if not exists single (
+        id INTEGER PRIMARY KEY,
+        track_id INTEGER,
+        cover BLOB,
+        FOREIGN KEY(track_id) REFERENCES tracks(id)
+    );
+    ",
+    )?;
+
+    // Tracks scanned before multiple library roots get their root assigned by the next scan
+    if !has_column(conn, "track", "root") {
+        conn.execute("ALTER TABLE track ADD COLUMN root TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Points the foreign keys at the tables that actually exist, rows that only make sense with
+/// their track or album are removed along with it. Sqlite can not alter constraints, so every
+/// table that has one is rebuilt.
+fn migration_foreign_keys(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    CREATE TABLE track_new (
+        id INTEGER PRIMARY KEY,
+        name TEXT,
+        path TEXT UNIQUE,
+        artist_id INTEGER REFERENCES artists(id) ON DELETE SET NULL,
+        size INTEGER,
+        mtime INTEGER,
+        root TEXT
+    );
+    INSERT INTO track_new (id, name, path, artist_id, size, mtime, root)
+        SELECT id, name, path, CASE WHEN artist_id IN (SELECT id FROM artists) THEN artist_id END,
+               size, mtime, root
+        FROM track;
+    DROP TABLE track;
+    ALTER TABLE track_new RENAME TO track;
+
+    CREATE TABLE album_new (
+        id INTEGER PRIMARY KEY,
+        name TEXT,
+        artist_id INTEGER REFERENCES artists(id) ON DELETE SET NULL,
+        disc_number INTEGER,
+        track_number INTEGER,
+        album_cover BLOB
+    );
+    INSERT INTO album_new (id, name, artist_id, disc_number, track_number, album_cover)
+        SELECT id, name, CASE WHEN artist_id IN (SELECT id FROM artists) THEN artist_id END,
+               disc_number, track_number, album_cover
+        FROM album;
+    DROP TABLE album;
+    ALTER TABLE album_new RENAME TO album;
+
+    CREATE TABLE album_tracks_new (
+        id INTEGER PRIMARY KEY,
+        album_id INTEGER NOT NULL REFERENCES album(id) ON DELETE CASCADE,
+        track_id INTEGER NOT NULL REFERENCES track(id) ON DELETE CASCADE,
+        track_number INTEGER,
+        disc_number INTEGER
+    );
+    INSERT INTO album_tracks_new (id, album_id, track_id, track_number, disc_number)
+        SELECT id, album_id, track_id, track_number, disc_number
+        FROM album_tracks
+        WHERE album_id IN (SELECT id FROM album) AND track_id IN (SELECT id FROM track);
+    DROP TABLE album_tracks;
+    ALTER TABLE album_tracks_new RENAME TO album_tracks;
+
+    CREATE TABLE track_genres_new (
+        id INTEGER PRIMARY KEY,
+        track_id INTEGER NOT NULL REFERENCES track(id) ON DELETE CASCADE,
+        genre_id INTEGER NOT NULL REFERENCES genres(id) ON DELETE CASCADE
+    );
+    INSERT INTO track_genres_new (id, track_id, genre_id)
+        SELECT id, track_id, genre_id
+        FROM track_genres
+        WHERE track_id IN (SELECT id FROM track) AND genre_id IN (SELECT id FROM genres);
+    DROP TABLE track_genres;
+    ALTER TABLE track_genres_new RENAME TO track_genres;
+
+    CREATE TABLE single_new (
+        id INTEGER PRIMARY KEY,
+        track_id INTEGER NOT NULL REFERENCES track(id) ON DELETE CASCADE,
+        cover BLOB
+    );
+    INSERT INTO single_new (id, track_id, cover)
+        SELECT id, track_id, cover
+        FROM single
+        WHERE track_id IN (SELECT id FROM track);
+    DROP TABLE single;
+    ALTER TABLE single_new RENAME TO single;
+    ",
     )
-    .unwrap();
+}
 
-    conn.execute(
+fn migration_indexes(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
         "
-        CREATE TABLE album (
-            id INTEGER PRIMARY KEY,
-            name TEXT,
-            artist_id INTEGER,
-            disc_number INTEGER,
-            track_number INTEGER,
-            album_cover BLOB,
-            FOREIGN KEY(artist_id) REFERENCES artist(id)
-        )",
-        [],
+    CREATE INDEX if not exists track_path ON track(path);
+    CREATE INDEX if not exists album_name ON album(name);
+    CREATE INDEX if not exists album_tracks_album_id ON album_tracks(album_id);
+    CREATE INDEX if not exists album_tracks_track_id ON album_tracks(track_id);
+    CREATE INDEX if not exists track_genres_track_id ON track_genres(track_id);
+    CREATE INDEX if not exists single_track_id ON single(track_id);
+    ",
     )
-    .unwrap();
+}
 
-    conn.execute(
+fn migration_scan_errors(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
         "
-        CREATE TABLE single (
-            id INTEGER PRIMARY KEY,
-            track_id INTEGER,
-            cover BLOB,
-            FOREIGN KEY(track_id) REFERENCES tracks(id)
-        )",
-        [],
+    CREATE TABLE scan_errors (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL,
+        root TEXT,
+        reason TEXT NOT NULL
+    );
+    CREATE INDEX scan_errors_path ON scan_errors(path);
+    ",
     )
-    .unwrap();
 }
-//todo: Theres probably a better way to do this.
-pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf) {
-    let conn = rusqlite::Connection::open(
-        dirs::data_local_dir()
-            .unwrap()
-            .join(crate::app::AppModel::APP_ID)
-            .join("nova_music.db"),
+
+fn migration_compilations(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE album ADD COLUMN compilation INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX album_identity ON album(name, artist_id, compilation);
+    -- The compilation flag was never stored, the next scan probes every file again to regroup
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_track_artists(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    CREATE TABLE track_artists (
+        track_id INTEGER NOT NULL REFERENCES track(id) ON DELETE CASCADE,
+        artist_id INTEGER NOT NULL REFERENCES artists(id) ON DELETE CASCADE,
+        role TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (track_id, artist_id, role)
+    );
+    CREATE INDEX track_artists_artist_id ON track_artists(artist_id);
+    -- Credits come from the tags, the next scan probes every file again to fill them in
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_classical(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE track ADD COLUMN work TEXT;
+    ALTER TABLE track ADD COLUMN opus TEXT;
+    ALTER TABLE track ADD COLUMN movement_name TEXT;
+    ALTER TABLE track ADD COLUMN movement_number INTEGER;
+    ALTER TABLE track ADD COLUMN part TEXT;
+    -- The new tags were thrown away so far, the next scan probes every file again
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_release_dates(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE album ADD COLUMN release_date TEXT;
+    ALTER TABLE album ADD COLUMN original_date TEXT;
+    ALTER TABLE album ADD COLUMN year INTEGER;
+    CREATE INDEX album_year ON album(year);
+    -- Dates were thrown away so far, the next scan probes every file again
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_audio_properties(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE track ADD COLUMN duration REAL;
+    ALTER TABLE track ADD COLUMN codec TEXT;
+    ALTER TABLE track ADD COLUMN sample_rate INTEGER;
+    ALTER TABLE track ADD COLUMN bit_depth INTEGER;
+    ALTER TABLE track ADD COLUMN channels INTEGER;
+    ALTER TABLE track ADD COLUMN bitrate INTEGER;
+    -- Only the decoder knew these so far, the next scan probes every file again
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_replay_gain(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE track ADD COLUMN track_gain REAL;
+    ALTER TABLE track ADD COLUMN track_peak REAL;
+    ALTER TABLE track ADD COLUMN album_gain REAL;
+    ALTER TABLE track ADD COLUMN album_peak REAL;
+    -- The gain tags were thrown away so far, the next scan probes every file again
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_loudness(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE track ADD COLUMN loudness REAL;
+    ALTER TABLE track ADD COLUMN true_peak REAL;
+    -- Set for tracks that were measured, even when they could not be decoded or are silent
+    ALTER TABLE track ADD COLUMN loudness_analyzed INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE album ADD COLUMN loudness REAL;
+    ALTER TABLE album ADD COLUMN true_peak REAL;
+    ",
+    )
+}
+
+fn migration_lyrics(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE track ADD COLUMN lyrics TEXT;
+    -- Lyrics were thrown away so far, the next scan probes every file again
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_artwork(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    ALTER TABLE album DROP COLUMN album_cover;
+    ALTER TABLE album ADD COLUMN artwork TEXT;
+    ALTER TABLE single DROP COLUMN cover;
+    ALTER TABLE single ADD COLUMN artwork TEXT;
+    -- Covers move to the artwork store, the next scan probes every file again to put them there
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+fn migration_genres(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    -- Genres were stored as tagged, the next scan probes every file again to split and
+    -- normalize them
+    DELETE FROM track_genres;
+    DROP TABLE genres;
+    CREATE TABLE genres (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        -- Broader genre it is listed under, browsing that one includes it
+        parent_id INTEGER REFERENCES genres(id) ON DELETE SET NULL
+    );
+    UPDATE track SET mtime = NULL;
+    ",
+    )
+}
+
+/// Adds the full-text search row of every track `WHERE` picks. The genre, album and artist rows
+/// of the track have to be written first.
+const TRACK_SEARCH_ROW: &str = "
+    INSERT INTO track_search (rowid, title, artist, album, genre, composer, lyrics)
+    SELECT t.id, t.name,
+        (SELECT group_concat(a.name, ' ')
+         FROM track_artists ta join artists a on a.id = ta.artist_id
+         WHERE ta.track_id = t.id AND ta.role != 'composer'),
+        (SELECT group_concat(al.name, ' ')
+         FROM album_tracks at join album al on al.id = at.album_id
+         WHERE at.track_id = t.id),
+        (SELECT group_concat(g.name, ' ')
+         FROM track_genres tg join genres g on g.id = tg.genre_id
+         WHERE tg.track_id = t.id),
+        (SELECT group_concat(a.name, ' ')
+         FROM track_artists ta join artists a on a.id = ta.artist_id
+         WHERE ta.track_id = t.id AND ta.role = 'composer'),
+        t.lyrics
+    FROM track t
+";
+
+fn migration_search(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(&format!(
+        "
+    -- Accents are ignored and words are found by their start, 2 and 3 letter prefixes are
+    -- indexed so searching while typing stays fast
+    CREATE VIRTUAL TABLE track_search USING fts5(
+        title, artist, album, genre, composer, lyrics,
+        tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+    );
+    -- Titles, names and albums weigh more than genres and lyrics when ranking
+    INSERT INTO track_search (track_search, rank) VALUES ('rank', 'bm25(10.0, 5.0, 5.0, 2.0, 2.0, 1.0)');
+    {TRACK_SEARCH_ROW};
+    CREATE TRIGGER track_search_delete AFTER DELETE ON track BEGIN
+        DELETE FROM track_search WHERE rowid = old.id;
+    END;
+
+    CREATE VIRTUAL TABLE artist_search USING fts5(
+        name, content = 'artists', content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+    );
+    INSERT INTO artist_search (artist_search) VALUES ('rebuild');
+    CREATE TRIGGER artist_search_insert AFTER INSERT ON artists BEGIN
+        INSERT INTO artist_search (rowid, name) VALUES (new.id, new.name);
+    END;
+    CREATE TRIGGER artist_search_delete AFTER DELETE ON artists BEGIN
+        INSERT INTO artist_search (artist_search, rowid, name) VALUES ('delete', old.id, old.name);
+    END;
+    CREATE TRIGGER artist_search_update AFTER UPDATE OF name ON artists BEGIN
+        INSERT INTO artist_search (artist_search, rowid, name) VALUES ('delete', old.id, old.name);
+        INSERT INTO artist_search (rowid, name) VALUES (new.id, new.name);
+    END;
+
+    CREATE VIRTUAL TABLE genre_search USING fts5(
+        name, content = 'genres', content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+    );
+    INSERT INTO genre_search (genre_search) VALUES ('rebuild');
+    CREATE TRIGGER genre_search_insert AFTER INSERT ON genres BEGIN
+        INSERT INTO genre_search (rowid, name) VALUES (new.id, new.name);
+    END;
+    CREATE TRIGGER genre_search_delete AFTER DELETE ON genres BEGIN
+        INSERT INTO genre_search (genre_search, rowid, name) VALUES ('delete', old.id, old.name);
+    END;
+    CREATE TRIGGER genre_search_update AFTER UPDATE OF name ON genres BEGIN
+        INSERT INTO genre_search (genre_search, rowid, name) VALUES ('delete', old.id, old.name);
+        INSERT INTO genre_search (rowid, name) VALUES (new.id, new.name);
+    END;
+    "
+    ))
+}
+
+/// Words in each search index, misspelled search words are corrected to the closest of them
+fn migration_search_vocab(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    CREATE VIRTUAL TABLE track_search_vocab USING fts5vocab(track_search, row);
+    CREATE VIRTUAL TABLE artist_search_vocab USING fts5vocab(artist_search, row);
+    CREATE VIRTUAL TABLE genre_search_vocab USING fts5vocab(genre_search, row);
+    ",
+    )
+}
+
+/// Device each library root was on when its missing files were last removed
+fn migration_library_roots(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    CREATE TABLE library_roots (
+        path TEXT PRIMARY KEY,
+        device INTEGER NOT NULL
+    );
+    ",
+    )
+}
+
+fn migration_album_artists(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+    -- Albums without an album artist were shared by every artist with an album of that name,
+    -- the next scan probes their tracks again to file them under the artist of the track
+    UPDATE track SET mtime = NULL
+    WHERE id IN (SELECT at.track_id
+                 FROM album_tracks at join album a on a.id = at.album_id
+                 WHERE a.artist_id IS NULL);
+    ",
+    )
+}
+
+/// Track search rows hold the names of artists, albums and genres, they are written again when
+/// one of those is renamed
+fn migration_search_renames(conn: &Connection) -> rusqlite::Result<()> {
+    let renamed = |table: &str, links: &str, column: &str| {
+        format!(
+            "
+    CREATE TRIGGER track_search_{table}_rename AFTER UPDATE OF name ON {table} BEGIN
+        DELETE FROM track_search
+        WHERE rowid IN (SELECT track_id FROM {links} WHERE {column} = new.id);
+        {TRACK_SEARCH_ROW}
+        WHERE t.id IN (SELECT track_id FROM {links} WHERE {column} = new.id);
+    END;
+    "
+        )
+    };
+
+    conn.execute_batch(&format!(
+        "{}{}{}",
+        renamed("artists", "track_artists", "artist_id"),
+        renamed("album", "album_tracks", "album_id"),
+        renamed("genres", "track_genres", "genre_id"),
+    ))
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    let mut stmt = match conn.prepare(&format!("PRAGMA table_info({})", table)) {
+        Ok(stmt) => stmt,
+        Err(err) => {
+            log::error!("Could not read columns of {}: {}", table, err);
+            return false;
+        }
+    };
+
+    let columns = match stmt.query_map([], |row| row.get::<&str, String>("name")) {
+        Ok(columns) => columns.filter_map(|a| a.ok()).collect::<Vec<String>>(),
+        Err(_) => vec![],
+    };
+
+    columns.iter().any(|name| name == column)
+}
+
+/// Size and modification time of a file, used to tell whether it changed since the last scan
+/// A sidecar `.lrc` file counts as part of the file, so adding or editing lyrics rescans it
+pub fn file_signature(path: &Path, metadata: &fs::Metadata) -> (u64, i64) {
+    let mtime = |metadata: &fs::Metadata| {
+        metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs() as i64)
+            .unwrap_or(0)
+    };
+    let lyrics_mtime = fs::metadata(lyrics_sidecar(path))
+        .map(|metadata| mtime(&metadata))
+        .unwrap_or(0);
+
+    (metadata.len(), mtime(metadata).max(lyrics_mtime))
+}
+
+/// Where lyrics for a file are looked for, next to it with the same name
+pub fn lyrics_sidecar(path: &Path) -> PathBuf {
+    path.with_extension("lrc")
+}
+
+fn sidecar_lyrics(path: &Path) -> Option<String> {
+    let bytes = fs::read(lyrics_sidecar(path)).ok()?;
+    // Older LRC files are not always UTF-8, a few odd characters beat no lyrics
+    let lyrics = String::from_utf8_lossy(&bytes)
+        .trim_start_matches('\u{feff}')
+        .to_string();
+
+    (!lyrics.trim().is_empty()).then_some(lyrics)
+}
+
+/// Every file currently in the track table along with its signature from when it was probed
+pub fn known_files() -> rusqlite::Result<HashMap<PathBuf, (u64, i64)>> {
+    let conn = open_connection()?;
+
+    let mut stmt = conn.prepare("SELECT path, size, mtime FROM track")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            PathBuf::from(row.get::<&str, String>("path")?),
+            (
+                row.get::<&str, u64>("size").unwrap_or(0),
+                row.get::<&str, i64>("mtime").unwrap_or(0),
+            ),
+        ))
+    })?;
+
+    rows.collect()
+}
+
+/// Removes a track and everything that only exists because of it. Nothing is removed when
+/// any of it fails.
+pub fn remove_track(conn: &Connection, filepath: &Path) -> rusqlite::Result<()> {
+    let path = filepath.to_string_lossy();
+
+    // A savepoint rather than a transaction, the scan writer calls this inside its own
+    conn.execute_batch("SAVEPOINT remove_track")?;
+
+    match delete_track(conn, &path) {
+        Ok(()) => conn.execute_batch("RELEASE remove_track"),
+        Err(err) => {
+            if let Err(err) = conn.execute_batch("ROLLBACK TO remove_track; RELEASE remove_track") {
+                log::error!("Could not undo removal of {}: {}", path, err);
+            }
+            Err(err)
+        }
+    }
+}
+
+fn delete_track(conn: &Connection, path: &str) -> rusqlite::Result<()> {
+    // Albums this track was the last remaining track of
+    conn.execute(
+        "
+        DELETE FROM album
+        WHERE id IN (SELECT album_id FROM album_tracks
+                     WHERE track_id IN (SELECT id FROM track WHERE path = ?1))
+          AND (SELECT count(*) FROM album_tracks WHERE album_tracks.album_id = album.id) = 1
+        ",
+        [path],
+    )?;
+
+    // Genre, album and single rows of the track go with it
+    conn.execute("DELETE FROM track WHERE path = ?", [path])?;
+
+    Ok(())
+}
+
+/// Replaces the problems recorded for a file, an empty `problems` clears them
+pub fn record_scan_errors(
+    conn: &Connection,
+    path: &Path,
+    root: &Path,
+    problems: &[String],
+) -> rusqlite::Result<()> {
+    let path = path.to_string_lossy();
+    conn.execute("DELETE FROM scan_errors WHERE path = ?", [&path])?;
+
+    for problem in problems {
+        conn.execute(
+            "INSERT INTO scan_errors (path, root, reason) VALUES (?, ?, ?)",
+            (&path, root.to_string_lossy(), problem),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Forgets the problems of files at or below `path`
+fn remove_scan_errors(conn: &Connection, path: &Path) {
+    if let Err(err) = conn.execute(
+        "DELETE FROM scan_errors WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+        [path.to_string_lossy()],
+    ) {
+        log::error!("Could not remove scan problems of {}: {}", path.display(), err);
+    }
+}
+
+/// Removes every track whose file was not seen during the last scan
+pub fn remove_missing_files(root: &Path, seen: &HashSet<PathBuf>) -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    for path in known_files()?
+        .keys()
+        .filter(|path| path.starts_with(root) && !seen.contains(*path))
+    {
+        log::info!("{} is gone, removing it from the library", path.display());
+        remove_track(&conn, path)?;
+    }
+
+    // Files that could not be added are not tracks, their problems are checked on their own
+    let reported = conn
+        .prepare("SELECT DISTINCT path FROM scan_errors WHERE root = ?")?
+        .query_map([root.to_string_lossy()], |row| {
+            row.get::<&str, String>("path")
+        })?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for path in reported
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| !seen.contains(path))
+    {
+        remove_scan_errors(&conn, &path);
+    }
+
+    Ok(())
+}
+
+/// Device `root` was on when its missing files were last removed, if they ever were
+pub fn root_device(root: &Path) -> rusqlite::Result<Option<u64>> {
+    let conn = open_connection()?;
+
+    conn.query_row(
+        "SELECT device FROM library_roots WHERE path = ?",
+        [root.to_string_lossy()],
+        |row| row.get(0),
     )
-    .unwrap();
+    .optional()
+}
+
+/// Remembers the device `root` is on, later scans only remove missing files while it stays
+pub fn record_root_device(root: &Path, device: u64) -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    conn.execute(
+        "INSERT INTO library_roots (path, device) VALUES (?1, ?2)
+         ON CONFLICT (path) DO UPDATE SET device = ?2",
+        (root.to_string_lossy(), device),
+    )?;
+
+    Ok(())
+}
+
+/// Removes the track at `path`, or every track below it when `path` was a directory
+pub fn remove_path(path: &Path) -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    for known in known_files()?
+        .keys()
+        .filter(|known| known.starts_with(path))
+    {
+        log::info!(
+            "{} was removed, removing it from the library",
+            known.display()
+        );
+        remove_track(&conn, known)?;
+    }
+
+    remove_scan_errors(&conn, path);
+    Ok(())
+}
+
+/// Removes every track that was found in a library root
+pub fn remove_root(root: &Path) -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    let paths = conn
+        .prepare("SELECT path FROM track WHERE root = ?")?
+        .query_map([root.to_string_lossy()], |row| {
+            row.get::<&str, String>("path")
+        })?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for path in paths {
+        remove_track(&conn, Path::new(&path))?;
+    }
+
+    conn.execute(
+        "DELETE FROM scan_errors WHERE root = ?",
+        [root.to_string_lossy()],
+    )?;
+    conn.execute(
+        "DELETE FROM library_roots WHERE path = ?",
+        [root.to_string_lossy()],
+    )?;
+
+    Ok(())
+}
+
+/// Deletes covers from the artwork store that no album or single shows anymore
+pub fn prune_artwork() -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    let in_use = conn
+        .prepare(
+            "SELECT artwork FROM album WHERE artwork IS NOT NULL
+             UNION SELECT artwork FROM single WHERE artwork IS NOT NULL",
+        )?
+        .query_map([], |row| row.get::<usize, String>(0))?
+        .collect::<rusqlite::Result<HashSet<String>>>()?;
+
+    artwork::prune(&in_use);
+    Ok(())
+}
+
+/// Albums with a track that has no ReplayGain tags and was not measured yet, along with
+/// every other track on them
+pub fn unanalyzed_albums(conn: &Connection) -> rusqlite::Result<Vec<AnalysisAlbum>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT at.album_id, track.id, track.path
+        FROM track
+            left join album_tracks at on track.id = at.track_id
+        WHERE at.album_id IN (SELECT at.album_id
+                              FROM album_tracks at
+                                  join track on at.track_id = track.id
+                              WHERE track.loudness_analyzed = 0
+                                AND track.track_gain IS NULL AND track.album_gain IS NULL)
+           OR (at.album_id IS NULL AND track.loudness_analyzed = 0
+               AND track.track_gain IS NULL AND track.album_gain IS NULL)
+        ORDER BY at.album_id IS NULL, at.album_id, at.disc_number, at.track_number
+        ",
+    )?;
+
+    let mut albums: Vec<AnalysisAlbum> = vec![];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let album_id: Option<u64> = row.get("album_id")?;
+        let track = (row.get("id")?, PathBuf::from(row.get::<_, String>("path")?));
+
+        match albums.last_mut() {
+            Some(album) if album_id.is_some() && album.album_id == album_id => {
+                album.tracks.push(track)
+            }
+            _ => albums.push(AnalysisAlbum {
+                album_id,
+                tracks: vec![track],
+            }),
+        }
+    }
+
+    Ok(albums)
+}
+
+/// Stores what the loudness analysis measured for an album and its tracks in one transaction,
+/// silent tracks, which have no loudness, are only marked as analyzed
+pub fn write_loudness(
+    conn: &mut Connection,
+    album_id: Option<u64>,
+    tracks: &[(u64, Option<Loudness>)],
+    album: Option<Loudness>,
+) -> rusqlite::Result<()> {
+    let transaction = conn.transaction()?;
+
+    for (id, loudness) in tracks {
+        transaction.execute(
+            "UPDATE track SET loudness = ?, true_peak = ?, loudness_analyzed = 1 WHERE id = ?",
+            (
+                loudness.map(|loudness| loudness.integrated),
+                loudness.map(|loudness| loudness.true_peak),
+                id,
+            ),
+        )?;
+    }
+
+    if let Some(album_id) = album_id {
+        transaction.execute(
+            "UPDATE album SET loudness = ?, true_peak = ? WHERE id = ?",
+            (
+                album.map(|loudness| loudness.integrated),
+                album.map(|loudness| loudness.true_peak),
+                album_id,
+            ),
+        )?;
+    }
+
+    transaction.commit()
+}
+
+/// Assigns `root` to tracks below it that were scanned before tracks recorded their root
+pub fn claim_tracks(root: &Path) -> rusqlite::Result<()> {
+    let conn = open_connection()?;
+
+    let paths = conn
+        .prepare("SELECT path FROM track WHERE root IS NULL")?
+        .query_map([], |row| row.get::<&str, String>("path"))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for path in paths
+        .iter()
+        .filter(|path| Path::new(path.as_str()).starts_with(root))
+    {
+        conn.execute(
+            "UPDATE track SET root = ? WHERE path = ?",
+            (root.to_string_lossy(), path),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// What a scan read from a file, gathered on the probe threads so the writer only has to
+/// insert it
+pub struct ProbedTrack {
+    track: Track,
+    album: Album,
+    album_tracks: AlbumTracks,
+    artist: Artist,
+    /// Everyone credited on the track, primary artists first
+    credits: Vec<(String, ArtistRole)>,
+    album_artist: Option<String>,
+    /// Hash of the cover in the [`artwork`] store
+    cover: Option<String>,
+    /// Genres of the track and every genre above them, with the one each is listed under
+    genre_parents: Vec<(String, Option<String>)>,
+    signature: (u64, i64),
+    properties: AudioProperties,
+    /// Tags that could not be read, the file is added without them
+    pub problems: Vec<String>,
+}
+
+/// Stream properties of a file's default track, read from its header at scan time so nothing
+/// has to be decoded to show them
+#[derive(Debug, Clone, Default)]
+pub struct AudioProperties {
+    /// In seconds
+    pub duration: Option<f64>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// Only known for lossless codecs
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    /// Average over the whole file in kbit/s, tags and cover art included
+    pub bitrate: Option<u32>,
+}
+
+impl AudioProperties {
+    pub fn new(params: &CodecParameters, file_size: u64) -> AudioProperties {
+        let duration = match (params.n_frames, params.time_base, params.sample_rate) {
+            (Some(frames), Some(time_base), _) => {
+                let time = time_base.calc_time(frames);
+                Some(time.seconds as f64 + time.frac)
+            }
+            (Some(frames), None, Some(rate)) if rate > 0 => Some(frames as f64 / rate as f64),
+            _ => None,
+        };
+
+        AudioProperties {
+            duration,
+            codec: get_codecs()
+                .get_codec(params.codec)
+                .map(|codec| codec.short_name.to_string()),
+            sample_rate: params.sample_rate,
+            bit_depth: params.bits_per_sample,
+            channels: params.channels.map(|channels| channels.count() as u32),
+            bitrate: duration
+                .filter(|duration| *duration > 0.0)
+                .map(|duration| (file_size as f64 * 8.0 / duration / 1000.0).round() as u32),
+        }
+    }
+}
+
+/// Placeholders a fallback pattern can use
+const PATH_FIELDS: &[&str] = &["albumartist", "artist", "album", "disc", "track", "title"];
+
+/// Tags taken from where a file is, for files that lack them
+#[derive(Debug, Default)]
+pub struct PathTags {
+    album_artist: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    disc_number: Option<u64>,
+    track_number: Option<u64>,
+    title: Option<String>,
+}
+
+/// A pattern like `{albumartist}/{album}/{track} - {title}` matched against the end of a
+/// file's path relative to its library root, without the extension
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    regex: Regex,
+    fields: Vec<&'static str>,
+}
+
+impl PathPattern {
+    /// `None` for an empty pattern, which turns the fallback off
+    pub fn new(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim().trim_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        let mut regex = String::from("(?:^|/)");
+        let mut fields = vec![];
+        let mut rest = pattern;
+
+        while let Some(start) = rest.find('{') {
+            regex.push_str(&regex::escape(&rest[..start]));
+            let after = &rest[start + 1..];
+
+            let field = after.find('}').and_then(|end| {
+                PATH_FIELDS
+                    .iter()
+                    .find(|field| **field == &after[..end])
+                    .map(|field| (end, *field))
+            });
+
+            match field {
+                Some((end, field)) => {
+                    regex.push_str(match field {
+                        "disc" | "track" => r"(\d+)",
+                        _ => r"([^/]+?)",
+                    });
+                    fields.push(field);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    // Not a placeholder, the brace is part of the name
+                    regex.push_str(r"\{");
+                    rest = after;
+                }
+            }
+        }
+
+        regex.push_str(&regex::escape(rest));
+        regex.push('$');
+
+        Some(PathPattern {
+            regex: Regex::new(&regex).ok()?,
+            fields,
+        })
+    }
+
+    pub fn apply(&self, root: &Path, filepath: &Path) -> PathTags {
+        let mut tags = PathTags::default();
+
+        let relative = filepath
+            .strip_prefix(root)
+            .unwrap_or(filepath)
+            .with_extension("");
+        let relative = relative.to_string_lossy();
+
+        let Some(captures) = self.regex.captures(&relative) else {
+            return tags;
+        };
+
+        for (field, value) in self.fields.iter().zip(captures.iter().skip(1)) {
+            let Some(value) = value.map(|value| value.as_str().trim().to_string()) else {
+                continue;
+            };
+
+            match *field {
+                "albumartist" => tags.album_artist = Some(value),
+                "artist" => tags.artist = Some(value),
+                "album" => tags.album = Some(value),
+                "disc" => tags.disc_number = value.parse().ok(),
+                "track" => tags.track_number = value.parse().ok(),
+                "title" => tags.title = Some(value),
+                _ => {}
+            }
+        }
+
+        tags
+    }
+}
+
+/// What an artist is credited for on a track, stored in `track_artists.role`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtistRole {
+    Primary,
+    Featured,
+    Remixer,
+    Composer,
+    Conductor,
+    Performer,
+}
+
+impl ArtistRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtistRole::Primary => "primary",
+            ArtistRole::Featured => "featured",
+            ArtistRole::Remixer => "remixer",
+            ArtistRole::Composer => "composer",
+            ArtistRole::Conductor => "conductor",
+            ArtistRole::Performer => "performer",
+        }
+    }
+}
+
+impl std::str::FromStr for ArtistRole {
+    type Err = ();
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "primary" => Ok(ArtistRole::Primary),
+            "featured" => Ok(ArtistRole::Featured),
+            "remixer" => Ok(ArtistRole::Remixer),
+            "composer" => Ok(ArtistRole::Composer),
+            "conductor" => Ok(ArtistRole::Conductor),
+            "performer" => Ok(ArtistRole::Performer),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Splits artist tags holding several names, like `A & B feat. C`, into one credit each
+#[derive(Debug, Clone)]
+pub struct ArtistSeparators {
+    /// Between artists of the same role
+    split: Regex,
+    /// Everyone after it is featured on the track
+    featuring: Option<Regex>,
+}
+
+/// Separators made of letters only split between words, so `ft.` leaves "Daft Punk" alone
+fn separator_alternation(separators: &[String]) -> Option<Regex> {
+    let alternatives = separators
+        .iter()
+        .map(|separator| separator.trim())
+        .filter(|separator| !separator.is_empty())
+        .map(
+            |separator| match separator.starts_with(|c: char| c.is_alphanumeric()) {
+                true => format!(r"\s+\(?{}\s+", regex::escape(separator)),
+                false => format!(r"\s*{}\s*", regex::escape(separator)),
+            },
+        )
+        .collect::<Vec<String>>();
+
+    match alternatives.is_empty() {
+        true => None,
+        false => regex::RegexBuilder::new(&alternatives.join("|"))
+            .case_insensitive(true)
+            .build()
+            .ok(),
+    }
+}
+
+/// `separators` together with nul, multi-valued ID3v2.4 frames arrive as a single string
+/// separated by it
+fn value_separators(separators: &[String]) -> Regex {
+    let mut split = separators.to_vec();
+    split.push("\0".to_string());
+    separator_alternation(&split).expect("nul separator is always valid")
+}
+
+impl ArtistSeparators {
+    pub fn new(separators: &[String], featuring: &[String]) -> Self {
+        ArtistSeparators {
+            split: value_separators(separators),
+            featuring: separator_alternation(featuring),
+        }
+    }
+
+    /// Credits in the order they appear in `value`, `role` is what the names before any
+    /// featuring separator are credited for
+    fn split(&self, value: &str, role: ArtistRole) -> Vec<(String, ArtistRole)> {
+        let (main, featured) = match self.featuring.as_ref().and_then(|regex| regex.find(value)) {
+            Some(found) => (&value[..found.start()], Some(&value[found.end()..])),
+            None => (value, None),
+        };
+
+        let names = |part: &str, role: ArtistRole| {
+            self.split
+                .split(part)
+                .map(|name| name.trim().trim_matches(|c| c == '(' || c == ')').trim())
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.to_string(), role))
+                .collect::<Vec<(String, ArtistRole)>>()
+        };
+
+        let mut credits = names(main, role);
+        if let Some(featured) = featured {
+            // "A feat. B ft. C" features both
+            let featured = match &self.featuring {
+                Some(regex) => regex.replace_all(featured, "\0").to_string(),
+                None => featured.to_string(),
+            };
+            let featured_role = match role {
+                ArtistRole::Primary => ArtistRole::Featured,
+                other => other,
+            };
+            credits.extend(names(&featured, featured_role));
+        }
+
+        credits
+    }
+}
+
+impl Default for ArtistSeparators {
+    fn default() -> Self {
+        ArtistSeparators::new(&[], &[])
+    }
+}
+
+/// Splits genre tags holding several genres, like `Rock; Pop`, and reads spellings of a genre
+/// as one
+#[derive(Debug, Clone)]
+pub struct GenreOptions {
+    split: Regex,
+    /// By [`genre_key`] of the alias, the genre it is read as
+    aliases: HashMap<String, String>,
+    /// By [`genre_key`], the broader genre it is listed under
+    parents: HashMap<String, String>,
+}
+
+impl GenreOptions {
+    /// `aliases` and `parents` are pairs of a genre and what it maps to
+    pub fn new(
+        separators: &[String],
+        aliases: &[(String, String)],
+        parents: &[(String, String)],
+    ) -> Self {
+        let by_key = |pairs: &[(String, String)]| {
+            pairs
+                .iter()
+                .map(|(genre, to)| (genre_key(genre), clean_genre(to)))
+                .filter(|(genre, to)| !genre.is_empty() && !to.is_empty())
+                .collect::<HashMap<String, String>>()
+        };
+
+        GenreOptions {
+            split: value_separators(separators),
+            aliases: by_key(aliases),
+            parents: by_key(parents),
+        }
+    }
+
+    /// Genres in `values` in the order they appear, each once
+    fn split(&self, values: &[String]) -> Vec<String> {
+        let mut genres: Vec<String> = vec![];
+        for value in values {
+            for genre in self.split.split(value).map(clean_genre) {
+                if genre.is_empty() {
+                    continue;
+                }
+                let genre = match self.aliases.get(&genre_key(&genre)) {
+                    Some(canonical) => canonical.clone(),
+                    None => genre,
+                };
+                if !genres
+                    .iter()
+                    .any(|known| genre_key(known) == genre_key(&genre))
+                {
+                    genres.push(genre);
+                }
+            }
+        }
+        genres
+    }
+
+    /// `genres` and every genre above them, each with the one it is listed under
+    fn lineage(&self, genres: &[String]) -> Vec<(String, Option<String>)> {
+        let mut lineage: Vec<(String, Option<String>)> = vec![];
+        let mut pending = genres.to_vec();
+
+        while let Some(genre) = pending.pop() {
+            // A genre listed under its own subgenre stops the walk up at the second visit
+            if lineage
+                .iter()
+                .any(|(known, _)| genre_key(known) == genre_key(&genre))
+            {
+                continue;
+            }
+            let parent = self.parents.get(&genre_key(&genre)).cloned();
+            pending.extend(parent.clone());
+            lineage.push((genre, parent));
+        }
+        lineage
+    }
+}
+
+impl Default for GenreOptions {
+    fn default() -> Self {
+        GenreOptions::new(&[], &[], &[])
+    }
+}
+
+/// Trimmed with inner whitespace collapsed, a genre written in lowercase only gets its words
+/// capitalized. Genres with capitals in them are kept as they are, so `R&B` and `EDM` stay.
+fn clean_genre(value: &str) -> String {
+    let genre = value.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if genre.chars().any(char::is_uppercase) {
+        return genre;
+    }
 
+    let mut capitalized = String::with_capacity(genre.len());
+    let mut word_start = true;
+    for c in genre.chars() {
+        match word_start {
+            true => capitalized.extend(c.to_uppercase()),
+            false => capitalized.push(c),
+        }
+        word_start = matches!(c, ' ' | '-' | '/');
+    }
+    capitalized
+}
+
+/// What spellings of the same genre have in common, aliases are looked up by it
+fn genre_key(genre: &str) -> String {
+    genre
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// How a scan turns what it finds in a file into tags
+#[derive(Debug, Clone, Default)]
+pub struct TagOptions {
+    pub fallback: Option<PathPattern>,
+    pub separators: ArtistSeparators,
+    pub genres: GenreOptions,
+    /// Images in the album folder looked for when a file has no cover, most preferred first
+    pub cover_names: Vec<String>,
+}
+
+//todo: Theres probably a better way to do this.
+pub fn read_tags(
+    metadata_tags: Vec<Tag>,
+    filepath: &PathBuf,
+    path_tags: Option<PathTags>,
+    separators: &ArtistSeparators,
+    genre_options: &GenreOptions,
+    cover_names: &[String],
+    properties: AudioProperties,
+) -> ProbedTrack {
     let mut track = Track {
         id: 0,
         genres: None,
         name: None,
+        work: None,
+        opus: None,
+        movement_name: None,
+        movement_number: None,
+        part: None,
+        replay_gain: ReplayGain::default(),
+        lyrics: None,
     };
+    // The grouping tag holds the work in some taggers, an actual work tag wins over it
+    let mut content_group = None;
 
     let mut album = Album {
         id: 0,
@@ -168,14 +1503,24 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
         artist_id: None,
         num_of_discs: 1,
         num_of_tracks: 0,
+        compilation: false,
+        release_date: None,
+        original_date: None,
     };
+    // `ReleaseDate` is the more specific of the two when a file has both
+    let mut date = None;
+    let mut release_date = None;
 
     let mut album_tracks = AlbumTracks {
         disc_number: 0,
         track_number: 0,
     };
 
-    let mut artist = Artist { id: 0, name: None };
+    let mut artist = Artist { name: None };
+    let mut album_artist = None;
+    // Raw values of every tag crediting someone, split once the path had its say
+    let mut credit_tags: Vec<(String, ArtistRole)> = vec![];
+    let mut problems = vec![];
 
     for tag in metadata_tags {
         if let Some(key) = tag.std_key {
@@ -193,7 +1538,7 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                     }
                 },
                 StandardTagKey::AlbumArtist => match tag.value {
-                    Value::String(mut name) => {
+                    Value::String(name) => {
                         // let regex = Regex::new("/Feat.|ft.|&/i").unwrap();
                         //
                         // match regex.find(&name) {
@@ -204,60 +1549,58 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                         //     }
                         // };
                         //
-
-                        match conn.execute("INSERT INTO artists (name) VALUES (?)", [name.trim()]) {
-                            Ok(_) => {
-                                // log::info!("Added artist {} to artists", name);
-                                album.artist_id = Some(conn.last_insert_rowid() as u32);
-                            }
-                            Err(_) => {
-                                // log::warn!("Artist: {} already created", name);
-                                album.artist_id = Some(
-                                    conn.query_row(
-                                        "SELECT id FROM artists WHERE name = ?",
-                                        &[&name],
-                                        |row| row.get::<usize, u32>(0),
-                                    )
-                                        .unwrap());
-                                // log::info!("ARTIST ID:  {}", album.artist_id);
-                            }
-                        }
+                        album_artist = Some(name);
                     }
                     _ => {}
                 },
                 StandardTagKey::Arranger => {}
                 StandardTagKey::Artist => {
+                    // Some formats repeat the tag for every artist
                     if let Value::String(val) = tag.value {
-                        artist.name = Some(val)
+                        artist.name.get_or_insert_with(|| val.clone());
+                        credit_tags.push((val, ArtistRole::Primary));
                     }
                 }
                 StandardTagKey::Bpm => {}
                 StandardTagKey::Comment => {}
-                StandardTagKey::Compilation => {}
-                StandardTagKey::Composer => {}
-                StandardTagKey::Conductor => {}
-                StandardTagKey::ContentGroup => {}
+                StandardTagKey::Compilation => {
+                    album.compilation = match tag.value {
+                        Value::Boolean(val) => val,
+                        Value::Flag => true,
+                        Value::UnsignedInt(val) => val != 0,
+                        Value::SignedInt(val) => val != 0,
+                        Value::String(val) => {
+                            matches!(val.trim().to_lowercase().as_str(), "1" | "true" | "yes")
+                        }
+                        _ => false,
+                    }
+                }
+                StandardTagKey::Composer => {
+                    if let Value::String(val) = tag.value {
+                        credit_tags.push((val, ArtistRole::Composer))
+                    }
+                }
+                StandardTagKey::Conductor => {
+                    if let Value::String(val) = tag.value {
+                        credit_tags.push((val, ArtistRole::Conductor))
+                    }
+                }
+                StandardTagKey::ContentGroup => {
+                    if let Value::String(val) = tag.value {
+                        content_group = Some(val)
+                    }
+                }
                 StandardTagKey::Copyright => {}
-                StandardTagKey::Date => {}
+                StandardTagKey::Date => match parse_date(&tag.value.to_string()) {
+                    Some(val) => date = Some(val),
+                    None => problems.push(fl!("InvalidDate", value = tag.value.to_string())),
+                },
                 StandardTagKey::Description => {}
                 StandardTagKey::DiscNumber => match tag.value {
-                    Value::String(val) => {
-                        // log::info!("DISC NUMBER");
-                        let mut final_val = val;
-
-                        if final_val.contains("/") {
-                            final_val = final_val
-                                .split("/")
-                                .next()
-                                .expect("Number")
-                                .parse()
-                                .unwrap();
-                        }
-
-                        album_tracks.disc_number = final_val
-                            .parse::<u64>()
-                            .expect(format!("Invalid track number: {}", final_val).as_str());
-                    }
+                    Value::String(val) => match parse_number(&val) {
+                        Some(number) => album_tracks.disc_number = number,
+                        None => problems.push(fl!("InvalidDiscNumber", value = val)),
+                    },
                     Value::UnsignedInt(val) => {
                         // log::info!("{}: {}", "DISC NUMBER unsigned int".red(), val);
                         album_tracks.disc_number = val
@@ -268,7 +1611,11 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 },
                 StandardTagKey::DiscSubtitle => {}
                 StandardTagKey::DiscTotal => match tag.value {
-                    Value::String(val) => album.num_of_discs = val.parse::<u32>().unwrap(),
+                    Value::String(val) => match parse_number(&val) {
+                        Some(number) => album.num_of_discs = number,
+                        None => problems.push(fl!("InvalidDiscTotal", value = val)),
+                    },
+                    Value::UnsignedInt(val) => album.num_of_discs = val as u32,
                     _ => {
                         // log::error!("Disc number is not a number");
                     }
@@ -278,19 +1625,13 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::EncoderSettings => {}
                 StandardTagKey::EncodingDate => {}
                 StandardTagKey::Engineer => {}
-                StandardTagKey::Ensemble => {}
+                StandardTagKey::Ensemble => {
+                    if let Value::String(val) = tag.value {
+                        credit_tags.push((val, ArtistRole::Performer))
+                    }
+                }
                 StandardTagKey::Genre => {
                     if !tag.value.to_string().is_empty() {
-                        match conn.execute(
-                            "insert into genres (name) values (?)",
-                            [tag.value.to_string()],
-                        ) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                // log::error!("error: {}", err);
-                            }
-                        }
-
                         if let Some(genres) = &mut track.genres {
                             genres.push(tag.value.to_string())
                         } else {
@@ -311,13 +1652,26 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::Language => {}
                 StandardTagKey::License => {}
                 StandardTagKey::Lyricist => {}
-                StandardTagKey::Lyrics => {}
+                StandardTagKey::Lyrics => {
+                    let lyrics = tag.value.to_string();
+                    if !lyrics.trim().is_empty() {
+                        track.lyrics = Some(lyrics);
+                    }
+                }
                 StandardTagKey::MediaFormat => {}
                 StandardTagKey::MixDj => {}
                 StandardTagKey::MixEngineer => {}
                 StandardTagKey::Mood => {}
-                StandardTagKey::MovementName => {}
-                StandardTagKey::MovementNumber => {}
+                StandardTagKey::MovementName => {
+                    if let Value::String(val) = tag.value {
+                        track.movement_name = Some(val)
+                    }
+                }
+                StandardTagKey::MovementNumber => match tag.value {
+                    Value::String(val) => track.movement_number = parse_number(&val),
+                    Value::UnsignedInt(val) => track.movement_number = Some(val as u32),
+                    _ => {}
+                },
                 StandardTagKey::MusicBrainzAlbumArtistId => {}
                 StandardTagKey::MusicBrainzAlbumId => {}
                 StandardTagKey::MusicBrainzArtistId => {}
@@ -333,16 +1687,31 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::MusicBrainzReleaseType => {}
                 StandardTagKey::MusicBrainzTrackId => {}
                 StandardTagKey::MusicBrainzWorkId => {}
-                StandardTagKey::Opus => {}
+                StandardTagKey::Opus => {
+                    if let Value::String(val) = tag.value {
+                        track.opus = Some(val)
+                    }
+                }
                 StandardTagKey::OriginalAlbum => {}
                 StandardTagKey::OriginalArtist => {}
-                StandardTagKey::OriginalDate => {}
+                StandardTagKey::OriginalDate => match parse_date(&tag.value.to_string()) {
+                    Some(val) => album.original_date = Some(val),
+                    None => problems.push(fl!("InvalidDate", value = tag.value.to_string())),
+                },
                 StandardTagKey::OriginalFile => {}
                 StandardTagKey::OriginalWriter => {}
                 StandardTagKey::Owner => {}
-                StandardTagKey::Part => {}
+                StandardTagKey::Part => {
+                    if let Value::String(val) = tag.value {
+                        track.part = Some(val)
+                    }
+                }
                 StandardTagKey::PartTotal => {}
-                StandardTagKey::Performer => {}
+                StandardTagKey::Performer => {
+                    if let Value::String(val) = tag.value {
+                        credit_tags.push((val, ArtistRole::Performer))
+                    }
+                }
                 StandardTagKey::Podcast => {}
                 StandardTagKey::PodcastCategory => {}
                 StandardTagKey::PodcastDescription => {}
@@ -351,12 +1720,27 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::PurchaseDate => {}
                 StandardTagKey::Rating => {}
                 StandardTagKey::ReleaseCountry => {}
-                StandardTagKey::ReleaseDate => {}
-                StandardTagKey::Remixer => {}
-                StandardTagKey::ReplayGainAlbumGain => {}
-                StandardTagKey::ReplayGainAlbumPeak => {}
-                StandardTagKey::ReplayGainTrackGain => {}
-                StandardTagKey::ReplayGainTrackPeak => {}
+                StandardTagKey::ReleaseDate => match parse_date(&tag.value.to_string()) {
+                    Some(val) => release_date = Some(val),
+                    None => problems.push(fl!("InvalidDate", value = tag.value.to_string())),
+                },
+                StandardTagKey::Remixer => {
+                    if let Value::String(val) = tag.value {
+                        credit_tags.push((val, ArtistRole::Remixer))
+                    }
+                }
+                StandardTagKey::ReplayGainAlbumGain => {
+                    track.replay_gain.album_gain = parse_gain(&tag.value.to_string())
+                }
+                StandardTagKey::ReplayGainAlbumPeak => {
+                    track.replay_gain.album_peak = parse_gain(&tag.value.to_string())
+                }
+                StandardTagKey::ReplayGainTrackGain => {
+                    track.replay_gain.track_gain = parse_gain(&tag.value.to_string())
+                }
+                StandardTagKey::ReplayGainTrackPeak => {
+                    track.replay_gain.track_peak = parse_gain(&tag.value.to_string())
+                }
                 StandardTagKey::Script => {}
                 StandardTagKey::SortAlbum => {}
                 StandardTagKey::SortAlbumArtist => {}
@@ -365,22 +1749,10 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::SortTrackTitle => {}
                 StandardTagKey::TaggingDate => {}
                 StandardTagKey::TrackNumber => match tag.value {
-                    Value::String(val) => {
-                        let mut final_val = val;
-
-                        if final_val.contains("/") {
-                            final_val = final_val
-                                .split("/")
-                                .next()
-                                .expect("Number")
-                                .parse()
-                                .unwrap();
-                        }
-
-                        album_tracks.track_number = final_val
-                            .parse::<u64>()
-                            .expect(format!("Invalid track number: {}", final_val).as_str());
-                    }
+                    Value::String(val) => match parse_number(&val) {
+                        Some(number) => album_tracks.track_number = number,
+                        None => problems.push(fl!("InvalidTrackNumber", value = val)),
+                    },
                     Value::UnsignedInt(val) => album_tracks.track_number = val,
 
                     Value::Binary(_) => {
@@ -409,9 +1781,11 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                     }
                 },
                 StandardTagKey::TrackTotal => match tag.value {
-                    Value::String(val) => {
-                        album.num_of_tracks = val.parse::<u64>().unwrap();
-                    }
+                    Value::String(val) => match parse_number(&val) {
+                        Some(number) => album.num_of_tracks = number,
+                        None => problems.push(fl!("InvalidTrackTotal", value = val)),
+                    },
+                    Value::UnsignedInt(val) => album.num_of_tracks = val,
                     _ => {
                         // log::error!("Track number is not a number");
                     }
@@ -434,222 +1808,452 @@ pub async fn create_database_entry(metadata_tags: Vec<Tag>, filepath: &PathBuf)
                 StandardTagKey::Version => {}
                 StandardTagKey::Writer => {}
             }
+        } else {
+            // Tags without a standard key, as `WORK` or the ID3 `TXXX:WORK`
+            let key = tag
+                .key
+                .rsplit(':')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            match (key.as_str(), tag.value) {
+                ("work", Value::String(val)) => track.work = Some(val),
+                ("movementname", Value::String(val)) => track.movement_name = Some(val),
+                ("movement" | "movementnumber", Value::String(val)) => {
+                    track.movement_number = parse_number(&val)
+                }
+                // Written by Picard to ID3 and by some taggers to Vorbis comments
+                ("originaldate" | "originalyear", value) => {
+                    if let Some(val) = parse_date(&value.to_string()) {
+                        album.original_date.get_or_insert(val);
+                    }
+                }
+                ("releasedate", value) => {
+                    if let Some(val) = parse_date(&value.to_string()) {
+                        release_date.get_or_insert(val);
+                    }
+                }
+                // ID3 keeps them in `TXXX` frames
+                ("replaygain_track_gain", value) => {
+                    track.replay_gain.track_gain = parse_gain(&value.to_string())
+                }
+                ("replaygain_track_peak", value) => {
+                    track.replay_gain.track_peak = parse_gain(&value.to_string())
+                }
+                ("replaygain_album_gain", value) => {
+                    track.replay_gain.album_gain = parse_gain(&value.to_string())
+                }
+                ("replaygain_album_peak", value) => {
+                    track.replay_gain.album_peak = parse_gain(&value.to_string())
+                }
+                // Opus files carry R128 gains instead, Q7.8 numbers relative to -23 LUFS, which
+                // is 5 dB below the ReplayGain reference
+                ("r128_track_gain", value) => {
+                    track.replay_gain.track_gain = r128_gain(&value.to_string())
+                }
+                ("r128_album_gain", value) => {
+                    track.replay_gain.album_gain = r128_gain(&value.to_string())
+                }
+                ("year", value) => {
+                    if let Some(val) = parse_date(&value.to_string()) {
+                        date.get_or_insert(val);
+                    }
+                }
+                // Written by foobar2000 and some lyrics fetchers to Vorbis comments
+                ("unsyncedlyrics", Value::String(val)) if !val.trim().is_empty() => {
+                    track.lyrics.get_or_insert(val);
+                }
+                _ => {}
+            }
         }
     }
 
+    if track.work.is_none() {
+        track.work = content_group;
+    }
+    // A sidecar file is usually synced, which embedded lyrics rarely are
+    if let Some(lyrics) = sidecar_lyrics(filepath) {
+        track.lyrics = Some(lyrics);
+    }
+    album.release_date = release_date.or(date);
 
+    // The path only fills in what the tags left out
+    if let Some(path_tags) = path_tags {
+        if track.name.is_none() {
+            track.name = path_tags.title;
+        }
+        if artist.name.is_none() {
+            artist.name = path_tags.artist.or(path_tags.album_artist.clone());
+        }
+        if album_artist.is_none() {
+            album_artist = path_tags.album_artist;
+        }
+        if album.name.is_empty() {
+            album.name = path_tags.album.unwrap_or_default();
+        }
+        if album_tracks.track_number == 0 {
+            album_tracks.track_number = path_tags.track_number.unwrap_or(0);
+        }
+        if album_tracks.disc_number == 0 {
+            album_tracks.disc_number = path_tags.disc_number.unwrap_or(0);
+        }
+    }
 
+    if album_artist
+        .as_deref()
+        .is_some_and(|name| name.trim().eq_ignore_ascii_case(VARIOUS_ARTISTS))
+    {
+        album.compilation = true;
+    }
 
-    log::info!(" {} BY {} IN {}", &track.name.as_ref().unwrap().on_blue(),  &artist.name.as_ref().unwrap().on_bright_yellow().black(), &album.name.on_bright_blue());
+    if !credit_tags
+        .iter()
+        .any(|(_, role)| *role == ArtistRole::Primary)
+    {
+        if let Some(name) = &artist.name {
+            credit_tags.push((name.clone(), ArtistRole::Primary));
+        }
+    }
 
-    match artist.name {
-        Some(name) => match conn.execute("INSERT INTO artists (name) VALUES (?)", [&name]) {
-            Ok(_) => {
-                // log::info!("Added artist {} to artists", name);
-                artist.id = conn.last_insert_rowid() as u32;
+    let mut credits: Vec<(String, ArtistRole)> = vec![];
+    for (value, role) in credit_tags {
+        for credit in separators.split(&value, role) {
+            if !credits.contains(&credit) {
+                credits.push(credit);
             }
-            Err(_) => {
-                // log::warn!("Artist: {} already created", name);
-                artist.id = conn
-                    .query_row("SELECT id FROM artists WHERE name = ?", &[&name], |row| {
-                        row.get::<usize, u32>(0)
-                    })
-                    .unwrap() ;
-            }
-        },
-        None => {
-            // log::error!("No artist was found");
         }
     }
 
-    conn.execute(
-        "INSERT INTO track (name, path, artist_id) VALUES (?, ?, ?)",
-        (&track.name, filepath.to_str().unwrap(), artist.id),
-    )
-    .unwrap();
+    if track.name.is_none() {
+        problems.push(fl!("MissingTitle"));
+        track.name = filepath
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+    }
+    if artist.name.is_none() {
+        problems.push(fl!("MissingArtist"));
+    }
 
-    track.id = conn.last_insert_rowid() as u64;
+    track.genres = track
+        .genres
+        .map(|values| genre_options.split(&values))
+        .filter(|genres| !genres.is_empty());
+    let genre_parents = genre_options.lineage(track.genres.as_deref().unwrap_or_default());
 
-    if let Some(genres) = track.genres {
-        for genre in genres {
-            match conn.query_row(
-                "SELECT id FROM genres WHERE name = ?",
-                &[&genre.trim().to_string()],
-                |row| {
-                    let row_id = row.get::<usize, u32>(0).unwrap();
-
-                    log::info!(
-                        "Genre: {} to be inserted with track id: {}",
-                        genre,
-                        track.id
-                    );
-                    match conn.execute(
-                        "insert into track_genres (track_id, genre_id) values (?, ?)",
-                        [track.id, row_id as u64],
-                    ) {
-                        Ok(v) => {
-                            log::info!("TRACKID: {}", track.id);
-                            Ok(v)
-                        }
-                        Err(err) => {
-                            log::error!("error while inserting into genre_tracks: {}", err);
-                            Ok(3)
-                        }
-                    }
-                },
-            ) {
-                Ok(_) => {}
-                Err(err) => {
-                    log::error!("error while finding id from genre_name{}", err)
-                }
-            }
-        }
+    // Only album tracks and singles store a cover, files without an album title get none
+    let cover = match album.name.is_empty() {
+        true => None,
+        false => find_cover(filepath, cover_names).and_then(|image| artwork::store(&image)),
+    };
+
+    let signature = match fs::metadata(filepath) {
+        Ok(metadata) => file_signature(filepath, &metadata),
+        Err(_) => (0, 0),
+    };
+
+    ProbedTrack {
+        track,
+        album,
+        album_tracks,
+        artist,
+        credits,
+        album_artist,
+        cover,
+        genre_parents,
+        signature,
+        properties,
+        problems,
     }
+}
 
-    if album.name.is_empty() {
-        log::warn!("{}", "No album title in file mdat".red())
-    } else {
-        // Check how many albums exist with this name
-        let a_similar: Vec<(u32, Option<u32>)>;
-
-        if album.artist_id.is_some() {
-            let mut similar_albums = match conn
-                .prepare(
-                    "
-                select main.album.id as id, art.id as aid from main.album
-                    join main.artists art on main.album.artist_id = art.id
-                where main.album.name = ?
-            ",
-                ) {
-                Ok(val) => val,
-                Err(err) => {
-                    log::error!("{}", err);
-                    panic!("Faulty sql request")
-                },
-            };
+/// Reads the number in front of a `/`, tags like "3/12" carry the total as well
+fn parse_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.split('/').next()?.trim().parse().ok()
+}
 
-            let a_iter = similar_albums
-                .query_map(&[&album.name], |row| {
-                    Ok(
-                        (row.get::<&str, u32>("id").unwrap(),
-                         Some(row.get::<&str, u32>("aid").unwrap())))
-                })
-                .unwrap();
+/// Reads a gain like "-6.54 dB" or a peak like "0.988"
+fn parse_gain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or(value.strip_suffix("db"))
+        .unwrap_or(value);
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|gain: &f64| gain.is_finite())
+}
 
-            a_similar = a_iter.into_iter().filter_map(|a| a.ok()).collect::<Vec<(u32, Option<u32>)>>();
-        } else {
-            let mut similar_albums = match conn
-                .prepare(
-                    "
-                select main.album.id as id from main.album
-                where main.album.name = ?
-            ",
-                ) {
-                Ok(val) => val,
-                Err(err) => {
-                    log::error!("{}", err);
-                    panic!("Faulty sql request")
-                },
-            };
+fn r128_gain(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<i16>()
+        .ok()
+        .map(|gain| gain as f64 / 256.0 + 5.0)
+}
 
-            let a_iter = similar_albums
-                .query_map(&[&album.name], |row| {
-                    Ok(
-                        (row.get::<&str, u32>("id").unwrap(),
-                         None
-                        )
-                    )
-                })
-                .unwrap();
+/// Normalizes a date tag to `YYYY`, `YYYY-MM` or `YYYY-MM-DD`. Taggers write anything from a
+/// bare year to ID3 timestamps like "2003-04-05T12:00", a month or day that makes no sense is
+/// dropped rather than the whole date. Dates starting with the day only give their year.
+fn parse_date(value: &str) -> Option<String> {
+    let mut groups = value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|group| !group.is_empty());
+    let first = groups.next()?;
 
-            a_similar = a_iter.into_iter().filter_map(|a| a.ok()).collect::<Vec<(u32, Option<u32>)>>();
-        }
+    let (year, month, day) = match first.len() {
+        // "20030405"
+        8 => (&first[..4], first.get(4..6), first.get(6..8)),
+        4 => (first, groups.next(), groups.next()),
+        _ => (groups.find(|group| group.len() == 4)?, None, None),
+    };
+
+    let year: u32 = year.parse().ok().filter(|year| *year > 0)?;
+    let Some(month) = month
+        .and_then(|month| month.parse::<u32>().ok())
+        .filter(|month| (1..=12).contains(month))
+    else {
+        return Some(format!("{:04}", year));
+    };
+    match day
+        .and_then(|day| day.parse::<u32>().ok())
+        .filter(|day| (1..=31).contains(day))
+    {
+        Some(day) => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+        None => Some(format!("{:04}-{:02}", year, month)),
+    }
+}
 
+/// Year an album is listed under, of its first release when known
+fn album_year(album: &Album) -> Option<u32> {
+    album
+        .original_date
+        .as_ref()
+        .or(album.release_date.as_ref())?
+        .get(..4)?
+        .parse()
+        .ok()
+}
 
-        log::info!("Similarities: {}", a_similar.len());
+/// Inserts a probed file, replacing whatever was recorded for it before. Runs on the scan
+/// writer, which wraps batches of these in a transaction.
+pub fn write_track(
+    conn: &Connection,
+    probed: ProbedTrack,
+    filepath: &Path,
+    root: &Path,
+) -> rusqlite::Result<()> {
+    let ProbedTrack {
+        mut track,
+        mut album,
+        album_tracks,
+        artist,
+        credits,
+        album_artist,
+        cover,
+        genre_parents,
+        signature: (size, mtime),
+        properties,
+        problems: _,
+    } = probed;
 
-        if a_similar.len() > 0 {
-            // if at least one album already exists with the same name, compare artists to verify if it is actually the same album
-            let mut insertion = false;
-            for artist_id in a_similar {
-                    if artist_id.1.is_none() {
-                        // if there is no artist value associated, but there is an album with the same name, insert anyway it's probably correct
-                        album.id = artist_id.0;
-                        insertion = true;
+    // A changed file replaces whatever was recorded for it before
+    remove_track(conn, filepath)?;
 
-                    } else {
-                        if artist_id.1.unwrap() == album.artist_id.unwrap() as u32 {
-                            // if album name is the same and artist_id this is probably the correct album
-                            album.id = artist_id.0;
-                            insertion = true;
-                        }
-                    }
-            }
+    log::info!(
+        " {} BY {} IN {}",
+        track.name.as_deref().unwrap_or_default().on_blue(),
+        artist.name.as_deref().unwrap_or_default().on_bright_yellow().black(),
+        &album.name.on_bright_blue()
+    );
 
-            if insertion == false {
-                // if artist id does not match with any previous album entries, it probably is a different album
-                let image_dat = find_visual(filepath);
-                insert_track_to_grouping(&album, track.id, image_dat, &conn);
-                album.id = conn.last_insert_rowid() as u32
-            }
+    let mut credit_ids = Vec::with_capacity(credits.len());
+    for (name, role) in &credits {
+        credit_ids.push((artist_id(conn, name)?, *role));
+    }
 
-        } else {
-            // if there are no matching albums create a new one, or if there is only one track associated, assume it is a single
-            let image_dat = find_visual(filepath);
-            insert_track_to_grouping(&album, track.id, image_dat, &conn);
-            album.id = conn.last_insert_rowid() as u32
+    // The track itself belongs to its first primary artist
+    let track_artist = credit_ids
+        .iter()
+        .find(|(_, role)| *role == ArtistRole::Primary)
+        .map(|(id, _)| *id);
+
+    // Without an album artist tag the album is by the artist of the track, so albums of the
+    // same name by different artists stay apart
+    album.artist_id = match (album.compilation, album_artist) {
+        (true, _) => Some(artist_id(conn, VARIOUS_ARTISTS)?),
+        (false, Some(name)) => Some(artist_id(conn, &name)?),
+        (false, None) => track_artist,
+    };
+
+    conn.execute(
+        "
+        INSERT INTO track (name, path, artist_id, size, mtime, root, work, opus, movement_name,
+                           movement_number, part, duration, codec, sample_rate, bit_depth,
+                           channels, bitrate, track_gain, track_peak, album_gain, album_peak,
+                           lyrics)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ",
+        rusqlite::params![
+            &track.name,
+            filepath.to_string_lossy(),
+            track_artist,
+            size,
+            mtime,
+            root.to_string_lossy(),
+            &track.work,
+            &track.opus,
+            &track.movement_name,
+            track.movement_number,
+            &track.part,
+            properties.duration,
+            &properties.codec,
+            properties.sample_rate,
+            properties.bit_depth,
+            properties.channels,
+            properties.bitrate,
+            track.replay_gain.track_gain,
+            track.replay_gain.track_peak,
+            track.replay_gain.album_gain,
+            track.replay_gain.album_peak,
+            &track.lyrics,
+        ],
+    )?;
+
+    track.id = conn.last_insert_rowid() as u64;
+
+    for (position, (credited, role)) in credit_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT OR IGNORE INTO track_artists (track_id, artist_id, role, position) VALUES (?, ?, ?, ?)",
+            (track.id, credited, role.as_str(), position),
+        )?;
+    }
+
+    // Parents are set again with every track, so ones removed from the settings go away
+    for (genre, parent) in &genre_parents {
+        let parent_id = match parent {
+            Some(parent) => Some(genre_id(conn, parent)?),
+            None => None,
+        };
+        conn.execute(
+            "UPDATE genres SET parent_id = ? WHERE id = ?",
+            (parent_id, genre_id(conn, genre)?),
+        )?;
+    }
+
+    for genre in track.genres.iter().flatten() {
+        conn.execute(
+            "INSERT INTO track_genres (track_id, genre_id) VALUES (?, ?)",
+            (track.id, genre_id(conn, genre)?),
+        )?;
+    }
+
+    if album.name.is_empty() {
+        log::warn!("{}", "No album title in file mdat".red())
+    } else {
+        // An album is its title together with its album artist. Compilations are only ever
+        // grouped with each other.
+        let existing = conn
+            .query_row(
+                "SELECT id FROM album WHERE name = ?1 AND artist_id IS ?2 AND compilation = ?3",
+                (&album.name, &album.artist_id, album.compilation),
+                |row| row.get::<usize, u32>(0),
+            )
+            .optional()?;
+
+        match existing {
+            Some(id) => {
+                album.id = id;
+                // Dates are taken from the first track that has them
+                conn.execute(
+                    "UPDATE album SET release_date = ?2, original_date = ?3, year = ?4 WHERE id = ?1 AND year IS NULL",
+                    (id, &album.release_date, &album.original_date, album_year(&album)),
+                )?;
+                // The cover follows the tracks as they are probed again, so a cover picked
+                // before the front cover was looked for gets replaced
+                if let Some(cover) = &cover {
+                    conn.execute("UPDATE album SET artwork = ?2 WHERE id = ?1", (id, cover))?;
+                }
+            }
+            None => {
+                // if there are no matching albums create a new one, or if there is only one track associated, assume it is a single
+                album.id = insert_track_to_grouping(&album, track.id, cover, conn)?;
+            }
         }
 
         if album.num_of_tracks != 1 {
-            match conn.execute(
+            conn.execute(
                 "INSERT INTO album_tracks (album_id, track_id, track_number, disc_number) VALUES (?, ?, ?, ?)",
                 (&album.id, &track.id, &album_tracks.track_number, &album_tracks.disc_number),
-            ) {
-                Ok(_) => {
-
-                }
-                Err(err) => {
-                    log::error!("album_track insertion went wrong \n ------ \n  {}", err.to_string());
-                }
-            }
+            )?;
         }
     }
+
+    conn.execute(&format!("{TRACK_SEARCH_ROW} WHERE t.id = ?"), [track.id])?;
+
+    Ok(())
+}
+
+/// Row of the artist called `name`, created if it is not known yet
+fn artist_id(conn: &Connection, name: &str) -> rusqlite::Result<u32> {
+    let name = name.trim();
+    conn.execute(
+        "INSERT INTO artists (name) VALUES (?) ON CONFLICT(name) DO NOTHING",
+        [name],
+    )?;
+    conn.query_row("SELECT id FROM artists WHERE name = ?", [name], |row| {
+        row.get::<usize, u32>(0)
+    })
+}
+
+/// Row of the genre called `name`, matched ignoring case and created if it is not known yet
+fn genre_id(conn: &Connection, name: &str) -> rusqlite::Result<u32> {
+    conn.execute(
+        "INSERT INTO genres (name) VALUES (?) ON CONFLICT(name) DO NOTHING",
+        [name],
+    )?;
+    conn.query_row("SELECT id FROM genres WHERE name = ?", [name], |row| {
+        row.get::<usize, u32>(0)
+    })
 }
 
-fn insert_track_to_grouping(album: &Album, track_id: u64, image_dat: Option<Box<[u8]>>, conn: &Connection) {
+/// Adds the album the track is on, or the single it is when it is an album of one track, and
+/// returns its id
+fn insert_track_to_grouping(album: &Album, track_id: u64, artwork: Option<String>, conn: &Connection) -> rusqlite::Result<u32> {
     if album.num_of_tracks != 1 {
         //Album
-        match conn.execute(
-            "INSERT INTO album (name, disc_number, track_number, artist_id, album_cover) VALUES (?, ?, ?, ?, ?)",
-            (&album.name, &album.num_of_discs, &album.num_of_tracks, &album.artist_id, image_dat),
-        ) {
-            Ok(_) => {
-                log::info!("{}", "Successfully added ALBUM!".purple());
-            }
-            Err(err) => {
-                log::error!("{} \n ERROR: {}", "Failed to insert ALBUM".red(), err);
-
-            }
-        }
+        conn.execute(
+            "INSERT INTO album (name, disc_number, track_number, artist_id, artwork, compilation, release_date, original_date, year) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            (&album.name, &album.num_of_discs, &album.num_of_tracks, &album.artist_id, artwork, album.compilation, &album.release_date, &album.original_date, album_year(album)),
+        )?;
+        log::info!("{}", "Successfully added ALBUM!".purple());
     } else {
         //Single
-        match conn.execute(
-            "INSERT INTO single (track_id, cover) VALUES (?, ?)",
-            (&track_id, image_dat),
-        ) {
-            Ok(_) => {
-                log::info!("{}", "Successfully added SINGLE!".green());
-            }
-            Err(err) => {
-                log::error!("{} \n ERROR: {}", "Failed to insert SINGLE".red(), err);
-            }
-        }
+        conn.execute(
+            "INSERT INTO single (track_id, artwork) VALUES (?, ?)",
+            (&track_id, artwork),
+        )?;
+        log::info!("{}", "Successfully added SINGLE!".green());
     }
 
+    Ok(conn.last_insert_rowid() as u32)
 }
 
-pub fn find_visual(filepath: &PathBuf) -> Option<Box<[u8]>> {
-    let file = fs::File::open(filepath).unwrap();
+/// Cover of a file, its embedded front cover or else an image in its album folder
+pub fn find_cover(filepath: &Path, cover_names: &[String]) -> Option<Box<[u8]>> {
+    find_visual(filepath).or_else(|| folder_cover(filepath, cover_names))
+}
+
+/// Embedded picture that is most likely the front cover
+pub fn find_visual(filepath: &Path) -> Option<Box<[u8]>> {
+    let file = match fs::File::open(filepath) {
+        Ok(file) => file,
+        Err(err) => {
+            log::warn!("Could not open {} for its cover: {}", filepath.display(), err);
+            return None;
+        }
+    };
 
     let probe = get_probe();
     let mss = symphonia::core::io::MediaSourceStream::new(Box::new(file), Default::default());
@@ -662,30 +2266,848 @@ pub fn find_visual(filepath: &PathBuf) -> Option<Box<[u8]>> {
     ) {
         Ok(read) => read,
         Err(err) => {
-            panic!("{}", err.to_string());
+            log::warn!("Could not read the cover of {}: {}", filepath.display(), err);
+            return None;
         }
     };
 
-    if let Some(mdat_rev) = reader.metadata.get() {
-        if let Some(mdat_rev) = mdat_rev.current() {
-            match mdat_rev.visuals().get(0) {
-                Some(visual) => {
-                    // log::info!("This album contains visual data!");
-                    Some(visual.data.clone())
-                }
-                None => {
-                    // log::info!("This album contains no visual data!");
-                    None
-                }
-            }
-        } else {
-            None
-        }
-    } else {
-        if let Some(mdat_rev) = reader.format.metadata().current() {
-            Some(mdat_rev.visuals().get(0)?.data.clone())
-        } else {
-            None
+    // ID3 tags in front of a FLAC stream are read by the probe, the stream has its own
+    let mut visuals: Vec<Visual> = vec![];
+    if let Some(metadata) = reader.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            visuals.extend_from_slice(revision.visuals());
         }
     }
+    if let Some(revision) = reader.format.metadata().current() {
+        visuals.extend_from_slice(revision.visuals());
+    }
+
+    // The first of the best ranked pictures, back covers and disc scans only as a last resort
+    visuals
+        .iter()
+        .min_by_key(|visual| match visual.usage {
+            Some(StandardVisualKey::FrontCover) => 0,
+            // MP4 and plain Vorbis pictures have no usage, they are almost always the cover
+            None => 1,
+            Some(
+                StandardVisualKey::BackCover
+                | StandardVisualKey::Media
+                | StandardVisualKey::Leaflet
+                | StandardVisualKey::FileIcon
+                | StandardVisualKey::OtherIcon,
+            ) => 3,
+            Some(_) => 2,
+        })
+        .map(|visual| visual.data.clone())
+}
+
+/// `CD1`, `Disc 2` and the like
+fn is_disc_folder(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let number = ["cd", "disc", "disk"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .map(str::trim_start);
+
+    number.is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Image formats a folder cover can be in
+const COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+/// Image next to a file named like one of `cover_names`, ignoring case and extension. Files in
+/// a disc folder like `CD1` also look in the album folder above it.
+fn folder_cover(filepath: &Path, cover_names: &[String]) -> Option<Box<[u8]>> {
+    let dir = filepath.parent()?;
+    let album_dir = dir
+        .file_name()
+        .filter(|name| is_disc_folder(&name.to_string_lossy()))
+        .and_then(|_| dir.parent());
+
+    [Some(dir), album_dir]
+        .into_iter()
+        .flatten()
+        .find_map(|dir| {
+            let images = fs::read_dir(dir)
+                .ok()?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|extension| {
+                        COVER_EXTENSIONS
+                            .contains(&extension.to_string_lossy().to_lowercase().as_str())
+                    })
+                })
+                .collect::<Vec<PathBuf>>();
+
+            cover_names.iter().find_map(|name| {
+                images
+                    .iter()
+                    .filter(|image| {
+                        image
+                            .file_stem()
+                            .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(name))
+                    })
+                    .find_map(|image| fs::read(image).ok())
+            })
+        })
+        .map(Vec::into_boxed_slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::Channels;
+    use symphonia::core::codecs::CODEC_TYPE_FLAC;
+    use symphonia::core::units::TimeBase;
+
+    /// Empty database at the latest schema
+    fn test_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrate_database(&conn).unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, table: &str) -> u32 {
+        conn.query_row(&format!("SELECT count(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn removing_a_track_keeps_its_album_until_the_last_one() {
+        let conn = test_database();
+        conn.execute_batch(
+            "
+            INSERT INTO album (id, name) VALUES (1, 'Abbey Road');
+            INSERT INTO track (id, name, path) VALUES
+                (1, 'Come Together', '/music/1.flac'),
+                (2, 'Something', '/music/2.flac');
+            INSERT INTO album_tracks (album_id, track_id) VALUES (1, 1), (1, 2);
+            INSERT INTO genres (id, name) VALUES (1, 'Rock');
+            INSERT INTO track_genres (track_id, genre_id) VALUES (1, 1), (2, 1);
+            ",
+        )
+        .unwrap();
+
+        remove_track(&conn, Path::new("/music/1.flac")).unwrap();
+        assert_eq!(count(&conn, "track"), 1);
+        assert_eq!(count(&conn, "album"), 1);
+        assert_eq!(count(&conn, "album_tracks"), 1);
+        assert_eq!(count(&conn, "track_genres"), 1);
+
+        remove_track(&conn, Path::new("/music/2.flac")).unwrap();
+        assert_eq!(count(&conn, "track"), 0);
+        assert_eq!(count(&conn, "album"), 0);
+        assert_eq!(count(&conn, "album_tracks"), 0);
+        assert_eq!(count(&conn, "track_genres"), 0);
+        assert_eq!(count(&conn, "genres"), 1);
+    }
+
+    #[test]
+    fn a_track_that_can_not_be_removed_keeps_its_album() {
+        let conn = test_database();
+        conn.execute_batch(
+            "
+            INSERT INTO album (id, name) VALUES (1, 'Abbey Road');
+            INSERT INTO track (id, name, path) VALUES (1, 'Come Together', '/music/1.flac');
+            INSERT INTO album_tracks (album_id, track_id) VALUES (1, 1);
+            CREATE TEMP TRIGGER kept BEFORE DELETE ON track BEGIN
+                SELECT RAISE(ABORT, 'kept');
+            END;
+            ",
+        )
+        .unwrap();
+
+        assert!(remove_track(&conn, Path::new("/music/1.flac")).is_err());
+        assert_eq!(count(&conn, "track"), 1);
+        assert_eq!(count(&conn, "album"), 1);
+        assert_eq!(count(&conn, "album_tracks"), 1);
+        // The savepoint is gone, so the next removal starts afresh
+        conn.execute_batch("DROP TRIGGER kept").unwrap();
+        remove_track(&conn, Path::new("/music/1.flac")).unwrap();
+        assert_eq!(count(&conn, "album"), 0);
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn migrations_repair_the_unversioned_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        // As the tables were created before the schema was versioned, with foreign keys
+        // pointing at `tracks` and `artist` that were never enforced
+        conn.execute_batch(
+            "
+            CREATE TABLE artists (id INTEGER PRIMARY KEY, name TEXT UNIQUE, artistpfp BLOB);
+            CREATE TABLE genres (id INTEGER PRIMARY KEY, name TEXT UNIQUE);
+            CREATE TABLE track_genres (
+                id INTEGER PRIMARY KEY, track_id INTEGER, genre_id INTEGER,
+                FOREIGN KEY(genre_id) REFERENCES genres(id)
+            );
+            CREATE TABLE album_tracks (
+                id INTEGER PRIMARY KEY, album_id INTEGER, track_id INTEGER,
+                track_number INTEGER, disc_number INTEGER,
+                FOREIGN KEY(album_id) REFERENCES album(id),
+                FOREIGN KEY(track_id) REFERENCES tracks(id)
+            );
+            CREATE TABLE track (
+                id INTEGER PRIMARY KEY, name TEXT, path TEXT UNIQUE, artist_id INTEGER,
+                size INTEGER, mtime INTEGER,
+                FOREIGN KEY(artist_id) REFERENCES artist(id)
+            );
+            CREATE TABLE album (
+                id INTEGER PRIMARY KEY, name TEXT, artist_id INTEGER, disc_number INTEGER,
+                track_number INTEGER, album_cover BLOB,
+                FOREIGN KEY(artist_id) REFERENCES artist(id)
+            );
+            CREATE TABLE single (
+                id INTEGER PRIMARY KEY, track_id INTEGER, cover BLOB,
+                FOREIGN KEY(track_id) REFERENCES tracks(id)
+            );
+
+            INSERT INTO artists (id, name) VALUES (1, 'The Beatles');
+            INSERT INTO genres (id, name) VALUES (1, 'Rock');
+            INSERT INTO track (id, name, path, artist_id, size, mtime) VALUES
+                (1, 'Come Together', '/music/1.flac', 1, 100, 10),
+                (2, 'Something', '/music/2.flac', 7, 100, 10),
+                (3, 'Her Majesty', '/music/3.flac', 1, 100, 10);
+            INSERT INTO album (id, name, artist_id) VALUES (1, 'Abbey Road', 1), (2, 'Let It Be', 9);
+            INSERT INTO album_tracks (album_id, track_id, track_number) VALUES
+                (1, 1, 1), (1, 2, 2), (1, 9, 3), (5, 1, 1);
+            INSERT INTO single (track_id) VALUES (3), (8);
+            INSERT INTO track_genres (track_id, genre_id) VALUES (1, 1), (9, 1), (1, 4);
+            ",
+        )
+        .unwrap();
+
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrate_database(&conn).unwrap();
+
+        assert_eq!(count(&conn, "artists"), 1);
+        assert_eq!(count(&conn, "track"), 3);
+        assert_eq!(count(&conn, "album"), 2);
+        assert_eq!(count(&conn, "album_tracks"), 2);
+        assert_eq!(count(&conn, "single"), 1);
+        let artist = |table: &str, id: u32| -> Option<u32> {
+            conn.query_row(
+                &format!("SELECT artist_id FROM {table} WHERE id = ?"),
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(artist("track", 1), Some(1));
+        assert_eq!(artist("track", 2), None);
+        assert_eq!(artist("album", 2), None);
+
+        let violations = conn
+            .prepare("PRAGMA foreign_key_check")
+            .unwrap()
+            .query_map([], |_| Ok(()))
+            .unwrap()
+            .count();
+        assert_eq!(violations, 0);
+
+        let version = || -> usize {
+            conn.query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(version(), MIGRATIONS.len());
+        migrate_database(&conn).unwrap();
+        assert_eq!(version(), MIGRATIONS.len());
+        assert_eq!(count(&conn, "track"), 3);
+    }
+
+    fn path_tags(pattern: &str, path: &str) -> PathTags {
+        PathPattern::new(pattern)
+            .unwrap()
+            .apply(Path::new("/music"), Path::new(path))
+    }
+
+    #[test]
+    fn path_pattern_fills_in_tags() {
+        let tags = path_tags(
+            "{albumartist}/{album}/{track} - {title}",
+            "/music/Pink Floyd/The Wall/03 - Another Brick in the Wall.flac",
+        );
+        assert_eq!(tags.album_artist.as_deref(), Some("Pink Floyd"));
+        assert_eq!(tags.album.as_deref(), Some("The Wall"));
+        assert_eq!(tags.track_number, Some(3));
+        assert_eq!(tags.title.as_deref(), Some("Another Brick in the Wall"));
+        assert_eq!(tags.artist, None);
+        assert_eq!(tags.disc_number, None);
+    }
+
+    #[test]
+    fn path_pattern_matches_the_end_of_the_path() {
+        let tags = path_tags(
+            "/{artist}/{album}/{disc}-{track} {title}/",
+            "/music/Rock/Queen/Jazz/1-05 Bicycle Race.mp3",
+        );
+        assert_eq!(tags.artist.as_deref(), Some("Queen"));
+        assert_eq!(tags.album.as_deref(), Some("Jazz"));
+        assert_eq!(tags.disc_number, Some(1));
+        assert_eq!(tags.track_number, Some(5));
+        assert_eq!(tags.title.as_deref(), Some("Bicycle Race"));
+
+        let tags = path_tags("{artist}/{album}/{track} {title}", "/music/loose.mp3");
+        assert_eq!(tags.artist, None);
+        assert_eq!(tags.title, None);
+    }
+
+    #[test]
+    fn path_pattern_keeps_other_text_literal() {
+        let tags = path_tags(
+            "[{album}] {live}/{title}",
+            "/music/[Alive] {live}/Intro (Edit).ogg",
+        );
+        assert_eq!(tags.album.as_deref(), Some("Alive"));
+        assert_eq!(tags.title.as_deref(), Some("Intro (Edit)"));
+
+        let tags = path_tags("{track} {title}", "/music/Intro.ogg");
+        assert_eq!(tags.track_number, None);
+        assert_eq!(tags.title, None);
+
+        assert!(PathPattern::new(" / ").is_none());
+    }
+
+    fn tag(key: StandardTagKey, value: &str) -> Tag {
+        Tag::new(Some(key), "", Value::from(value))
+    }
+
+    #[test]
+    fn default_separators_keep_duos_together() {
+        use crate::config::{DEFAULT_ARTIST_SEPARATORS, DEFAULT_FEATURING_SEPARATORS};
+
+        let words = |separators: &str| {
+            separators
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<String>>()
+        };
+        let separators = ArtistSeparators::new(
+            &words(DEFAULT_ARTIST_SEPARATORS),
+            &words(DEFAULT_FEATURING_SEPARATORS),
+        );
+        assert_eq!(
+            separators.split("Simon & Garfunkel", ArtistRole::Primary),
+            credits(&[("Simon & Garfunkel", ArtistRole::Primary)])
+        );
+        assert_eq!(
+            separators.split(
+                "Simon & Garfunkel; Paul Simon feat. Art Garfunkel",
+                ArtistRole::Primary
+            ),
+            credits(&[
+                ("Simon & Garfunkel", ArtistRole::Primary),
+                ("Paul Simon", ArtistRole::Primary),
+                ("Art Garfunkel", ArtistRole::Featured),
+            ])
+        );
+    }
+
+    /// Tag without a standard key, as `TXXX:WORK`
+    fn custom_tag(key: &str, value: &str) -> Tag {
+        Tag::new(None, key, Value::from(value))
+    }
+
+    fn probe(path: &str, tags: Vec<Tag>) -> ProbedTrack {
+        read_tags(
+            tags,
+            &PathBuf::from(path),
+            None,
+            &ArtistSeparators::default(),
+            &GenreOptions::default(),
+            &[],
+            AudioProperties::default(),
+        )
+    }
+
+    fn add(conn: &Connection, path: &str, tags: Vec<Tag>) {
+        write_track(
+            conn,
+            probe(path, tags),
+            Path::new(path),
+            Path::new("/music"),
+        )
+        .unwrap();
+    }
+
+    fn album_of(conn: &Connection, path: &str) -> (u32, Option<String>) {
+        conn.query_row(
+            "SELECT al.id, a.name
+             FROM track t
+                join album_tracks at on at.track_id = t.id
+                join album al on al.id = at.album_id
+                left join artists a on a.id = al.artist_id
+             WHERE t.path = ?",
+            [path],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn albums_are_told_apart_by_their_album_artist() {
+        let conn = test_database();
+        let hits = |artist: &str| {
+            vec![
+                tag(StandardTagKey::Album, "Greatest Hits"),
+                tag(StandardTagKey::Artist, artist),
+                tag(StandardTagKey::AlbumArtist, artist),
+            ]
+        };
+        add(&conn, "/music/queen/1.flac", hits("Queen"));
+        add(&conn, "/music/queen/2.flac", hits("Queen"));
+        add(&conn, "/music/abba/1.flac", hits("ABBA"));
+
+        let (queen, artist) = album_of(&conn, "/music/queen/1.flac");
+        assert_eq!(artist.as_deref(), Some("Queen"));
+        assert_eq!(album_of(&conn, "/music/queen/2.flac").0, queen);
+        let (abba, artist) = album_of(&conn, "/music/abba/1.flac");
+        assert_ne!(abba, queen);
+        assert_eq!(artist.as_deref(), Some("ABBA"));
+    }
+
+    #[test]
+    fn compilations_are_grouped_under_various_artists() {
+        let conn = test_database();
+        add(
+            &conn,
+            "/music/queen/1.flac",
+            vec![
+                tag(StandardTagKey::Album, "Greatest Hits"),
+                tag(StandardTagKey::Artist, "Queen"),
+                tag(StandardTagKey::AlbumArtist, "Queen"),
+            ],
+        );
+        add(
+            &conn,
+            "/music/hits/1.flac",
+            vec![
+                tag(StandardTagKey::Album, "Greatest Hits"),
+                tag(StandardTagKey::Artist, "Queen"),
+                tag(StandardTagKey::Compilation, "1"),
+            ],
+        );
+        add(
+            &conn,
+            "/music/hits/2.flac",
+            vec![
+                tag(StandardTagKey::Album, "Greatest Hits"),
+                tag(StandardTagKey::Artist, "Blondie"),
+                tag(StandardTagKey::AlbumArtist, "various artists"),
+            ],
+        );
+
+        let (compilation, artist) = album_of(&conn, "/music/hits/1.flac");
+        assert_eq!(artist.as_deref(), Some(VARIOUS_ARTISTS));
+        assert_eq!(album_of(&conn, "/music/hits/2.flac").0, compilation);
+        assert_ne!(album_of(&conn, "/music/queen/1.flac").0, compilation);
+        assert_eq!(count(&conn, "album"), 2);
+    }
+
+    #[test]
+    fn tracks_whose_album_can_not_be_added_fail_to_write() {
+        let conn = test_database();
+        conn.execute_batch(
+            "
+            CREATE TEMP TRIGGER no_albums BEFORE INSERT ON album BEGIN
+                SELECT RAISE(ABORT, 'no albums');
+            END;
+            ",
+        )
+        .unwrap();
+
+        let written = write_track(
+            &conn,
+            probe(
+                "/music/1.flac",
+                vec![
+                    tag(StandardTagKey::Album, "Abbey Road"),
+                    tag(StandardTagKey::Artist, "The Beatles"),
+                ],
+            ),
+            Path::new("/music/1.flac"),
+            Path::new("/music"),
+        );
+        assert!(written.is_err());
+        assert_eq!(count(&conn, "album_tracks"), 0);
+    }
+
+    fn credits(credits: &[(&str, ArtistRole)]) -> Vec<(String, ArtistRole)> {
+        credits
+            .iter()
+            .map(|(name, role)| (name.to_string(), *role))
+            .collect()
+    }
+
+    #[test]
+    fn artist_tags_are_split_into_credits() {
+        let separators = ArtistSeparators::new(
+            &[";".to_string(), "&".to_string(), "vs".to_string()],
+            &["feat.".to_string(), "ft.".to_string()],
+        );
+        assert_eq!(
+            separators.split("A & B feat. C ft. D", ArtistRole::Primary),
+            credits(&[
+                ("A", ArtistRole::Primary),
+                ("B", ArtistRole::Primary),
+                ("C", ArtistRole::Featured),
+                ("D", ArtistRole::Featured),
+            ])
+        );
+        assert_eq!(
+            separators.split("Daft Punk;Elvis vs JXL (Feat. Devs)", ArtistRole::Primary),
+            credits(&[
+                ("Daft Punk", ArtistRole::Primary),
+                ("Elvis", ArtistRole::Primary),
+                ("JXL", ArtistRole::Primary),
+                ("Devs", ArtistRole::Featured),
+            ])
+        );
+        assert_eq!(
+            ArtistSeparators::default().split("Bach\0Handel", ArtistRole::Composer),
+            credits(&[
+                ("Bach", ArtistRole::Composer),
+                ("Handel", ArtistRole::Composer)
+            ])
+        );
+        assert_eq!(
+            separators.split("Tiësto ft. Karen", ArtistRole::Remixer),
+            credits(&[
+                ("Tiësto", ArtistRole::Remixer),
+                ("Karen", ArtistRole::Remixer)
+            ])
+        );
+    }
+
+    #[test]
+    fn classical_tags_are_read() {
+        let probed = probe(
+            "/music/5.flac",
+            vec![
+                tag(StandardTagKey::Artist, "Berliner Philharmoniker"),
+                tag(StandardTagKey::Composer, "Ludwig van Beethoven"),
+                tag(StandardTagKey::Conductor, "Herbert von Karajan"),
+                tag(StandardTagKey::ContentGroup, "Symphonies"),
+                custom_tag("TXXX:WORK", "Symphony No. 5"),
+                tag(StandardTagKey::MovementNumber, "2/4"),
+                tag(StandardTagKey::MovementName, "Andante con moto"),
+                tag(StandardTagKey::Opus, "Op. 67"),
+            ],
+        );
+        assert_eq!(probed.track.work.as_deref(), Some("Symphony No. 5"));
+        assert_eq!(probed.track.movement_number, Some(2));
+        assert_eq!(
+            probed.track.movement_name.as_deref(),
+            Some("Andante con moto")
+        );
+        assert_eq!(probed.track.opus.as_deref(), Some("Op. 67"));
+        assert_eq!(
+            probed.credits,
+            credits(&[
+                ("Berliner Philharmoniker", ArtistRole::Primary),
+                ("Ludwig van Beethoven", ArtistRole::Composer),
+                ("Herbert von Karajan", ArtistRole::Conductor),
+            ])
+        );
+
+        let probed = probe(
+            "/music/6.flac",
+            vec![
+                tag(StandardTagKey::ContentGroup, "Symphony No. 6"),
+                custom_tag("MOVEMENT", "3"),
+            ],
+        );
+        assert_eq!(probed.track.work.as_deref(), Some("Symphony No. 6"));
+        assert_eq!(probed.track.movement_number, Some(3));
+    }
+
+    #[test]
+    fn dates_are_normalized() {
+        assert_eq!(parse_date("1977").as_deref(), Some("1977"));
+        assert_eq!(parse_date("1977-10").as_deref(), Some("1977-10"));
+        assert_eq!(parse_date("1977/10/28").as_deref(), Some("1977-10-28"));
+        assert_eq!(
+            parse_date("1977-10-28T12:00").as_deref(),
+            Some("1977-10-28")
+        );
+        assert_eq!(parse_date("19771028").as_deref(), Some("1977-10-28"));
+        assert_eq!(parse_date(" 1977 (Remaster 2011)").as_deref(), Some("1977"));
+    }
+
+    #[test]
+    fn dates_drop_what_makes_no_sense() {
+        assert_eq!(parse_date("1977-13-28").as_deref(), Some("1977"));
+        assert_eq!(parse_date("1977-10-32").as_deref(), Some("1977-10"));
+        assert_eq!(parse_date("1977-00").as_deref(), Some("1977"));
+        // Day first only gives the year, 28/10 and 10/28 can not be told apart
+        assert_eq!(parse_date("28/10/1977").as_deref(), Some("1977"));
+        assert_eq!(parse_date("5.4.1977").as_deref(), Some("1977"));
+        assert_eq!(parse_date("0000"), None);
+        assert_eq!(parse_date("10/28/77"), None);
+        assert_eq!(parse_date("unknown"), None);
+        assert_eq!(parse_date(""), None);
+    }
+
+    #[test]
+    fn audio_properties_come_from_the_stream() {
+        let mut params = CodecParameters::new();
+        params
+            .for_codec(CODEC_TYPE_FLAC)
+            .with_sample_rate(44100)
+            .with_time_base(TimeBase::new(1, 44100))
+            .with_n_frames(44100 * 180)
+            .with_bits_per_sample(16)
+            .with_channels(Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+
+        let properties = AudioProperties::new(&params, 20_000_000);
+        assert_eq!(properties.duration, Some(180.0));
+        assert_eq!(properties.codec.as_deref(), Some("flac"));
+        assert_eq!(properties.sample_rate, Some(44100));
+        assert_eq!(properties.bit_depth, Some(16));
+        assert_eq!(properties.channels, Some(2));
+        assert_eq!(properties.bitrate, Some(889));
+    }
+
+    #[test]
+    fn audio_properties_without_a_time_base_use_the_sample_rate() {
+        let mut params = CodecParameters::new();
+        params.with_sample_rate(48000).with_n_frames(96000);
+        let properties = AudioProperties::new(&params, 64_000);
+        assert_eq!(properties.duration, Some(2.0));
+        assert_eq!(properties.bitrate, Some(256));
+        assert_eq!(properties.codec, None);
+
+        let properties = AudioProperties::new(&CodecParameters::new(), 64_000);
+        assert_eq!(properties.duration, None);
+        assert_eq!(properties.bitrate, None);
+    }
+
+    #[test]
+    fn disc_folders_are_recognized() {
+        assert!(is_disc_folder("CD1"));
+        assert!(is_disc_folder("Disc 2"));
+        assert!(is_disc_folder("disk03"));
+        assert!(!is_disc_folder("CD"));
+        assert!(!is_disc_folder("Disco 2"));
+        assert!(!is_disc_folder("Discography"));
+    }
+
+    #[test]
+    fn folder_covers_are_found_by_name() {
+        let album = std::env::temp_dir().join(format!("nova_cover_test_{}", std::process::id()));
+        let disc = album.join("CD1");
+        fs::create_dir_all(&disc).unwrap();
+        fs::write(album.join("Cover.JPG"), "cover").unwrap();
+        fs::write(album.join("folder.png"), "folder").unwrap();
+        fs::write(album.join("front.txt"), "notes").unwrap();
+
+        let cover = |file: PathBuf, names: &[&str]| {
+            let names = names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>();
+            folder_cover(&file, &names).map(|image| image.to_vec())
+        };
+        assert_eq!(
+            cover(album.join("01.flac"), &["folder", "cover"]),
+            Some(b"folder".to_vec())
+        );
+        assert_eq!(
+            cover(album.join("01.flac"), &["front", "cover"]),
+            Some(b"cover".to_vec())
+        );
+        assert_eq!(cover(album.join("01.flac"), &["front"]), None);
+        assert_eq!(
+            cover(disc.join("01.flac"), &["cover"]),
+            Some(b"cover".to_vec())
+        );
+
+        fs::write(disc.join("cover.webp"), "disc").unwrap();
+        assert_eq!(
+            cover(disc.join("01.flac"), &["cover"]),
+            Some(b"disc".to_vec())
+        );
+
+        fs::remove_dir_all(&album).unwrap();
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(genre, to)| (genre.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn genres_are_cleaned() {
+        assert_eq!(clean_genre("  hip   hop "), "Hip Hop");
+        assert_eq!(clean_genre("drum-n-bass/jungle"), "Drum-N-Bass/Jungle");
+        assert_eq!(clean_genre("R&B"), "R&B");
+        assert_eq!(clean_genre("EDM"), "EDM");
+        assert_eq!(genre_key(" Hip  HOP"), "hip hop");
+    }
+
+    #[test]
+    fn genres_are_split_and_aliased() {
+        let options = GenreOptions::new(
+            &[";".to_string(), "/".to_string()],
+            &pairs(&[("Hip-Hop", "hip hop"), ("hiphop", "Hip Hop"), ("", "Pop")]),
+            &[],
+        );
+        assert_eq!(
+            options.split(&[
+                "rock; HIPHOP / Hip-Hop".to_string(),
+                "Rock\0Trip Hop".to_string(),
+                " ; ".to_string(),
+            ]),
+            ["Rock", "Hip Hop", "Trip Hop"]
+        );
+    }
+
+    #[test]
+    fn genre_lineage_stops_at_cycles() {
+        let options = GenreOptions::new(
+            &[],
+            &[],
+            &pairs(&[
+                ("trip hop", "electronic"),
+                ("Electronic", "Trip Hop"),
+                ("Techno", "Electronic"),
+            ]),
+        );
+        assert_eq!(
+            options.lineage(&["Trip Hop".to_string()]),
+            [
+                ("Trip Hop".to_string(), Some("Electronic".to_string())),
+                ("Electronic".to_string(), Some("Trip Hop".to_string())),
+            ]
+        );
+        assert_eq!(
+            options.lineage(&["Rock".to_string()]),
+            [("Rock".to_string(), None)]
+        );
+    }
+
+    fn search(conn: &Connection, table: &str, query: &str) -> Vec<u32> {
+        conn.prepare(&format!(
+            "SELECT rowid FROM {table} WHERE {table} MATCH ? ORDER BY rank"
+        ))
+        .unwrap()
+        .query_map([query], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<Vec<u32>>>()
+        .unwrap()
+    }
+
+    fn track_id(conn: &Connection, path: &str) -> u32 {
+        conn.query_row("SELECT id FROM track WHERE path = ?", [path], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn track_search_ranks_titles_above_genres_and_lyrics() {
+        let conn = test_database();
+        add(
+            &conn,
+            "/music/1.flac",
+            vec![
+                tag(StandardTagKey::TrackTitle, "Yesterday"),
+                custom_tag("UNSYNCEDLYRICS", "Love was such an easy game to play"),
+            ],
+        );
+        add(
+            &conn,
+            "/music/2.flac",
+            vec![
+                tag(StandardTagKey::TrackTitle, "Help"),
+                tag(StandardTagKey::Genre, "Love Songs"),
+            ],
+        );
+        add(
+            &conn,
+            "/music/3.flac",
+            vec![tag(StandardTagKey::TrackTitle, "Love Me Do")],
+        );
+
+        let ids =
+            ["/music/3.flac", "/music/2.flac", "/music/1.flac"].map(|path| track_id(&conn, path));
+        assert_eq!(search(&conn, "track_search", "love"), ids);
+    }
+
+    #[test]
+    fn search_ignores_accents_and_finds_word_starts() {
+        let conn = test_database();
+        add(
+            &conn,
+            "/music/1.flac",
+            vec![
+                tag(StandardTagKey::TrackTitle, "Déjà Vu"),
+                tag(StandardTagKey::Artist, "Beyoncé"),
+                tag(StandardTagKey::Album, "B'Day"),
+            ],
+        );
+        let id = track_id(&conn, "/music/1.flac");
+
+        assert_eq!(search(&conn, "track_search", "deja"), [id]);
+        assert_eq!(search(&conn, "track_search", "beyon*"), [id]);
+        assert_eq!(search(&conn, "track_search", "album:day"), [id]);
+        assert_eq!(search(&conn, "track_search", "title:beyonce"), []);
+        assert_eq!(search(&conn, "artist_search", "beyonce").len(), 1);
+        assert_eq!(search(&conn, "track_search", "vus"), []);
+    }
+
+    #[test]
+    fn albums_without_an_album_artist_belong_to_the_track_artist() {
+        let conn = test_database();
+        let hits = |artist: &str| {
+            vec![
+                tag(StandardTagKey::Album, "Greatest Hits"),
+                tag(StandardTagKey::Artist, artist),
+            ]
+        };
+        add(&conn, "/music/queen/1.flac", hits("Queen"));
+        add(&conn, "/music/queen/2.flac", hits("Queen"));
+        add(&conn, "/music/abba/1.flac", hits("ABBA"));
+
+        let (queen, artist) = album_of(&conn, "/music/queen/1.flac");
+        assert_eq!(artist.as_deref(), Some("Queen"));
+        assert_eq!(album_of(&conn, "/music/queen/2.flac").0, queen);
+        assert_ne!(album_of(&conn, "/music/abba/1.flac").0, queen);
+    }
+
+    #[test]
+    fn renamed_artists_albums_and_genres_are_searched_by_their_new_name() {
+        let conn = test_database();
+        add(
+            &conn,
+            "/music/1.flac",
+            vec![
+                tag(StandardTagKey::TrackTitle, "Purple Rain"),
+                tag(StandardTagKey::Artist, "Prince"),
+                tag(StandardTagKey::Album, "Soundtrack"),
+                tag(StandardTagKey::Genre, "Funk"),
+            ],
+        );
+        let id = track_id(&conn, "/music/1.flac");
+        conn.execute_batch(
+            "
+            UPDATE artists SET name = 'The Artist' WHERE name = 'Prince';
+            UPDATE album SET name = 'Purple Rain' WHERE name = 'Soundtrack';
+            UPDATE genres SET name = 'Minneapolis Sound' WHERE name = 'Funk';
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(search(&conn, "track_search", "artist:artist"), [id]);
+        assert_eq!(search(&conn, "track_search", "prince"), []);
+        assert_eq!(search(&conn, "track_search", "album:purple"), [id]);
+        assert_eq!(search(&conn, "track_search", "genre:minneapolis"), [id]);
+        assert_eq!(search(&conn, "track_search", "funk"), []);
+    }
 }
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Matching what was typed into a search field the way the full-text index does, ignoring case
+//! and accents, while forgiving a typo or two in longer words.
+
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Typos the longest words can have and still match
+pub const MAX_TYPOS: usize = 2;
+
+/// Lowercase and without accents, so "Sigur Rós" becomes "sigur ros"
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Folded words of `text`, punctuation separates them like it does in the index
+pub fn words(text: &str) -> Vec<String> {
+    tokens(text).map(|(_, token)| fold(token)).collect()
+}
+
+/// Short words have to be typed right, there are too many words a typo away from them
+fn typos_allowed(word: &str) -> usize {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => MAX_TYPOS,
+    }
+}
+
+/// Typos between `typed` and `word`, which may go on past what was typed. Both are folded.
+/// `None` when there are too many for them to be the same word.
+pub fn typos(typed: &str, word: &str) -> Option<usize> {
+    if word.starts_with(typed) {
+        return Some(0);
+    }
+
+    let prefix = word.chars().take(typed.chars().count()).collect::<String>();
+    let typos =
+        strsim::damerau_levenshtein(typed, word).min(strsim::damerau_levenshtein(typed, &prefix));
+    (typos <= typos_allowed(typed)).then_some(typos)
+}
+
+/// How far `text` is from having every one of `words` in it, in typos. `None` when one of them
+/// is not in it.
+pub fn score(words: &[String], text: &str) -> Option<usize> {
+    let tokens = self::words(text);
+    words.iter().try_fold(0, |total, word| {
+        tokens
+            .iter()
+            .filter_map(|token| typos(word, token))
+            .min()
+            .map(|typos| total + typos)
+    })
+}
+
+/// Byte ranges of `text` matching one of `words`, to highlight them. Only the typed part of a
+/// word is highlighted, all of it when it was matched with typos.
+pub fn highlights(words: &[String], text: &str) -> Vec<Range<usize>> {
+    if words.is_empty() {
+        return vec![];
+    }
+
+    tokens(text)
+        .filter_map(|(start, token)| {
+            let folded = fold(token);
+            let matched = words
+                .iter()
+                .filter_map(|word| match typos(word, &folded)? {
+                    0 => Some(word.chars().count()),
+                    _ => Some(folded.chars().count()),
+                })
+                .max()?;
+
+            // Folding can turn one character into several, so count them as folded
+            let mut length = 0;
+            let end = token
+                .char_indices()
+                .find_map(|(index, c)| {
+                    length += fold(&c.to_string()).chars().count();
+                    (length >= matched).then_some(index + c.len_utf8())
+                })
+                .unwrap_or(token.len());
+            Some(start..start + end)
+        })
+        .collect()
+}
+
+/// Runs of letters and digits in `text` with where they start
+fn tokens(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let in_word = |c: char| c.is_alphanumeric() || is_combining_mark(c);
+    text.char_indices()
+        .filter(move |(index, c)| {
+            in_word(*c) && !text[..*index].chars().next_back().is_some_and(in_word)
+        })
+        .map(move |(start, _)| {
+            let end = text[start..]
+                .char_indices()
+                .find(|(_, c)| !in_word(*c))
+                .map_or(text.len(), |(index, _)| start + index);
+            (start, &text[start..end])
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_case_and_accents() {
+        assert_eq!(fold("Sigur Rós"), "sigur ros");
+        assert_eq!(
+            words("AC/DC's Back-in Black"),
+            ["ac", "dc", "s", "back", "in", "black"]
+        );
+    }
+
+    #[test]
+    fn splits_tokens_where_they_start() {
+        assert_eq!(
+            tokens("Hello, wörld!").collect::<Vec<_>>(),
+            [(0, "Hello"), (7, "wörld")]
+        );
+        // A combining accent belongs to the letter before it
+        assert_eq!(
+            tokens("cafe\u{301} au").collect::<Vec<_>>(),
+            [(0, "cafe\u{301}"), (7, "au")]
+        );
+    }
+
+    #[test]
+    fn forgives_typos_in_longer_words() {
+        assert_eq!(typos("beat", "beatles"), Some(0));
+        assert_eq!(typos("beatels", "beatles"), Some(1));
+        assert_eq!(typos("beatlse", "beatles"), Some(1));
+        assert_eq!(typos("beatels", "beatlesque"), Some(1));
+        assert_eq!(typos("cut", "cat"), None);
+        assert_eq!(typos("radiohaed", "radiohead"), Some(1));
+        assert_eq!(typos("zeppelin", "beatles"), None);
+    }
+
+    #[test]
+    fn scores_every_word() {
+        let typed = words("beatels abbey");
+        assert_eq!(score(&typed, "Abbey Road by The Beatles"), Some(1));
+        assert_eq!(score(&typed, "Abbey Road"), None);
+        assert_eq!(score(&words("sigur ros"), "Sigur Rós"), Some(0));
+    }
+
+    #[test]
+    fn highlights_typed_part_or_whole_word() {
+        assert!(highlights(&[], "Sigur Rós").is_empty());
+        // Accented letters are longer in the text than folded
+        assert_eq!(highlights(&words("sig ros"), "Sigur Rós"), [0..3, 6..10]);
+        // Matched with a typo, all of the word is highlighted
+        assert_eq!(
+            highlights(&words("the beatels"), "The Beatles"),
+            [0..3, 4..11]
+        );
+    }
+
+    #[test]
+    fn highlights_characters_that_fold_into_several() {
+        // A hangul syllable is three letters once folded, typing it highlights just that one
+        let typed = words("한");
+        assert_eq!(typed[0].chars().count(), 3);
+        assert_eq!(highlights(&typed, "한국 한"), [0..3, 7..10]);
+    }
+}
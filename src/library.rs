@@ -0,0 +1,1152 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Read access to the library database for the UI. Every query goes through here so a broken
+//! or missing database ends up as a [`LibraryError`] instead of a panic.
+
+use crate::database::{open_connection, ArtistRole, AudioProperties, Loudness, ReplayGain};
+use crate::query::{Filter, NumberRange, Query, Text};
+use crate::{fl, fuzzy};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Connections kept open between queries, more are opened when several tasks query at once
+const POOL_SIZE: usize = 4;
+
+static POOL: Mutex<Vec<Connection>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+pub enum LibraryError {
+    Database(rusqlite::Error),
+    Io(std::io::Error),
+    AlbumNotFound { title: String, artist: String },
+    ArtistNotFound(String),
+    TrackNotFound(String),
+}
+
+impl Display for LibraryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            LibraryError::Database(err) => fl!("LibraryDatabaseError", error = err.to_string()),
+            LibraryError::Io(err) => fl!("LibraryIoError", error = err.to_string()),
+            LibraryError::AlbumNotFound { title, artist } => {
+                fl!(
+                    "AlbumNotFound",
+                    title = title.as_str(),
+                    artist = artist.as_str()
+                )
+            }
+            LibraryError::ArtistNotFound(name) => fl!("ArtistNotFound", name = name.as_str()),
+            LibraryError::TrackNotFound(track) => fl!("TrackNotFound", track = track.as_str()),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for LibraryError {}
+
+impl From<rusqlite::Error> for LibraryError {
+    fn from(err: rusqlite::Error) -> Self {
+        LibraryError::Database(err)
+    }
+}
+
+impl From<std::io::Error> for LibraryError {
+    fn from(err: std::io::Error) -> Self {
+        LibraryError::Io(err)
+    }
+}
+
+/// Runs `query` on a pooled connection, opening a new one when the pool is empty
+fn with_connection<T>(
+    query: impl FnOnce(&Connection) -> Result<T, LibraryError>,
+) -> Result<T, LibraryError> {
+    let pooled = POOL.lock().unwrap_or_else(|err| err.into_inner()).pop();
+    let conn = match pooled {
+        Some(conn) => conn,
+        None => open_connection()?,
+    };
+
+    let result = query(&conn);
+
+    let mut pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+    if pool.len() < POOL_SIZE {
+        pool.push(conn);
+    }
+
+    result
+}
+
+#[derive(Debug, Clone)]
+pub struct Album {
+    pub id: u32,
+    pub title: String,
+    pub artist: String,
+    pub disc_count: u32,
+    pub track_count: u32,
+    /// Hash of the cover in [`crate::artwork`]
+    pub cover: Option<String>,
+    /// Tracks are by different artists, the album artist is [`crate::database::VARIOUS_ARTISTS`]
+    pub compilation: bool,
+    /// Of the first release when the tags know it, otherwise of this one
+    pub year: Option<i32>,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, as precise as the tags were
+    pub release_date: Option<String>,
+    pub original_date: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: u32,
+    pub title: String,
+    pub artist: String,
+    pub album_title: String,
+    pub path: PathBuf,
+    pub track_number: u32,
+    pub disc_number: u32,
+    /// Of its album, or its own when it is a single
+    pub cover: Option<String>,
+    /// Classical work the track is a movement of
+    pub work: Option<String>,
+    pub opus: Option<String>,
+    pub movement_name: Option<String>,
+    pub movement_number: Option<u32>,
+    pub properties: AudioProperties,
+    /// Of the file in bytes
+    pub size: u64,
+    pub replay_gain: ReplayGain,
+}
+
+#[derive(Debug, Clone)]
+pub struct Single {
+    pub track_id: u32,
+    pub title: String,
+    pub artist: String,
+    pub cover: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtistPage {
+    pub name: String,
+    pub picture: Option<Vec<u8>>,
+    pub albums: Vec<Album>,
+    pub singles: Vec<Single>,
+    /// Albums by someone else with at least one track by this artist
+    pub appears_on: Vec<Album>,
+    /// Every track the artist is credited on, whatever for
+    pub credits: Vec<Credit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Credit {
+    pub track: Track,
+    /// In the order the tags listed them, never empty
+    pub roles: Vec<ArtistRole>,
+}
+
+/// Oldest first, undated albums after all the others
+const ALBUM_CHRONOLOGY: &str =
+    "a.year IS NULL, a.year, coalesce(a.original_date, a.release_date), a.name";
+
+fn album_from_row(row: &rusqlite::Row) -> rusqlite::Result<Album> {
+    Ok(Album {
+        id: row.get("id")?,
+        title: row.get::<_, Option<String>>("name")?.unwrap_or_default(),
+        artist: row.get::<_, Option<String>>("artist")?.unwrap_or_default(),
+        disc_count: row.get::<_, Option<u32>>("disc_number")?.unwrap_or(0),
+        track_count: row.get::<_, Option<u32>>("track_number")?.unwrap_or(0),
+        cover: row.get("artwork")?,
+        compilation: row.get("compilation")?,
+        year: row.get("year")?,
+        release_date: row.get("release_date")?,
+        original_date: row.get("original_date")?,
+    })
+}
+
+const TRACK_QUERY: &str = "
+    SELECT track.id, track.name, track.path, art.name as artist, a.name as album_title,
+           coalesce(a.artwork, s.artwork) as artwork, at.track_number, at.disc_number,
+           track.work, track.opus, track.movement_name, track.movement_number, track.duration,
+           track.codec, track.sample_rate, track.bit_depth, track.channels, track.bitrate,
+           track.size, track.track_gain, track.track_peak, track.album_gain, track.album_peak,
+           track.loudness, track.true_peak, a.loudness as album_loudness,
+           a.true_peak as album_true_peak
+    FROM track
+        left join album_tracks at on track.id = at.track_id
+        left join artists art on track.artist_id = art.id
+        left join album a on at.album_id = a.id
+        left join single s on s.track_id = track.id
+";
+
+fn track_from_row(row: &rusqlite::Row) -> rusqlite::Result<Track> {
+    Ok(Track {
+        id: row.get("id")?,
+        title: row.get::<_, Option<String>>("name")?.unwrap_or_default(),
+        artist: row.get::<_, Option<String>>("artist")?.unwrap_or_default(),
+        album_title: row
+            .get::<_, Option<String>>("album_title")?
+            .unwrap_or_default(),
+        path: PathBuf::from(row.get::<_, String>("path")?),
+        track_number: row.get::<_, Option<u32>>("track_number")?.unwrap_or(0),
+        disc_number: row.get::<_, Option<u32>>("disc_number")?.unwrap_or(0),
+        cover: row.get("artwork")?,
+        work: row.get("work")?,
+        opus: row.get("opus")?,
+        movement_name: row.get("movement_name")?,
+        movement_number: row.get("movement_number")?,
+        properties: AudioProperties {
+            duration: row.get("duration")?,
+            codec: row.get("codec")?,
+            sample_rate: row.get("sample_rate")?,
+            bit_depth: row.get("bit_depth")?,
+            channels: row.get("channels")?,
+            bitrate: row.get("bitrate")?,
+        },
+        size: row.get::<_, Option<u64>>("size")?.unwrap_or(0),
+        replay_gain: ReplayGain {
+            track_gain: row.get("track_gain")?,
+            track_peak: row.get("track_peak")?,
+            album_gain: row.get("album_gain")?,
+            album_peak: row.get("album_peak")?,
+        }
+        .with_analysis(
+            Loudness::from_columns(row.get("loudness")?, row.get("true_peak")?),
+            Loudness::from_columns(row.get("album_loudness")?, row.get("album_true_peak")?),
+        ),
+    })
+}
+
+/// An empty `artist` matches the album regardless of who it is by
+pub fn album_by_title_artist(title: &str, artist: &str) -> Result<Album, LibraryError> {
+    with_connection(|conn| {
+        conn.query_row(
+            "
+            SELECT album.id, album.name, art.name as artist, album.disc_number,
+                   album.track_number, album.artwork, album.compilation, album.year,
+                   album.release_date, album.original_date
+            FROM album
+                left join artists art on album.artist_id = art.id
+            WHERE album.name = ?1 and (?2 = '' or art.name = ?2)
+            ",
+            [title, artist],
+            album_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| LibraryError::AlbumNotFound {
+            title: title.to_string(),
+            artist: artist.to_string(),
+        })
+    })
+}
+
+/// Every album, in the order they were added to the library
+pub fn albums() -> Result<Vec<Album>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(
+            "
+            SELECT a.id, a.name, art.name as artist, a.disc_number, a.track_number, a.artwork,
+                   a.compilation, a.year, a.release_date, a.original_date
+            FROM album a
+                left join artists art on a.artist_id = art.id
+            ORDER BY a.id
+            ",
+        )?;
+        let albums = stmt
+            .query_map([], album_from_row)?
+            .collect::<rusqlite::Result<Vec<Album>>>()?;
+        Ok(albums)
+    })
+}
+
+/// Tracks of an album in disc and track order
+pub fn tracks_for_album(album_id: u32) -> Result<Vec<Track>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "{TRACK_QUERY} WHERE at.album_id = ? ORDER BY at.disc_number, at.track_number"
+        ))?;
+        let tracks = stmt
+            .query_map([album_id], track_from_row)?
+            .collect::<rusqlite::Result<Vec<Track>>>()?;
+        Ok(tracks)
+    })
+}
+
+pub fn artist_page(name: &str) -> Result<ArtistPage, LibraryError> {
+    with_connection(|conn| {
+        let picture = conn
+            .query_row(
+                "SELECT artistpfp FROM artists WHERE name = ?",
+                [name],
+                |row| row.get::<_, Option<Vec<u8>>>("artistpfp"),
+            )
+            .optional()?
+            .ok_or_else(|| LibraryError::ArtistNotFound(name.to_string()))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "
+            SELECT a.id, a.name, art.name as artist, a.disc_number, a.track_number, a.artwork,
+                   a.compilation, a.year, a.release_date, a.original_date
+            FROM album a
+                left join artists art on a.artist_id = art.id
+            WHERE art.name = ?
+            ORDER BY {ALBUM_CHRONOLOGY}
+            "
+        ))?;
+        let albums = stmt
+            .query_map([name], album_from_row)?
+            .collect::<rusqlite::Result<Vec<Album>>>()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "
+            SELECT DISTINCT a.id, a.name, art.name as artist, a.disc_number, a.track_number,
+                   a.artwork, a.compilation, a.year, a.release_date, a.original_date
+            FROM album a
+                join album_tracks at on at.album_id = a.id
+                join track_artists ta on ta.track_id = at.track_id
+                join artists credited on ta.artist_id = credited.id
+                left join artists art on a.artist_id = art.id
+            WHERE credited.name = ?1 and ta.role in ('primary', 'featured')
+              and a.artist_id IS NOT credited.id
+            ORDER BY {ALBUM_CHRONOLOGY}
+            "
+        ))?;
+        let appears_on = stmt
+            .query_map([name], album_from_row)?
+            .collect::<rusqlite::Result<Vec<Album>>>()?;
+
+        let mut stmt = conn.prepare(
+            "
+            SELECT t.id as track_id, t.name, a.name as artist, s.artwork
+            FROM single s
+                left join track t on s.track_id = t.id
+                left join artists a on t.artist_id = a.id
+            WHERE a.name = ?
+            ORDER BY t.name
+            ",
+        )?;
+        let singles = stmt
+            .query_map([name], |row| {
+                Ok(Single {
+                    track_id: row.get::<_, Option<u32>>("track_id")?.unwrap_or(0),
+                    title: row.get::<_, Option<String>>("name")?.unwrap_or_default(),
+                    artist: row.get::<_, Option<String>>("artist")?.unwrap_or_default(),
+                    cover: row.get("artwork")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Single>>>()?;
+
+        let mut stmt = conn.prepare(
+            "
+            SELECT track.id, track.name, track.path, art.name as artist, a.name as album_title,
+                   coalesce(a.artwork, s.artwork) as artwork, at.track_number, at.disc_number,
+                   track.work, track.opus, track.movement_name, track.movement_number,
+                   track.duration, track.codec, track.sample_rate, track.bit_depth,
+                   track.channels, track.bitrate, track.size, track.track_gain,
+                   track.track_peak, track.album_gain, track.album_peak, track.loudness,
+                   track.true_peak, a.loudness as album_loudness,
+                   a.true_peak as album_true_peak, ta.role
+            FROM track_artists ta
+                join artists credited on ta.artist_id = credited.id
+                join track on ta.track_id = track.id
+                left join album_tracks at on track.id = at.track_id
+                left join artists art on track.artist_id = art.id
+                left join album a on at.album_id = a.id
+                left join single s on s.track_id = track.id
+            WHERE credited.name = ?
+            ORDER BY a.name, at.disc_number, at.track_number, track.name, ta.position
+            ",
+        )?;
+        let mut rows = stmt.query([name])?;
+        let mut credits: Vec<Credit> = vec![];
+        while let Some(row) = rows.next()? {
+            let track = track_from_row(row)?;
+            let Ok(role) = row.get::<_, String>("role")?.parse::<ArtistRole>() else {
+                continue;
+            };
+
+            // Someone credited twice on a track, like a composer performing it, is listed once
+            match credits.last_mut() {
+                Some(credit) if credit.track.id == track.id => credit.roles.push(role),
+                _ => credits.push(Credit {
+                    track,
+                    roles: vec![role],
+                }),
+            }
+        }
+
+        Ok(ArtistPage {
+            name: name.to_string(),
+            picture,
+            albums,
+            singles,
+            appears_on,
+            credits,
+        })
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Composer {
+    pub name: String,
+    pub picture: Option<Vec<u8>>,
+    pub track_count: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Artist {
+    pub name: String,
+    pub picture: Option<Vec<u8>>,
+}
+
+/// Artists with an album, a single or a credit on any track. Featured artists have credits of
+/// their own, so names are listed as they are.
+pub fn artists() -> Result<Vec<Artist>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(
+            "
+            SELECT artists.name, artists.artistpfp
+            FROM artists
+            WHERE exists(SELECT * FROM album WHERE album.artist_id = artists.id)
+               OR exists(SELECT *
+                         FROM single join track on single.track_id = track.id
+                         WHERE track.artist_id = artists.id)
+               OR exists(SELECT * FROM track_artists WHERE track_artists.artist_id = artists.id)
+            ",
+        )?;
+        let artists = stmt
+            .query_map([], |row| {
+                Ok(Artist {
+                    name: row.get("name")?,
+                    picture: row.get("artistpfp")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Artist>>>()?;
+        Ok(artists)
+    })
+}
+
+/// Artists credited as the composer of at least one track
+pub fn composers() -> Result<Vec<Composer>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(
+            "
+            SELECT art.name, art.artistpfp, count(DISTINCT ta.track_id) as track_count
+            FROM track_artists ta
+                join artists art on ta.artist_id = art.id
+            WHERE ta.role = 'composer'
+            GROUP BY art.id
+            ORDER BY art.name
+            ",
+        )?;
+        let composers = stmt
+            .query_map([], |row| {
+                Ok(Composer {
+                    name: row.get("name")?,
+                    picture: row.get("artistpfp")?,
+                    track_count: row.get("track_count")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Composer>>>()?;
+        Ok(composers)
+    })
+}
+
+/// Movements of one recording of a work, a track without a work tag is a work of its own
+#[derive(Debug, Clone)]
+pub struct Work {
+    pub title: String,
+    pub opus: Option<String>,
+    pub album_title: String,
+    pub movements: Vec<Track>,
+}
+
+/// Title of a work with its opus number, unless the title already has it
+pub fn work_title(work: &str, opus: Option<&str>) -> String {
+    match opus {
+        Some(opus) if !opus.is_empty() && !work.contains(opus) => format!("{}, {}", work, opus),
+        _ => work.to_string(),
+    }
+}
+
+/// Works of a composer by title, recordings of the same work on different albums are kept apart
+pub fn works_by_composer(name: &str) -> Result<Vec<Work>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "
+            {TRACK_QUERY}
+                join track_artists ta on ta.track_id = track.id and ta.role = 'composer'
+                join artists composer on ta.artist_id = composer.id
+            WHERE composer.name = ?
+            ORDER BY coalesce(track.work, track.name), a.name, track.movement_number,
+                     at.disc_number, at.track_number
+            "
+        ))?;
+        let tracks = stmt
+            .query_map([name], track_from_row)?
+            .collect::<rusqlite::Result<Vec<Track>>>()?;
+
+        let mut works: Vec<Work> = vec![];
+        for track in tracks {
+            match (works.last_mut(), &track.work) {
+                (Some(work), Some(title))
+                    if work.title == *title && work.album_title == track.album_title =>
+                {
+                    work.movements.push(track)
+                }
+                _ => works.push(Work {
+                    title: track.work.clone().unwrap_or_else(|| track.title.clone()),
+                    opus: track.opus.clone(),
+                    album_title: track.album_title.clone(),
+                    movements: vec![track],
+                }),
+            }
+        }
+
+        Ok(works)
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Genre {
+    pub name: String,
+    pub track_count: u32,
+    pub album_count: u32,
+    /// Of one of its albums, hash in [`crate::artwork`]
+    pub cover: Option<String>,
+}
+
+/// Every genre paired with itself and each genre below it. `UNION` drops rows it already has,
+/// so genres listed under each other do not recurse forever.
+const GENRE_TREE: &str = "
+    WITH RECURSIVE genre_tree(root_id, genre_id) AS (
+        SELECT id, id FROM genres
+        UNION
+        SELECT genre_tree.root_id, g.id
+        FROM genres g join genre_tree on g.parent_id = genre_tree.genre_id
+    )
+";
+
+/// Genres at least one track is tagged with, by name. Counts include the genres listed under
+/// them.
+pub fn genres() -> Result<Vec<Genre>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "
+            {GENRE_TREE}
+            SELECT g.name, count(DISTINCT tg.track_id) as track_count,
+                   count(DISTINCT at.album_id) as album_count, max(a.artwork) as artwork
+            FROM genres g
+                join genre_tree gt on gt.root_id = g.id
+                join track_genres tg on tg.genre_id = gt.genre_id
+                left join album_tracks at on at.track_id = tg.track_id
+                left join album a on at.album_id = a.id
+            GROUP BY g.id
+            ORDER BY g.name COLLATE NOCASE
+            "
+        ))?;
+        let genres = stmt
+            .query_map([], |row| {
+                Ok(Genre {
+                    name: row.get("name")?,
+                    track_count: row.get("track_count")?,
+                    album_count: row.get("album_count")?,
+                    cover: row.get("artwork")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Genre>>>()?;
+        Ok(genres)
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct GenrePage {
+    pub name: String,
+    /// Albums with at least one track of the genre or a genre listed under it
+    pub albums: Vec<Album>,
+    /// Album tracks in album order, then the ones that are not on an album
+    pub tracks: Vec<Track>,
+}
+
+pub fn genre_page(name: &str) -> Result<GenrePage, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "
+            {GENRE_TREE}
+            SELECT DISTINCT a.id, a.name, art.name as artist, a.disc_number, a.track_number,
+                   a.artwork, a.compilation, a.year, a.release_date, a.original_date
+            FROM album a
+                join album_tracks at on at.album_id = a.id
+                join track_genres tg on tg.track_id = at.track_id
+                join genre_tree gt on gt.genre_id = tg.genre_id
+                join genres g on gt.root_id = g.id
+                left join artists art on a.artist_id = art.id
+            WHERE g.name = ?
+            ORDER BY {ALBUM_CHRONOLOGY}
+            "
+        ))?;
+        let albums = stmt
+            .query_map([name], album_from_row)?
+            .collect::<rusqlite::Result<Vec<Album>>>()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "
+            {GENRE_TREE}
+            {TRACK_QUERY}
+            WHERE track.id IN (SELECT tg.track_id
+                               FROM track_genres tg
+                                   join genre_tree gt on gt.genre_id = tg.genre_id
+                                   join genres g on gt.root_id = g.id
+                               WHERE g.name = ?)
+            ORDER BY at.album_id IS NULL, {ALBUM_CHRONOLOGY}, at.disc_number, at.track_number,
+                     track.name
+            "
+        ))?;
+        let tracks = stmt
+            .query_map([name], track_from_row)?
+            .collect::<rusqlite::Result<Vec<Track>>>()?;
+
+        Ok(GenrePage {
+            name: name.to_string(),
+            albums,
+            tracks,
+        })
+    })
+}
+
+/// What a track search looks through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Composer,
+    Lyrics,
+}
+
+impl SearchField {
+    pub const ALL: [SearchField; 6] = [
+        SearchField::Title,
+        SearchField::Artist,
+        SearchField::Album,
+        SearchField::Genre,
+        SearchField::Composer,
+        SearchField::Lyrics,
+    ];
+
+    /// Of `track_search`
+    fn column(&self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Artist => "artist",
+            SearchField::Album => "album",
+            SearchField::Genre => "genre",
+            SearchField::Composer => "composer",
+            SearchField::Lyrics => "lyrics",
+        }
+    }
+}
+
+/// Corrections tried for a word the index does not have
+const MAX_CORRECTIONS: usize = 8;
+
+/// `text` as a full-text string, quoted so nothing typed is read as query syntax. Unless it was
+/// a phrase, it only has to start a word. `None` when there is nothing to search for.
+fn fts_string(text: &Text) -> Option<String> {
+    let quote = |value: &str| format!("\"{}\"", value.replace('"', "\"\""));
+    if !text.searchable() {
+        return None;
+    }
+
+    let typed = match text.phrase {
+        true => quote(&text.value),
+        false => format!("{}*", quote(&text.value)),
+    };
+    match text.alternatives.is_empty() {
+        true => Some(typed),
+        false => Some(format!(
+            "({typed} OR {})",
+            text.alternatives
+                .iter()
+                .map(|word| quote(word))
+                .collect::<Vec<String>>()
+                .join(" OR ")
+        )),
+    }
+}
+
+/// For each term of `query`, the indexed words a few typos away when `index` does not have the
+/// word that was typed, closest first. Phrases and what is left out are taken as typed.
+fn corrections(
+    conn: &Connection,
+    query: &Query,
+    index: &str,
+) -> Result<Vec<Vec<(String, usize)>>, LibraryError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT term FROM {index}_vocab WHERE term >= ? AND term < ?"
+    ))?;
+
+    query
+        .terms
+        .iter()
+        .map(|term| {
+            let text = match &term.filter {
+                Filter::Text(text) | Filter::Field(_, text) if !term.negated && !text.phrase => {
+                    text
+                }
+                _ => return Ok(vec![]),
+            };
+            let word = match fuzzy::words(&text.value).as_slice() {
+                [word] if word.chars().any(char::is_alphabetic) => word.clone(),
+                _ => return Ok(vec![]),
+            };
+
+            // A typo in the first letter is rare enough to only look through the words it starts
+            let Some(first) = word.chars().next() else {
+                return Ok(vec![]);
+            };
+            let next = char::from_u32(first as u32 + 1).unwrap_or(char::MAX);
+            let terms = stmt
+                .query_map((first.to_string(), next.to_string()), |row| {
+                    row.get::<_, String>(0)
+                })?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            if terms.iter().any(|term| term.starts_with(&word)) {
+                return Ok(vec![]);
+            }
+
+            let mut found = terms
+                .into_iter()
+                .filter_map(|term| fuzzy::typos(&word, &term).map(|typos| (term, typos)))
+                .collect::<Vec<(String, usize)>>();
+            found.sort_by_key(|(_, typos)| *typos);
+            found.truncate(MAX_CORRECTIONS);
+            Ok(found)
+        })
+        .collect()
+}
+
+/// Runs `search` for `query`, then again with the words `index` does not have swapped for the
+/// indexed words one typo away, then two. What needed fewer typos to be found comes first.
+fn search_with_typos<T: Eq + Hash + Clone>(
+    conn: &Connection,
+    query: &Query,
+    index: &str,
+    mut search: impl FnMut(&Query) -> Result<Vec<T>, LibraryError>,
+) -> Result<Vec<T>, LibraryError> {
+    let mut found = search(query)?;
+    let mut seen = found.iter().cloned().collect::<HashSet<T>>();
+    let corrections = corrections(conn, query, index)?;
+
+    for typos in 1..=fuzzy::MAX_TYPOS {
+        if !corrections
+            .iter()
+            .flatten()
+            .any(|(_, count)| *count == typos)
+        {
+            continue;
+        }
+
+        let terms = query
+            .terms
+            .iter()
+            .zip(&corrections)
+            .map(|(term, corrections)| {
+                let mut term = term.clone();
+                if let Filter::Text(text) | Filter::Field(_, text) = &mut term.filter {
+                    text.alternatives = corrections
+                        .iter()
+                        .filter(|(_, count)| *count <= typos)
+                        .map(|(word, _)| word.clone())
+                        .collect();
+                }
+                term
+            })
+            .collect();
+        for item in search(&Query { terms })? {
+            if seen.insert(item.clone()) {
+                found.push(item);
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// SQL for the rows of `table` matching `query`, looked up in its full-text index `index` with
+/// free text in `columns`. Other filters are on `track`.
+struct SearchFilter {
+    from: String,
+    conditions: String,
+    /// Ranked by `found.rank`, otherwise there was no text to look for
+    ranked: bool,
+    params: Vec<Value>,
+}
+
+fn search_filter(
+    query: &Query,
+    table: &str,
+    index: &str,
+    columns: &[&str],
+) -> Option<SearchFilter> {
+    let mut matches = vec![];
+    let mut conditions = vec![];
+    let mut params = vec![];
+
+    for term in &query.terms {
+        let condition = match &term.filter {
+            Filter::Text(text) | Filter::Field(_, text) => {
+                let Some(string) = fts_string(text) else {
+                    continue;
+                };
+                let string = match &term.filter {
+                    Filter::Field(field, _) => format!("{} : {string}", field.column()),
+                    _ if columns.is_empty() => continue,
+                    _ => format!("{{{}}} : {string}", columns.join(" ")),
+                };
+                if !term.negated {
+                    matches.push(string);
+                    continue;
+                }
+                params.push(Value::Text(string));
+                format!("{table}.id IN (SELECT rowid FROM {index} WHERE {index} MATCH ?)")
+            }
+            Filter::Year(range) => format!(
+                "track.id IN (SELECT at.track_id
+                    FROM album_tracks at join album al on al.id = at.album_id
+                    WHERE {})",
+                range_condition("al.year", range, &mut params)
+            ),
+            Filter::Duration(range) => range_condition("track.duration", range, &mut params),
+            Filter::Bitrate(range) => range_condition("track.bitrate", range, &mut params),
+            Filter::Format(format) => {
+                let extension = format
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                params.push(Value::Text(format.clone()));
+                params.push(Value::Text(format!("%.{extension}")));
+                "(lower(track.codec) = ? OR track.path LIKE ? ESCAPE '\\')".to_string()
+            }
+        };
+        conditions.push(match term.negated {
+            // Rows without the value are left in too
+            true => format!("({condition}) IS NOT TRUE"),
+            false => condition,
+        });
+    }
+
+    if matches.is_empty() && conditions.is_empty() {
+        return None;
+    }
+
+    let from = match matches.is_empty() {
+        true => format!("FROM {table}"),
+        false => {
+            params.insert(0, Value::Text(matches.join(" AND ")));
+            format!(
+                "FROM (SELECT rowid, rank FROM {index} WHERE {index} MATCH ?) found
+                    join {table} on {table}.id = found.rowid"
+            )
+        }
+    };
+    Some(SearchFilter {
+        from,
+        conditions: match conditions.is_empty() {
+            true => "1".to_string(),
+            false => conditions.join(" AND "),
+        },
+        ranked: !matches.is_empty(),
+        params,
+    })
+}
+
+/// `column` within `range`, its bounds are added to `params`
+fn range_condition(column: &str, range: &NumberRange, params: &mut Vec<Value>) -> String {
+    let mut bounds = vec![];
+    for (bound, included, excluded) in [(range.min, ">=", ">"), (range.max, "<=", "<")] {
+        let (operator, value) = match bound {
+            Bound::Included(value) => (included, value),
+            Bound::Excluded(value) => (excluded, value),
+            Bound::Unbounded => continue,
+        };
+        params.push(Value::Real(value));
+        bounds.push(format!("{column} {operator} ?"));
+    }
+
+    match bounds.is_empty() {
+        true => format!("{column} IS NOT NULL"),
+        false => bounds.join(" AND "),
+    }
+}
+
+/// Ids of the tracks matching `query`, free text is looked for across `fields`. Best match
+/// first when there is text to rank by.
+pub fn search_tracks(query: &Query, fields: &[SearchField]) -> Result<Vec<u32>, LibraryError> {
+    let columns = fields
+        .iter()
+        .map(SearchField::column)
+        .collect::<Vec<&str>>();
+
+    with_connection(|conn| {
+        search_with_typos(conn, query, "track_search", |query| {
+            let Some(filter) = search_filter(query, "track", "track_search", &columns) else {
+                return Ok(vec![]);
+            };
+            let mut stmt = conn.prepare(&format!(
+                "SELECT track.id {} WHERE {} ORDER BY {}",
+                filter.from,
+                filter.conditions,
+                match filter.ranked {
+                    true => "found.rank",
+                    false => "track.id",
+                }
+            ))?;
+            let ids = stmt
+                .query_map(params_from_iter(filter.params), |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<u32>>>()?;
+            Ok(ids)
+        })
+    })
+}
+
+/// Ids of the albums with a track matching `query`, free text is looked for in album titles and
+/// artists. Ranked by their best track.
+pub fn search_albums(query: &Query) -> Result<Vec<u32>, LibraryError> {
+    with_connection(|conn| {
+        search_with_typos(conn, query, "track_search", |query| {
+            let Some(filter) = search_filter(query, "track", "track_search", &["album", "artist"])
+            else {
+                return Ok(vec![]);
+            };
+            let mut stmt = conn.prepare(&format!(
+                "
+                SELECT album_tracks.album_id
+                {} join album_tracks on album_tracks.track_id = track.id
+                WHERE {}
+                GROUP BY album_tracks.album_id
+                ORDER BY {}
+                ",
+                filter.from,
+                filter.conditions,
+                match filter.ranked {
+                    true => "min(found.rank)",
+                    false => "album_tracks.album_id",
+                }
+            ))?;
+            let ids = stmt
+                .query_map(params_from_iter(filter.params), |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<u32>>>()?;
+            Ok(ids)
+        })
+    })
+}
+
+/// Names of the artists matching `query`. Free text and `artist:` are looked for in their
+/// names, the other filters keep the artists credited on a track matching them.
+pub fn search_artists(query: &Query) -> Result<Vec<String>, LibraryError> {
+    let (name_terms, track_terms) = query.terms.iter().cloned().partition::<Vec<_>, _>(|term| {
+        matches!(
+            term.filter,
+            Filter::Text(_) | Filter::Field(SearchField::Artist, _)
+        )
+    });
+    let name_terms = name_terms
+        .into_iter()
+        .map(|mut term| {
+            if let Filter::Field(_, text) = term.filter {
+                term.filter = Filter::Text(text);
+            }
+            term
+        })
+        .collect();
+
+    let tracks = search_filter(&Query { terms: track_terms }, "track", "track_search", &[]);
+
+    with_connection(|conn| {
+        search_with_typos(
+            conn,
+            &Query { terms: name_terms },
+            "artist_search",
+            |query| {
+                let names = search_filter(query, "artists", "artist_search", &["name"]);
+                if names.is_none() && tracks.is_none() {
+                    return Ok(vec![]);
+                }
+
+                let mut params = vec![];
+                let mut sql = match &names {
+                    Some(names) => {
+                        params.extend(names.params.iter().cloned());
+                        format!(
+                            "SELECT artists.name {} WHERE {}",
+                            names.from, names.conditions
+                        )
+                    }
+                    None => "SELECT artists.name FROM artists WHERE 1".to_string(),
+                };
+                if let Some(tracks) = &tracks {
+                    params.extend(tracks.params.iter().cloned());
+                    sql.push_str(&format!(
+                        " AND artists.id IN (SELECT track_artists.artist_id FROM track_artists
+                            WHERE track_artists.track_id IN (SELECT track.id {} WHERE {}))",
+                        tracks.from, tracks.conditions
+                    ));
+                }
+                sql.push_str(match names.is_some_and(|names| names.ranked) {
+                    true => " ORDER BY found.rank",
+                    false => " ORDER BY artists.name",
+                });
+
+                let mut stmt = conn.prepare(&sql)?;
+                let names = stmt
+                    .query_map(params_from_iter(params), |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                Ok(names)
+            },
+        )
+    })
+}
+
+/// Names of the genres matching `input`, best match first
+pub fn search_genres(input: &str) -> Result<Vec<String>, LibraryError> {
+    with_connection(|conn| {
+        search_with_typos(conn, &Query::text(input), "genre_search", |query| {
+            let Some(filter) = search_filter(query, "genres", "genre_search", &["name"]) else {
+                return Ok(vec![]);
+            };
+            let mut stmt = conn.prepare(&format!(
+                "SELECT genres.name {} WHERE {} ORDER BY found.rank",
+                filter.from, filter.conditions
+            ))?;
+            let names = stmt
+                .query_map(params_from_iter(filter.params), |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(names)
+        })
+    })
+}
+
+/// Every track in the library, for the Tracks page
+pub fn tracks() -> Result<Vec<Track>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare(TRACK_QUERY)?;
+        let tracks = stmt
+            .query_map([], track_from_row)?
+            .collect::<rusqlite::Result<Vec<Track>>>()?;
+        Ok(tracks)
+    })
+}
+
+pub fn track_by_id(id: u32) -> Result<Track, LibraryError> {
+    with_connection(|conn| {
+        conn.query_row(
+            &format!("{TRACK_QUERY} WHERE track.id = ?"),
+            [id],
+            track_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| LibraryError::TrackNotFound(id.to_string()))
+    })
+}
+
+pub fn track_by_path(path: &str) -> Result<Track, LibraryError> {
+    with_connection(|conn| {
+        conn.query_row(
+            &format!("{TRACK_QUERY} WHERE track.path = ?"),
+            [path],
+            track_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| LibraryError::TrackNotFound(path.to_string()))
+    })
+}
+
+/// Lyrics are only needed for the track playing, so they are not part of `Track`
+pub fn lyrics(track_id: u32) -> Result<Option<String>, LibraryError> {
+    with_connection(|conn| {
+        Ok(conn
+            .query_row("SELECT lyrics FROM track WHERE id = ?", [track_id], |row| {
+                row.get::<_, Option<String>>("lyrics")
+            })
+            .optional()?
+            .flatten())
+    })
+}
+
+pub fn set_artist_picture(name: &str, picture: Vec<u8>) -> Result<(), LibraryError> {
+    with_connection(|conn| {
+        match conn.execute(
+            "UPDATE artists SET artistpfp = ? WHERE name = ?",
+            (picture, name),
+        )? {
+            0 => Err(LibraryError::ArtistNotFound(name.to_string())),
+            _ => Ok(()),
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanProblem {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Files the last scans could not add, or added without some of their tags
+pub fn scan_problems() -> Result<Vec<ScanProblem>, LibraryError> {
+    with_connection(|conn| {
+        let mut stmt = conn.prepare("SELECT path, reason FROM scan_errors ORDER BY path, id")?;
+        let problems = stmt
+            .query_map([], |row| {
+                Ok(ScanProblem {
+                    path: PathBuf::from(row.get::<_, String>("path")?),
+                    reason: row.get("reason")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<ScanProblem>>>()?;
+        Ok(problems)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate_database;
+
+    #[test]
+    fn misspelled_words_are_corrected_to_indexed_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate_database(&conn).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO track_search (rowid, title, artist) VALUES
+                (1, 'Yellow Submarine', 'The Beatles'),
+                (2, 'Déjà Vu', 'Crosby, Stills, Nash & Young');
+            ",
+        )
+        .unwrap();
+
+        let corrections = |input: &str| {
+            corrections(&conn, &Query::parse(input).unwrap(), "track_search").unwrap()
+        };
+        let found = |word: &str, typos: usize| vec![(word.to_string(), typos)];
+
+        assert_eq!(
+            corrections("beatels subamrine dejs"),
+            [found("beatles", 1), found("submarine", 1), found("deja", 1)]
+        );
+        // Words that are indexed, or start one, are taken as typed
+        assert_eq!(corrections("beat yellow"), [vec![], vec![]]);
+        assert_eq!(
+            corrections("-beatels \"beatels\" title:beatels"),
+            [vec![], vec![], found("beatles", 1)]
+        );
+        // So is a typo in the first letter
+        assert_eq!(corrections("veatles"), [vec![]]);
+    }
+}
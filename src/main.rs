@@ -3,11 +3,15 @@
 use crate::log::setup_logger;
 
 mod app;
+mod artwork;
 mod config;
 mod database;
+mod fuzzy;
 mod i18n;
+mod library;
 mod log;
 mod mpris;
+mod query;
 
 fn main() -> cosmic::iced::Result {
     //start logging
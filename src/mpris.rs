@@ -1 +1,546 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
+
+//! MPRIS2 server that exposes playback to desktop media widgets, playerctl and lock screens.
+
+use crate::app::{AppModel, LoopState, Message};
+use crate::database::find_visual;
+use crate::{artwork, library};
+use cosmic::iced::futures::channel::mpsc::Sender;
+use cosmic::Application;
+use futures_util::SinkExt;
+use rodio::Sink;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.NovaMusic";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// What the player is doing, as far as MPRIS clients are concerned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerState {
+    pub status: PlaybackStatus,
+    pub loop_state: LoopState,
+    pub volume: f64,
+    pub track: Option<TrackMetadata>,
+    pub has_next: bool,
+    pub has_previous: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackMetadata {
+    pub id: u32,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub path: PathBuf,
+    pub length: Option<f64>,
+    /// Filled in off the bus executor once the cover is stored, property getters only read it
+    pub art_url: Option<String>,
+}
+
+/// Handle the app keeps once the server is up, used to push state changes to clients
+#[derive(Debug, Clone)]
+pub struct MprisHandle {
+    connection: zbus::Connection,
+    state: Arc<Mutex<PlayerState>>,
+}
+
+impl MprisHandle {
+    /// Stores the new state and emits `PropertiesChanged` for whatever differs from the old one
+    pub fn update(&self, mut new: PlayerState) {
+        let old = {
+            let mut state = self.state.lock().unwrap();
+            if let (Some(track), Some(current)) = (&mut new.track, &state.track) {
+                if track.path == current.path {
+                    track.art_url = current.art_url.clone();
+                }
+            }
+            if *state == new {
+                return;
+            }
+            std::mem::replace(&mut *state, new.clone())
+        };
+
+        let track_changed = new.track.as_ref().map(|track| &track.path)
+            != old.track.as_ref().map(|track| &track.path);
+        if let Some(track) = new.track.as_ref().filter(|_| track_changed) {
+            self.load_art_url(track.path.clone());
+        }
+
+        self.notify(old, new);
+    }
+
+    /// Stores the cover of a track that just started on its own thread, as it reads the
+    /// database and the file and may encode images, then lets clients know about it
+    fn load_art_url(&self, path: PathBuf) {
+        let handle = self.clone();
+        std::thread::spawn(move || {
+            let Some(url) = cover_art_url(&path) else {
+                return;
+            };
+
+            let (old, new) = {
+                let mut state = handle.state.lock().unwrap();
+                let old = state.clone();
+                match &mut state.track {
+                    Some(track) if track.path == path => track.art_url = Some(url),
+                    // Another track started in the meantime
+                    _ => return,
+                }
+                (old, state.clone())
+            };
+
+            handle.notify(old, new);
+        });
+    }
+
+    fn notify(&self, old: PlayerState, new: PlayerState) {
+        let connection = self.connection.clone();
+        self.connection
+            .executor()
+            .spawn(
+                async move {
+                    if let Err(err) = notify_changes(&connection, &old, &new).await {
+                        log::warn!("Failed to notify MPRIS clients: {}", err);
+                    }
+                },
+                "mpris-properties-changed",
+            )
+            .detach();
+    }
+
+    /// Lets clients know the position jumped instead of progressing normally
+    pub fn seeked(&self, position: Duration) {
+        let connection = self.connection.clone();
+        self.connection
+            .executor()
+            .spawn(
+                async move {
+                    let emitter = match SignalEmitter::new(&connection, OBJECT_PATH) {
+                        Ok(emitter) => emitter,
+                        Err(err) => {
+                            log::warn!("Failed to emit Seeked: {}", err);
+                            return;
+                        }
+                    };
+                    if let Err(err) = Player::seeked(&emitter, position.as_micros() as i64).await {
+                        log::warn!("Failed to emit Seeked: {}", err);
+                    }
+                },
+                "mpris-seeked",
+            )
+            .detach();
+    }
+}
+
+/// Starts the server on the session bus, forwarding client requests as app messages
+pub fn subscription(
+    sink: Arc<Sink>,
+    state: Arc<Mutex<PlayerState>>,
+) -> cosmic::iced::Subscription<Message> {
+    struct MprisSubscription;
+
+    cosmic::iced::Subscription::run_with_id(
+        TypeId::of::<MprisSubscription>(),
+        cosmic::iced_futures::stream::channel(10, |mut tx| async move {
+            let root = Root;
+            let player = Player {
+                tx: tx.clone(),
+                sink,
+                state: Arc::clone(&state),
+            };
+
+            let connection = match zbus::connection::Builder::session()
+                .and_then(|builder| builder.name(BUS_NAME))
+                .and_then(|builder| builder.serve_at(OBJECT_PATH, root))
+                .and_then(|builder| builder.serve_at(OBJECT_PATH, player))
+            {
+                Ok(builder) => builder.build().await,
+                Err(err) => Err(err),
+            };
+
+            match connection {
+                Ok(connection) => {
+                    log::info!("MPRIS server running as {}", BUS_NAME);
+                    let _ = tx
+                        .send(Message::MprisReady(MprisHandle { connection, state }))
+                        .await;
+                }
+                Err(err) => {
+                    log::error!("Could not start MPRIS server: {}", err);
+                }
+            }
+
+            // The connection lives in the app from here on, keep the subscription alive with it
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
+async fn notify_changes(
+    connection: &zbus::Connection,
+    old: &PlayerState,
+    new: &PlayerState,
+) -> zbus::Result<()> {
+    let iface = connection
+        .object_server()
+        .interface::<_, Player>(OBJECT_PATH)
+        .await?;
+    let emitter = iface.signal_emitter();
+    let player = iface.get().await;
+
+    if old.status != new.status {
+        player.playback_status_changed(emitter).await?;
+    }
+    if old.track != new.track {
+        player.metadata_changed(emitter).await?;
+    }
+    if old.loop_state != new.loop_state {
+        player.loop_status_changed(emitter).await?;
+        player.shuffle_changed(emitter).await?;
+    }
+    if old.volume != new.volume {
+        player.volume_changed(emitter).await?;
+    }
+    if old.has_next != new.has_next {
+        player.can_go_next_changed(emitter).await?;
+    }
+    if old.has_previous != new.has_previous {
+        player.can_go_previous_changed(emitter).await?;
+    }
+    if old.track.is_some() != new.track.is_some() {
+        player.can_play_changed(emitter).await?;
+        player.can_pause_changed(emitter).await?;
+        player.can_seek_changed(emitter).await?;
+    }
+
+    Ok(())
+}
+
+/// Url of the largest stored copy of a track's cover, the album cover the library picked or
+/// else the embedded one
+fn cover_art_url(path: &Path) -> Option<String> {
+    let hash = match library::track_by_path(&path.to_string_lossy())
+        .ok()
+        .and_then(|track| track.cover)
+    {
+        Some(hash) => hash,
+        None => artwork::store(&find_visual(path)?)?,
+    };
+
+    Some(format!(
+        "file://{}",
+        artwork::path(&hash, artwork::LARGEST)?.to_string_lossy()
+    ))
+}
+
+fn track_object_path(track: &Option<TrackMetadata>) -> OwnedObjectPath {
+    let path = match track {
+        Some(track) => format!("/dev/lunarsrl/NovaMusic/Track/{}", track.id),
+        None => NO_TRACK.to_string(),
+    };
+
+    ObjectPath::try_from(path)
+        .unwrap_or_else(|_| ObjectPath::from_static_str_unchecked(NO_TRACK))
+        .into()
+}
+
+struct Root;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    async fn raise(&self) {}
+
+    async fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        crate::fl!("app-title")
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        AppModel::APP_ID.to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Player {
+    tx: Sender<Message>,
+    sink: Arc<Sink>,
+    state: Arc<Mutex<PlayerState>>,
+}
+
+impl Player {
+    async fn send(&self, message: Message) {
+        if let Err(err) = self.tx.clone().send(message).await {
+            log::warn!("MPRIS request dropped: {}", err);
+        }
+    }
+
+    fn state(&self) -> PlayerState {
+        self.state.lock().unwrap().clone()
+    }
+
+    async fn seek_to(&self, position: Duration) {
+        self.send(Message::SeekTrack(position.as_secs_f64())).await;
+        self.send(Message::SeekFinished).await;
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn next(&self) {
+        self.send(Message::SkipTrack).await;
+    }
+
+    async fn previous(&self) {
+        self.send(Message::PreviousTrack).await;
+    }
+
+    async fn pause(&self) {
+        if self.state().status == PlaybackStatus::Playing {
+            self.send(Message::PlayPause).await;
+        }
+    }
+
+    async fn play_pause(&self) {
+        if self.state().track.is_some() {
+            self.send(Message::PlayPause).await;
+        }
+    }
+
+    async fn stop(&self) {
+        self.pause().await;
+        self.seek_to(Duration::ZERO).await;
+    }
+
+    async fn play(&self) {
+        if self.state().status == PlaybackStatus::Paused {
+            self.send(Message::PlayPause).await;
+        }
+    }
+
+    async fn seek(&self, offset: i64) {
+        let state = self.state();
+        let Some(track) = state.track else {
+            return;
+        };
+
+        let position = self.sink.get_pos().as_micros() as i64 + offset;
+        let length = track.length.map(|val| (val * 1_000_000.0) as i64);
+
+        match length {
+            Some(length) if position > length => self.send(Message::SkipTrack).await,
+            _ => {
+                self.seek_to(Duration::from_micros(position.max(0) as u64))
+                    .await
+            }
+        }
+    }
+
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let state = self.state();
+        if track_id.as_str() != track_object_path(&state.track).as_str() || position < 0 {
+            return;
+        }
+
+        if let Some(length) = state.track.and_then(|track| track.length) {
+            if position > (length * 1_000_000.0) as i64 {
+                return;
+            }
+        }
+
+        self.seek_to(Duration::from_micros(position as u64)).await;
+    }
+
+    async fn open_uri(&self, _uri: String) {}
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        match self.state().status {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+        .to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        match self.state().loop_state {
+            LoopState::LoopingTrack => "Track",
+            LoopState::LoopingQueue => "Playlist",
+            LoopState::NotLooping | LoopState::RandomShuffle => "None",
+        }
+        .to_string()
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&mut self, value: String) {
+        let loop_state = match value.as_str() {
+            "Track" => LoopState::LoopingTrack,
+            "Playlist" => LoopState::LoopingQueue,
+            _ => LoopState::NotLooping,
+        };
+        self.send(Message::SetLoopState(loop_state)).await;
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn set_rate(&mut self, _value: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.state().loop_state == LoopState::RandomShuffle
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&mut self, value: bool) {
+        let current = self.state().loop_state;
+        let loop_state = match (value, current) {
+            (true, _) => LoopState::RandomShuffle,
+            (false, LoopState::RandomShuffle) => LoopState::NotLooping,
+            (false, other) => other,
+        };
+        self.send(Message::SetLoopState(loop_state)).await;
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let state = self.state();
+        let mut metadata = HashMap::new();
+
+        let mut insert = |key: &str, value: Value| {
+            if let Ok(value) = OwnedValue::try_from(value) {
+                metadata.insert(key.to_string(), value);
+            }
+        };
+
+        insert(
+            "mpris:trackid",
+            Value::from(track_object_path(&state.track)),
+        );
+
+        if let Some(track) = state.track {
+            insert("xesam:title", Value::from(track.title));
+            insert("xesam:artist", Value::from(vec![track.artist]));
+            insert("xesam:album", Value::from(track.album));
+            insert(
+                "xesam:url",
+                Value::from(format!("file://{}", track.path.to_string_lossy())),
+            );
+            if let Some(length) = track.length {
+                insert("mpris:length", Value::from((length * 1_000_000.0) as i64));
+            }
+            if let Some(url) = track.art_url {
+                insert("mpris:artUrl", Value::from(url));
+            }
+        }
+
+        metadata
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state().volume
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&mut self, value: f64) {
+        self.send(Message::VolumeSliderChange(
+            (value.clamp(0.0, 1.0) * 100.0) as f32,
+        ))
+        .await;
+    }
+
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.sink.get_pos().as_micros() as i64
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.state().has_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.state().has_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,510 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! What is typed into the search fields of the library pages. Besides words to look for, a
+//! query can filter on fields, like `artist:"Miles Davis" year:1955..1960 -live duration:>10m`.
+//! [`crate::library`] turns it into SQL.
+
+use crate::library::SearchField;
+use crate::{fl, fuzzy};
+use std::fmt::{Display, Formatter};
+use std::ops::Bound;
+
+/// Names that can go before a `:`
+const FIELDS: [&str; 12] = [
+    "title", "artist", "album", "genre", "composer", "lyrics", "year", "duration", "length",
+    "format", "codec", "bitrate",
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+/// One space separated part of a query, `-` in front of it leaves out what it matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Looked for in whichever fields the page searches
+    Text(Text),
+    Field(SearchField, Text),
+    /// Of the album the track is on
+    Year(NumberRange),
+    /// In seconds
+    Duration(NumberRange),
+    /// In kbit/s
+    Bitrate(NumberRange),
+    /// Codec or file extension, lowercase
+    Format(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub value: String,
+    /// Quoted, so it has to match as a whole rather than as the start of a word
+    pub phrase: bool,
+    /// Indexed words it could have been meant as, when it is not in the index itself
+    pub alternatives: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberRange {
+    pub min: Bound<f64>,
+    pub max: Bound<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    UnclosedQuote,
+    MissingValue(String),
+    InvalidYear(String),
+    InvalidDuration(String),
+    InvalidBitrate(String),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            QueryError::UnclosedQuote => fl!("QueryUnclosedQuote"),
+            QueryError::MissingValue(field) => fl!("QueryMissingValue", field = field.as_str()),
+            QueryError::InvalidYear(value) => fl!("QueryInvalidYear", value = value.as_str()),
+            QueryError::InvalidDuration(value) => {
+                fl!("QueryInvalidDuration", value = value.as_str())
+            }
+            QueryError::InvalidBitrate(value) => {
+                fl!("QueryInvalidBitrate", value = value.as_str())
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let mut terms = vec![];
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let Some(&first) = chars.peek() else {
+                break;
+            };
+
+            let mut negated = false;
+            if first == '-' {
+                chars.next();
+                match chars.peek() {
+                    Some(c) if !c.is_whitespace() => negated = true,
+                    // A lone dash is just a word
+                    _ => {
+                        terms.push(Term {
+                            negated: false,
+                            filter: Filter::Text(Text {
+                                value: "-".to_string(),
+                                phrase: false,
+                                alternatives: vec![],
+                            }),
+                        });
+                        continue;
+                    }
+                }
+            }
+
+            if chars.next_if_eq(&'"').is_some() {
+                let value = quoted(&mut chars)?;
+                terms.push(Term {
+                    negated,
+                    filter: Filter::Text(Text {
+                        value,
+                        phrase: true,
+                        alternatives: vec![],
+                    }),
+                });
+                continue;
+            }
+
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ':') {
+                word.push(c);
+            }
+
+            // Only known names make a field, so times like 4:30 and titles like Re:Zero stay words
+            let field = word.to_lowercase();
+            if FIELDS.contains(&field.as_str()) && chars.next_if_eq(&':').is_some() {
+                let (value, phrase) = match chars.next_if_eq(&'"') {
+                    Some(_) => (quoted(&mut chars)?, true),
+                    None => (rest_of_word(&mut chars), false),
+                };
+                if value.trim().is_empty() {
+                    return Err(QueryError::MissingValue(word));
+                }
+                terms.push(Term {
+                    negated,
+                    filter: field_filter(&field, value, phrase)?,
+                });
+                continue;
+            }
+
+            word.push_str(&rest_of_word(&mut chars));
+            terms.push(Term {
+                negated,
+                filter: Filter::Text(Text {
+                    value: word,
+                    phrase: false,
+                    alternatives: vec![],
+                }),
+            });
+        }
+
+        Ok(Query { terms })
+    }
+
+    /// Every word of `input` as free text, for searches without filters. Split like the index
+    /// splits them, so each can be corrected on its own.
+    pub fn text(input: &str) -> Query {
+        Query {
+            terms: fuzzy::words(input)
+                .into_iter()
+                .map(|word| Term {
+                    negated: false,
+                    filter: Filter::Text(Text {
+                        value: word,
+                        phrase: false,
+                        alternatives: vec![],
+                    }),
+                })
+                .collect(),
+        }
+    }
+
+    /// Folded words that were looked for, to highlight them in what was found
+    pub fn words(&self) -> Vec<String> {
+        self.terms
+            .iter()
+            .filter(|term| !term.negated)
+            .filter_map(|term| match &term.filter {
+                Filter::Text(text) | Filter::Field(_, text) => Some(fuzzy::words(&text.value)),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Whether there is text to look for, to rank the results by how well they match it
+    pub fn ranked(&self) -> bool {
+        self.terms.iter().any(|term| match &term.filter {
+            Filter::Text(text) | Filter::Field(_, text) => !term.negated && text.searchable(),
+            _ => false,
+        })
+    }
+}
+
+impl Text {
+    /// Punctuation on its own is not indexed, so there is nothing to look for
+    pub fn searchable(&self) -> bool {
+        self.value.chars().any(char::is_alphanumeric)
+    }
+}
+
+/// Up to the closing quote, which is consumed
+fn quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, QueryError> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some(c) => value.push(c),
+            None => return Err(QueryError::UnclosedQuote),
+        }
+    }
+}
+
+fn rest_of_word(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+        value.push(c);
+    }
+    value
+}
+
+/// `field` is one of [`FIELDS`], in lowercase
+fn field_filter(field: &str, value: String, phrase: bool) -> Result<Filter, QueryError> {
+    let text = Text {
+        value,
+        phrase,
+        alternatives: vec![],
+    };
+    Ok(match field {
+        "title" => Filter::Field(SearchField::Title, text),
+        "artist" => Filter::Field(SearchField::Artist, text),
+        "album" => Filter::Field(SearchField::Album, text),
+        "genre" => Filter::Field(SearchField::Genre, text),
+        "composer" => Filter::Field(SearchField::Composer, text),
+        "lyrics" => Filter::Field(SearchField::Lyrics, text),
+        "year" => Filter::Year(
+            number_range(&text.value, |value| {
+                value.parse::<u16>().ok().map(f64::from)
+            })
+            .ok_or(QueryError::InvalidYear(text.value))?,
+        ),
+        "duration" | "length" => Filter::Duration(
+            number_range(&text.value, seconds).ok_or(QueryError::InvalidDuration(text.value))?,
+        ),
+        "bitrate" => Filter::Bitrate(
+            number_range(&text.value, kbits).ok_or(QueryError::InvalidBitrate(text.value))?,
+        ),
+        _ => Filter::Format(text.value.trim_start_matches('.').to_lowercase()),
+    })
+}
+
+/// `1955..1960`, `1955..`, `..1960`, `>10m`, `<=320` or a single value. A single value covers
+/// up to the next whole number, so `duration:4:30` finds every track between 4:30 and 4:31.
+fn number_range(value: &str, number: impl Fn(&str) -> Option<f64>) -> Option<NumberRange> {
+    if let Some((min, max)) = value.split_once("..") {
+        let bound = |value: &str| match value.is_empty() {
+            true => Some(Bound::Unbounded),
+            false => number(value).map(Bound::Included),
+        };
+        let (mut min, mut max) = (bound(min)?, bound(max)?);
+        // Written the wrong way round
+        if let (Bound::Included(low), Bound::Included(high)) = (min, max) {
+            if low > high {
+                (min, max) = (max, min);
+            }
+        }
+        return Some(NumberRange { min, max });
+    }
+
+    let range = if let Some(value) = value.strip_prefix(">=") {
+        NumberRange {
+            min: Bound::Included(number(value)?),
+            max: Bound::Unbounded,
+        }
+    } else if let Some(value) = value.strip_prefix("<=") {
+        NumberRange {
+            min: Bound::Unbounded,
+            max: Bound::Included(number(value)?),
+        }
+    } else if let Some(value) = value.strip_prefix('>') {
+        NumberRange {
+            min: Bound::Excluded(number(value)?),
+            max: Bound::Unbounded,
+        }
+    } else if let Some(value) = value.strip_prefix('<') {
+        NumberRange {
+            min: Bound::Unbounded,
+            max: Bound::Excluded(number(value)?),
+        }
+    } else {
+        let value = number(value.strip_prefix('=').unwrap_or(value))?;
+        NumberRange {
+            min: Bound::Included(value),
+            max: Bound::Excluded(value + 1.0),
+        }
+    };
+    Some(range)
+}
+
+/// `90`, `90s`, `10m`, `1h30m` or `4:30`
+fn seconds(value: &str) -> Option<f64> {
+    if value.is_empty() {
+        return None;
+    }
+
+    if value.contains(':') {
+        return value.split(':').try_fold(0.0, |total, part| {
+            part.parse::<u32>()
+                .ok()
+                .map(|part| total * 60.0 + f64::from(part))
+        });
+    }
+
+    let mut total = 0.0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c.to_ascii_lowercase() {
+            '0'..='9' | '.' => number.push(c),
+            unit @ ('h' | 'm' | 's') => {
+                let scale = match unit {
+                    'h' => 3600.0,
+                    'm' => 60.0,
+                    _ => 1.0,
+                };
+                total += number.parse::<f64>().ok()? * scale;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    match number.is_empty() {
+        true => Some(total),
+        false => Some(total + number.parse::<f64>().ok()?),
+    }
+}
+
+/// `320`, `320k` or `320kbps`
+fn kbits(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    let value = value
+        .strip_suffix("kbps")
+        .or_else(|| value.strip_suffix('k'))
+        .unwrap_or(&value);
+    value.parse::<u32>().ok().map(f64::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str, phrase: bool) -> Text {
+        Text {
+            value: value.to_string(),
+            phrase,
+            alternatives: vec![],
+        }
+    }
+
+    fn filters(input: &str) -> Vec<Filter> {
+        Query::parse(input)
+            .unwrap()
+            .terms
+            .into_iter()
+            .map(|term| term.filter)
+            .collect()
+    }
+
+    fn year(value: &str) -> Option<f64> {
+        value.parse::<u16>().ok().map(f64::from)
+    }
+
+    #[test]
+    fn words_with_a_colon_are_text() {
+        assert_eq!(
+            filters("Re:Zero"),
+            vec![Filter::Text(text("Re:Zero", false))]
+        );
+        assert_eq!(
+            filters("Halo: Reach"),
+            vec![
+                Filter::Text(text("Halo:", false)),
+                Filter::Text(text("Reach", false))
+            ]
+        );
+        assert_eq!(filters("Note:"), vec![Filter::Text(text("Note:", false))]);
+        assert_eq!(filters("4:30"), vec![Filter::Text(text("4:30", false))]);
+    }
+
+    #[test]
+    fn fields_phrases_and_negation() {
+        let query = Query::parse(r#"Artist:"Miles Davis" -live year:1955..1960"#).unwrap();
+        assert_eq!(
+            query.terms,
+            vec![
+                Term {
+                    negated: false,
+                    filter: Filter::Field(SearchField::Artist, text("Miles Davis", true)),
+                },
+                Term {
+                    negated: true,
+                    filter: Filter::Text(text("live", false)),
+                },
+                Term {
+                    negated: false,
+                    filter: Filter::Year(NumberRange {
+                        min: Bound::Included(1955.0),
+                        max: Bound::Included(1960.0),
+                    }),
+                },
+            ]
+        );
+        assert_eq!(
+            filters("- x"),
+            vec![
+                Filter::Text(text("-", false)),
+                Filter::Text(text("x", false))
+            ]
+        );
+        assert_eq!(
+            filters("format:.FLAC"),
+            vec![Filter::Format("flac".to_string())]
+        );
+    }
+
+    #[test]
+    fn missing_and_empty_values_are_errors() {
+        assert_eq!(
+            Query::parse("title:"),
+            Err(QueryError::MissingValue("title".to_string()))
+        );
+        assert_eq!(
+            Query::parse("duration:>"),
+            Err(QueryError::InvalidDuration(">".to_string()))
+        );
+        assert_eq!(
+            Query::parse("length:<"),
+            Err(QueryError::InvalidDuration("<".to_string()))
+        );
+        assert_eq!(
+            Query::parse("year:new"),
+            Err(QueryError::InvalidYear("new".to_string()))
+        );
+        assert_eq!(
+            Query::parse(r#"album:"Kind of"#),
+            Err(QueryError::UnclosedQuote)
+        );
+    }
+
+    #[test]
+    fn number_ranges() {
+        let range = |min, max| Some(NumberRange { min, max });
+
+        assert_eq!(
+            number_range("1960..1955", year),
+            range(Bound::Included(1955.0), Bound::Included(1960.0))
+        );
+        assert_eq!(
+            number_range("1955..", year),
+            range(Bound::Included(1955.0), Bound::Unbounded)
+        );
+        assert_eq!(
+            number_range("..1960", year),
+            range(Bound::Unbounded, Bound::Included(1960.0))
+        );
+        assert_eq!(
+            number_range(">=1955", year),
+            range(Bound::Included(1955.0), Bound::Unbounded)
+        );
+        assert_eq!(
+            number_range("<1960", year),
+            range(Bound::Unbounded, Bound::Excluded(1960.0))
+        );
+        assert_eq!(
+            number_range("=1958", year),
+            range(Bound::Included(1958.0), Bound::Excluded(1959.0))
+        );
+        assert_eq!(number_range(">", year), None);
+        assert_eq!(number_range("1955..later", year), None);
+    }
+
+    #[test]
+    fn lengths_and_bitrates() {
+        assert_eq!(seconds("90"), Some(90.0));
+        assert_eq!(seconds("90s"), Some(90.0));
+        assert_eq!(seconds("1.5m"), Some(90.0));
+        assert_eq!(seconds("1h30m"), Some(5400.0));
+        assert_eq!(seconds("4:30"), Some(270.0));
+        assert_eq!(seconds(""), None);
+        assert_eq!(seconds("4:"), None);
+        assert_eq!(seconds("10x"), None);
+
+        assert_eq!(kbits("320"), Some(320.0));
+        assert_eq!(kbits("320K"), Some(320.0));
+        assert_eq!(kbits("320kbps"), Some(320.0));
+        assert_eq!(kbits(""), None);
+    }
+}